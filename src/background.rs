@@ -0,0 +1,12 @@
+use crate::material::Color;
+use crate::vec::Vec3;
+
+pub mod gradient;
+pub mod environment;
+pub mod sky;
+
+/// What is seen in a direction when a ray leaves the scene without hitting anything
+pub trait Background: Send + Sync {
+    /// The light arriving from `direction`, in RGB or at the `wavelengths` of a spectral render
+    fn color(&self, direction: &Vec3, wavelengths: Option<&Vec3>) -> Color;
+}
@@ -0,0 +1,141 @@
+use std::f32::consts::PI;
+use rand::random;
+use crate::background::Background;
+use crate::distribution::Distribution2D;
+use crate::hdr::HdrImage;
+use crate::light::{Light, LightSample};
+use crate::material::Color;
+use crate::spectrum::emission;
+use crate::vec::Vec3;
+
+/// An equirectangular image surrounding the scene, with +z at the top row of the image.
+/// It is also a light, importance sampled by the brightness of its pixels
+pub struct Environment {
+    image: HdrImage,
+    /// Radians the image is turned about the z axis
+    rotation: f32,
+    intensity: f32,
+    distribution: Distribution2D
+}
+
+impl Environment {
+    /// `rotation` is in degrees about the z axis
+    pub fn new(image: HdrImage, rotation: f32, intensity: f32) -> Environment {
+        let width: usize = image.get_width();
+        let height: usize = image.get_height();
+        // rows near the poles cover less of the sphere so they are weighted down
+        let weights: Vec<f32> = image.get_pixels()
+            .iter()
+            .enumerate()
+            .map(|(i, pixel)| {
+                let theta: f32 = PI * ((i / width) as f32 + 0.5) / height as f32;
+                pixel.luminance() * theta.sin()
+            })
+            .collect();
+        Environment {
+            distribution: Distribution2D::new(&weights, width, height),
+            image,
+            rotation: rotation.to_radians(),
+            intensity
+        }
+    }
+
+    fn direction_to_uv(&self, direction: &Vec3) -> (f32, f32) {
+        // normalized exactly as the approximate normalization can carry directions over pixel edges
+        let direction: Vec3 = *direction / direction.get_length();
+        // unlike the arc cosine of z this stays precise at the poles, where the density is largest
+        let theta: f32 = (direction.x * direction.x + direction.y * direction.y).sqrt().atan2(direction.z);
+        let phi: f32 = (direction.y.atan2(direction.x) - self.rotation).rem_euclid(2.0 * PI);
+        (phi / (2.0 * PI), theta / PI)
+    }
+
+    fn uv_to_direction(&self, u: f32, v: f32) -> Vec3 {
+        let phi: f32 = u * 2.0 * PI + self.rotation;
+        let theta: f32 = v * PI;
+        Vec3::new(theta.sin() * phi.cos(), theta.sin() * phi.sin(), theta.cos())
+    }
+
+    fn lookup(&self, u: f32, v: f32) -> Color {
+        let x: usize = ((u * self.image.get_width() as f32) as usize).min(self.image.get_width() - 1);
+        let y: usize = ((v * self.image.get_height() as f32) as usize).min(self.image.get_height() - 1);
+        self.image.get_pixel(x, y) * self.intensity
+    }
+}
+
+impl Background for Environment {
+    fn color(&self, direction: &Vec3, wavelengths: Option<&Vec3>) -> Color {
+        let (u, v) = self.direction_to_uv(direction);
+        emission(&self.lookup(u, v), None, wavelengths)
+    }
+}
+
+impl Light for Environment {
+    fn sample(&self, point: &Vec3, wavelengths: Option<&Vec3>) -> Option<LightSample> {
+        let ((u, v), _) = self.distribution.sample(random::<f32>(), random::<f32>());
+        let direction: Vec3 = self.uv_to_direction(u, v);
+        // looked up from the direction so they agree with `pdf` and `color` even where rounding
+        // carries it over the edge of a pixel
+        let pdf: f32 = self.pdf(point, &direction);
+        if pdf <= 0.0 {
+            return None;
+        }
+        Some(LightSample::new(direction, f32::INFINITY, self.color(&direction, wavelengths), pdf))
+    }
+
+    fn pdf(&self, _point: &Vec3, direction: &Vec3) -> f32 {
+        let (u, v) = self.direction_to_uv(direction);
+        let sin_theta: f32 = (v * PI).sin();
+        if sin_theta <= 0.0 {
+            return 0.0;
+        }
+        self.distribution.pdf(u, v) / (2.0 * PI * PI * sin_theta)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sampling::tests::mean_and_error;
+
+    /// A dim sky with one bright pixel in it
+    fn environment(rotation: f32) -> Environment {
+        let mut pixels: Vec<Color> = vec![Color::new(0.1, 0.1, 0.1); 16 * 8];
+        pixels[2 * 16 + 5] = Color::new(50.0, 40.0, 30.0);
+        Environment::new(HdrImage::new(16, 8, pixels), rotation, 2.0)
+    }
+
+    #[test]
+    fn samples_show_the_background_with_their_density() {
+        for rotation in [0.0, 75.0] {
+            let environment: Environment = environment(rotation);
+            let point: Vec3 = Vec3::new(0.0, 0.0, 0.0);
+            let mut bright: usize = 0;
+            let (covered, error) = mean_and_error((0..20_000)
+                .map(|_| {
+                    let sample: LightSample = environment.sample(&point, None).unwrap();
+                    let direction: &Vec3 = sample.get_direction();
+                    let pdf: f32 = environment.pdf(&point, direction);
+                    assert!((sample.get_pdf() - pdf).abs() <= 1e-2 * pdf, "sampled with {} but the density is {}", sample.get_pdf(), pdf);
+                    let radiance: Color = sample.get_radiance();
+                    assert!((radiance.x - environment.color(direction, None).x).abs() <= 1e-4 * radiance.x);
+                    if radiance.x > 1.0 {
+                        bright += 1;
+                    }
+                    1.0 / sample.get_pdf()
+                })
+                .collect());
+            // every direction is covered once so the weights add up to the whole sphere
+            assert!((covered - 4.0 * PI).abs() < 0.01 * 4.0 * PI + 4.0 * error, "samples cover {} +- {}", covered, error);
+            assert!(bright > 10_000, "the bright pixel was only picked {} times", bright);
+        }
+    }
+
+    #[test]
+    fn rotation_turns_the_image_about_z() {
+        let still: Environment = environment(0.0);
+        let turned: Environment = environment(90.0);
+        let direction: Vec3 = Vec3::new(0.6, 0.3, 0.5);
+        let rotated: Vec3 = Vec3::new(-0.3, 0.6, 0.5);
+        assert!((still.color(&direction, None).x - turned.color(&rotated, None).x).abs() < 1e-6);
+    }
+}
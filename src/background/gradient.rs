@@ -0,0 +1,32 @@
+use crate::background::Background;
+use crate::material::Color;
+use crate::spectrum::emission;
+use crate::vec::Vec3;
+
+/// A blend from one color straight down to another straight up
+pub struct Gradient {
+    bottom: Color,
+    top: Color
+}
+
+impl Gradient {
+    pub fn new(bottom: Color, top: Color) -> Gradient {
+        Gradient {
+            bottom,
+            top
+        }
+    }
+}
+
+impl Default for Gradient {
+    fn default() -> Gradient {
+        Gradient::new(Color::new(1.0, 1.0, 1.0), Color::new(0.5, 0.7, 1.0))
+    }
+}
+
+impl Background for Gradient {
+    fn color(&self, direction: &Vec3, wavelengths: Option<&Vec3>) -> Color {
+        let a: f32 = (direction.get_normalized().z + 1.0) / 2.0;
+        emission(&(self.bottom * (1.0 - a) + self.top * a), None, wavelengths)
+    }
+}
@@ -0,0 +1,166 @@
+use std::f32::consts::PI;
+use std::sync::Arc;
+use crate::background::Background;
+use crate::light::directional::DirectionalLight;
+use crate::material::Color;
+use crate::spectrum::{emission, xyz_to_rgb};
+use crate::vec::Vec3;
+
+/// Solar illuminance above the atmosphere in the kilolux the sky luminance is given in
+const SOLAR_ILLUMINANCE: f32 = 128.0;
+
+/// The analytic daylight sky of Preetham, Shirley and Smits 1999, "A Practical Analytic Model
+/// for Daylight", with an optional sun whose disk and light come from a directional light
+pub struct Sky {
+    sun_direction: Vec3,
+    sun_zenith: f32,
+    /// Perez coefficients A to E for luminance and the two chromaticity coordinates
+    coefficients: [[f32; 5]; 3],
+    /// Luminance and chromaticity straight up
+    zenith: [f32; 3],
+    intensity: f32,
+    sun: Option<Arc<DirectionalLight>>
+}
+
+impl Sky {
+    /// `elevation` is in degrees above the horizon and `azimuth` in degrees from +x towards +y
+    pub fn new(elevation: f32, azimuth: f32, turbidity: f32, intensity: f32) -> Sky {
+        let elevation: f32 = elevation.to_radians();
+        let azimuth: f32 = azimuth.to_radians();
+        let sun_direction: Vec3 = Vec3::new(
+            elevation.cos() * azimuth.cos(),
+            elevation.cos() * azimuth.sin(),
+            elevation.sin()
+        );
+        let t: f32 = turbidity;
+        let coefficients: [[f32; 5]; 3] = [
+            [0.1787 * t - 1.4630, -0.3554 * t + 0.4275, -0.0227 * t + 5.3251, 0.1206 * t - 2.5771, -0.0670 * t + 0.3703],
+            [-0.0193 * t - 0.2592, -0.0665 * t + 0.0008, -0.0004 * t + 0.2125, -0.0641 * t - 0.8989, -0.0033 * t + 0.0452],
+            [-0.0167 * t - 0.2608, -0.0950 * t + 0.0092, -0.0079 * t + 0.2102, -0.0441 * t - 1.6537, -0.0109 * t + 0.0529]
+        ];
+        // the sun is kept just above the horizon where the model stays finite
+        let theta: f32 = (PI / 2.0 - elevation).min(PI / 2.0 - 0.01);
+        let theta_2: f32 = theta * theta;
+        let theta_3: f32 = theta_2 * theta;
+        let chi: f32 = (4.0 / 9.0 - t / 120.0) * (PI - 2.0 * theta);
+        let zenith: [f32; 3] = [
+            ((4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192).max(0.0),
+            t * t * (0.00166 * theta_3 - 0.00375 * theta_2 + 0.00209 * theta)
+                + t * (-0.02903 * theta_3 + 0.06377 * theta_2 - 0.03202 * theta + 0.00394)
+                + (0.11693 * theta_3 - 0.21196 * theta_2 + 0.06052 * theta + 0.25886),
+            t * t * (0.00275 * theta_3 - 0.00610 * theta_2 + 0.00317 * theta)
+                + t * (-0.04214 * theta_3 + 0.08970 * theta_2 - 0.04153 * theta + 0.00516)
+                + (0.15346 * theta_3 - 0.26756 * theta_2 + 0.06670 * theta + 0.26688)
+        ];
+        Sky {
+            sun_direction,
+            sun_zenith: theta,
+            coefficients,
+            zenith,
+            intensity,
+            sun: None
+        }
+    }
+
+    /// Builds the sun as a visible directional light matching the sky, it needs adding to the
+    /// scene as a light as well as being drawn by the sky
+    pub fn build_sun(&mut self, angular_diameter: f32) -> Arc<DirectionalLight> {
+        let sun: Arc<DirectionalLight> = Arc::new(DirectionalLight::visible(
+            -self.sun_direction,
+            self.sun_transmittance(),
+            SOLAR_ILLUMINANCE * self.intensity,
+            angular_diameter
+        ));
+        self.sun = Some(sun.clone());
+        sun
+    }
+
+    pub fn get_sun_direction(&self) -> &Vec3 {
+        &self.sun_direction
+    }
+
+    /// The Perez sky distribution for a view at `theta` from the zenith and `gamma` from the sun
+    fn perez(coefficients: &[f32; 5], theta: f32, gamma: f32) -> f32 {
+        let [a, b, c, d, e] = *coefficients;
+        (1.0 + a * (b / theta.cos().max(0.01)).exp()) * (1.0 + c * (d * gamma).exp() + e * gamma.cos().powi(2))
+    }
+
+    /// How much sunlight makes it through the atmosphere at the red, green and blue wavelengths
+    /// from Rayleigh and aerosol scattering, following the appendix of the paper
+    fn sun_transmittance(&self) -> Color {
+        let zenith_degrees: f32 = self.sun_zenith.to_degrees();
+        let air_mass: f32 = 1.0 / (self.sun_zenith.cos() + 0.15 * (93.885 - zenith_degrees).powf(-1.253));
+        let turbidity: f32 = (self.coefficients[0][0] + 1.4630) / 0.1787;
+        let beta: f32 = 0.04608 * turbidity - 0.04586;
+        let transmittance = |wavelength: f32| -> f32 {
+            let rayleigh: f32 = (-0.008735 * wavelength.powf(-4.08) * air_mass).exp();
+            let aerosol: f32 = (-beta * wavelength.powf(-1.3) * air_mass).exp();
+            rayleigh * aerosol
+        };
+        Color::new(transmittance(0.65), transmittance(0.55), transmittance(0.45))
+    }
+
+    fn sky_radiance(&self, direction: &Vec3) -> Color {
+        let direction: Vec3 = *direction / direction.get_length();
+        // below the horizon the model breaks down so the horizon is carried on downwards
+        let theta: f32 = direction.z.clamp(0.001, 1.0).acos();
+        let gamma: f32 = direction.dot(&self.sun_direction).clamp(-1.0, 1.0).acos();
+        let value = |i: usize| -> f32 {
+            self.zenith[i] * Sky::perez(&self.coefficients[i], theta, gamma) / Sky::perez(&self.coefficients[i], 0.0, self.sun_zenith)
+        };
+        let luminance: f32 = value(0);
+        let x: f32 = value(1);
+        let y: f32 = value(2).max(1e-4);
+        let xyz: Vec3 = Vec3::new(x / y * luminance, luminance, (1.0 - x - y) / y * luminance);
+        xyz_to_rgb(&xyz).max(&Color::new(0.0, 0.0, 0.0)) * self.intensity
+    }
+}
+
+impl Background for Sky {
+    fn color(&self, direction: &Vec3, wavelengths: Option<&Vec3>) -> Color {
+        let sky: Color = emission(&self.sky_radiance(direction), None, wavelengths);
+        match &self.sun {
+            // a blackbody sun keeps its own spectrum rather than one upsampled from RGB
+            Some(sun) => sky + sun.disk_radiance(direction, wavelengths),
+            None => sky
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_sun_is_placed_by_elevation_and_azimuth() {
+        let overhead: Vec3 = *Sky::new(90.0, 0.0, 3.0, 1.0).get_sun_direction();
+        assert!((overhead.z - 1.0).abs() < 1e-6);
+        let east: Vec3 = *Sky::new(0.0, 90.0, 3.0, 1.0).get_sun_direction();
+        assert!((east.y - 1.0).abs() < 1e-6 && east.x.abs() < 1e-6 && east.z.abs() < 1e-6);
+    }
+
+    #[test]
+    fn the_zenith_has_the_zenith_luminance() {
+        for (elevation, turbidity) in [(60.0, 2.0), (20.0, 4.0), (5.0, 8.0)] {
+            let sky: Sky = Sky::new(elevation, 30.0, turbidity, 0.5);
+            let luminance: f32 = sky.sky_radiance(&Vec3::new(0.0, 0.0, 1.0)).luminance();
+            let expected: f32 = sky.zenith[0] * 0.5;
+            assert!((luminance - expected).abs() < 0.02 * expected, "{} at the zenith instead of {}", luminance, expected);
+        }
+    }
+
+    #[test]
+    fn the_sky_glows_around_the_sun_and_reddens_it_at_sunset() {
+        let mut sky: Sky = Sky::new(30.0, 0.0, 3.0, 1.0);
+        let toward: Vec3 = Vec3::new(0.8, 0.0, 0.6);
+        let away: Vec3 = Vec3::new(-0.8, 0.0, 0.6);
+        assert!(sky.color(&toward, None).luminance() > sky.color(&away, None).luminance());
+        let sun: Arc<DirectionalLight> = sky.build_sun(0.5);
+        let to_sun: Vec3 = sun.get_direction_to_light();
+        assert!(sky.color(&to_sun, None).luminance() > 100.0 * sky.color(&toward, None).luminance());
+        let noon: Color = Sky::new(70.0, 0.0, 3.0, 1.0).sun_transmittance();
+        let sunset: Color = Sky::new(3.0, 0.0, 3.0, 1.0).sun_transmittance();
+        assert!(sunset.x / sunset.z > noon.x / noon.z);
+        assert!(noon.max_component() <= 1.0 && sunset.luminance() < noon.luminance());
+    }
+}
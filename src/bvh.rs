@@ -0,0 +1,293 @@
+use crate::bvh::aabb::Aabb;
+use crate::hittable::HitRecord;
+use crate::ray::Ray;
+use crate::vec::Vec3;
+
+pub mod aabb;
+
+const BIN_COUNT: usize = 16;
+const MAX_LEAF_SIZE: usize = 4;
+const TRAVERSAL_COST: f32 = 1.0;
+const INTERSECTION_COST: f32 = 1.0;
+
+#[derive(Debug)]
+enum BvhNode {
+    Leaf {
+        bounds: Aabb,
+        start: usize,
+        count: usize
+    },
+    /// The first child always directly follows its parent in the node list
+    Interior {
+        bounds: Aabb,
+        second_child: usize,
+        axis: usize
+    }
+}
+
+impl BvhNode {
+    fn get_bounds(&self) -> &Aabb {
+        match self {
+            BvhNode::Leaf { bounds, .. } => bounds,
+            BvhNode::Interior { bounds, .. } => bounds
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Bin {
+    bounds: Aabb,
+    count: usize
+}
+
+struct BuildItem {
+    index: usize,
+    bounds: Aabb,
+    centroid: Vec3
+}
+
+/// A bounding volume hierarchy over a list of primitives identified by index,
+/// built with a binned surface area heuristic and stored as a flat node list
+#[derive(Debug)]
+pub struct Bvh {
+    nodes: Vec<BvhNode>,
+    indices: Vec<usize>
+}
+
+impl Bvh {
+    /// Builds a hierarchy from pairs of primitive index and primitive bounds
+    pub fn build(primitives: Vec<(usize, Aabb)>) -> Bvh {
+        let mut items: Vec<BuildItem> = primitives
+            .into_iter()
+            .map(|(index, bounds)| {
+                BuildItem {
+                    index,
+                    bounds,
+                    centroid: bounds.centroid()
+                }
+            })
+            .collect();
+        let mut nodes: Vec<BvhNode> = Vec::with_capacity(items.len() * 2);
+        if !items.is_empty() {
+            Bvh::build_recursive(&mut nodes, &mut items, 0);
+        }
+        Bvh {
+            nodes,
+            indices: items.iter().map(|i| i.index).collect()
+        }
+    }
+
+    fn build_recursive(nodes: &mut Vec<BvhNode>, items: &mut [BuildItem], offset: usize) -> usize {
+        let node_index: usize = nodes.len();
+        let bounds: Aabb = items
+            .iter()
+            .fold(Aabb::empty(), |acc, i| acc.union(&i.bounds));
+        let leaf = BvhNode::Leaf { bounds, start: offset, count: items.len() };
+        if items.len() == 1 {
+            nodes.push(leaf);
+            return node_index;
+        }
+
+        let centroid_bounds: Aabb = items
+            .iter()
+            .fold(Aabb::empty(), |acc, i| acc.grow(&i.centroid));
+        let split: Option<(usize, f32, f32)> = Bvh::find_split(items, &bounds, &centroid_bounds);
+        let mut mid: usize = match split {
+            Some((axis, position, cost)) if cost < items.len() as f32 * INTERSECTION_COST || items.len() > MAX_LEAF_SIZE => {
+                Bvh::partition(items, |i| i.centroid.get_axis(axis) < position)
+            },
+            _ => 0
+        };
+        if (mid == 0 || mid == items.len()) && items.len() > MAX_LEAF_SIZE {
+            // every centroid is in the same place so there is no good split, just halve the list
+            mid = items.len() / 2;
+        }
+        if mid == 0 || mid == items.len() {
+            nodes.push(leaf);
+            return node_index;
+        }
+
+        let axis: usize = split.map_or(centroid_bounds.longest_axis(), |(axis, _, _)| axis);
+        nodes.push(BvhNode::Interior { bounds, second_child: 0, axis });
+        let (left, right) = items.split_at_mut(mid);
+        Bvh::build_recursive(nodes, left, offset);
+        let second: usize = Bvh::build_recursive(nodes, right, offset + mid);
+        if let BvhNode::Interior { second_child, .. } = &mut nodes[node_index] {
+            *second_child = second;
+        }
+        node_index
+    }
+
+    /// Evaluates the surface area heuristic at the bin boundaries of every axis and
+    /// returns the axis, split position and estimated cost of the cheapest split
+    fn find_split(items: &[BuildItem], bounds: &Aabb, centroid_bounds: &Aabb) -> Option<(usize, f32, f32)> {
+        let parent_area: f32 = bounds.surface_area();
+        let mut best: Option<(usize, f32, f32)> = None;
+        for axis in 0..3 {
+            let low: f32 = centroid_bounds.get_min().get_axis(axis);
+            let high: f32 = centroid_bounds.get_max().get_axis(axis);
+            if high - low <= f32::EPSILON {
+                continue;
+            }
+            let scale: f32 = BIN_COUNT as f32 / (high - low);
+            let mut bins: [Bin; BIN_COUNT] = [Bin { bounds: Aabb::empty(), count: 0 }; BIN_COUNT];
+            for item in items {
+                let bin: usize = (((item.centroid.get_axis(axis) - low) * scale) as usize).min(BIN_COUNT - 1);
+                bins[bin].bounds = bins[bin].bounds.union(&item.bounds);
+                bins[bin].count += 1;
+            }
+
+            // sweep from the right so each split can be costed in a single pass from the left
+            let mut right_areas: [f32; BIN_COUNT] = [0.0; BIN_COUNT];
+            let mut right_counts: [usize; BIN_COUNT] = [0; BIN_COUNT];
+            let mut right_bounds: Aabb = Aabb::empty();
+            let mut right_count: usize = 0;
+            for i in (1..BIN_COUNT).rev() {
+                right_bounds = right_bounds.union(&bins[i].bounds);
+                right_count += bins[i].count;
+                right_areas[i] = right_bounds.surface_area();
+                right_counts[i] = right_count;
+            }
+            let mut left_bounds: Aabb = Aabb::empty();
+            let mut left_count: usize = 0;
+            for i in 0..BIN_COUNT - 1 {
+                left_bounds = left_bounds.union(&bins[i].bounds);
+                left_count += bins[i].count;
+                if left_count == 0 || right_counts[i + 1] == 0 {
+                    continue;
+                }
+                let cost: f32 = TRAVERSAL_COST + INTERSECTION_COST * (
+                    left_bounds.surface_area() * left_count as f32
+                    + right_areas[i + 1] * right_counts[i + 1] as f32
+                ) / parent_area;
+                if best.is_none_or(|(_, _, best_cost)| cost < best_cost) {
+                    best = Some((axis, low + (i + 1) as f32 / scale, cost));
+                }
+            }
+        }
+        best
+    }
+
+    fn partition(items: &mut [BuildItem], predicate: impl Fn(&BuildItem) -> bool) -> usize {
+        let mut mid: usize = 0;
+        for i in 0..items.len() {
+            if predicate(&items[i]) {
+                items.swap(i, mid);
+                mid += 1;
+            }
+        }
+        mid
+    }
+
+    pub fn get_node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn bounding_box(&self) -> Option<Aabb> {
+        self.nodes.first().map(|node| *node.get_bounds())
+    }
+
+    /// Finds the closest hit by calling `hit_primitive` with the index of every primitive
+    /// whose bounds the ray passes through, nearest subtrees first
+    pub fn hit<'a, F>(&self, ray: &Ray, t_min: f32, t_max: f32, mut hit_primitive: F) -> Option<HitRecord<'a>>
+    where
+        F: FnMut(usize, f32, f32) -> Option<HitRecord<'a>>
+    {
+        if self.nodes.is_empty() {
+            return None;
+        }
+        let origin: &Vec3 = ray.get_origin();
+        let direction: &Vec3 = ray.get_direction();
+        let inverse_direction: Vec3 = Vec3::new(1.0 / direction.x, 1.0 / direction.y, 1.0 / direction.z);
+        let mut closest_hit: Option<HitRecord> = None;
+        let mut closest_t: f32 = t_max;
+        let mut stack: Vec<usize> = Vec::with_capacity(64);
+        stack.push(0);
+        while let Some(node_index) = stack.pop() {
+            let node: &BvhNode = &self.nodes[node_index];
+            if !node.get_bounds().hit(origin, &inverse_direction, t_min, closest_t) {
+                continue;
+            }
+            match node {
+                BvhNode::Leaf { start, count, .. } => {
+                    for index in &self.indices[*start..*start + *count] {
+                        if let Some(hit) = hit_primitive(*index, t_min, closest_t) {
+                            closest_t = hit.get_t();
+                            closest_hit = Some(hit);
+                        }
+                    }
+                },
+                BvhNode::Interior { second_child, axis, .. } => {
+                    // push the far child first so the near one is popped next
+                    if direction.get_axis(*axis) < 0.0 {
+                        stack.push(node_index + 1);
+                        stack.push(*second_child);
+                    } else {
+                        stack.push(*second_child);
+                        stack.push(node_index + 1);
+                    }
+                }
+            }
+        }
+        closest_hit
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use rand::random;
+    use super::*;
+    use crate::hittable::sphere::Sphere;
+    use crate::hittable::Hittable;
+    use crate::material::diffuse::Diffuse;
+    use crate::material::Material;
+    use crate::texture::constant::Constant;
+
+    fn spheres(centers: impl Iterator<Item = Vec3>) -> Vec<Sphere> {
+        let material: Arc<dyn Material> = Arc::new(Diffuse::new(Arc::new(Constant::new(Vec3::new(1.0, 1.0, 1.0))), 1.0));
+        centers.map(|center| Sphere::new(center, 0.3, material.clone())).collect()
+    }
+
+    fn random_point(size: f32) -> Vec3 {
+        (Vec3::new(random::<f32>(), random::<f32>(), random::<f32>()) - Vec3::new(0.5, 0.5, 0.5)) * size
+    }
+
+    /// Checks the hierarchy finds the same closest hit as testing every sphere in turn, and
+    /// gives how many rays hit anything
+    fn assert_matches_brute_force(spheres: &[Sphere], rays: &[Ray]) -> usize {
+        let bvh: Bvh = Bvh::build(spheres.iter().enumerate().map(|(i, sphere)| (i, sphere.bounding_box().unwrap())).collect());
+        for ray in rays {
+            let expected: Option<f32> = spheres
+                .iter()
+                .filter_map(|sphere| sphere.hit(ray, 0.001, f32::INFINITY))
+                .map(|hit| hit.get_t())
+                .reduce(f32::min);
+            let found: Option<f32> = bvh.hit(ray, 0.001, f32::INFINITY, |i, t_min, t_max| spheres[i].hit(ray, t_min, t_max)).map(|hit| hit.get_t());
+            assert_eq!(found, expected);
+        }
+        rays.iter().filter(|ray| spheres.iter().any(|sphere| sphere.hit(ray, 0.001, f32::INFINITY).is_some())).count()
+    }
+
+    #[test]
+    fn finds_the_same_hits_as_brute_force() {
+        let spheres: Vec<Sphere> = spheres((0..500).map(|_| random_point(20.0)));
+        let mut rays: Vec<Ray> = (0..2000).map(|_| Ray::new(random_point(30.0), random_point(1.0))).collect();
+        // rays along the axes divide by zero for the other two
+        for axis in [Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, -1.0, 0.0), Vec3::new(0.0, 0.0, 1.0)] {
+            rays.extend((0..200).map(|_| Ray::new(random_point(20.0) - axis * 20.0, axis)));
+        }
+        assert!(assert_matches_brute_force(&spheres, &rays) > 100);
+    }
+
+    #[test]
+    fn handles_primitives_that_cannot_be_split() {
+        // every centroid in the same place leaves the surface area heuristic nothing to bin
+        let stacked: Vec<Sphere> = spheres((0..20).map(|_| Vec3::new(0.0, 0.0, 0.0)));
+        let rays: Vec<Ray> = (0..200).map(|_| Ray::from_to(random_point(4.0) + Vec3::new(5.0, 0.0, 0.0), random_point(1.0))).collect();
+        assert!(assert_matches_brute_force(&stacked, &rays) > 0);
+        let empty: Bvh = Bvh::build(Vec::new());
+        assert!(empty.bounding_box().is_none());
+        assert!(empty.hit(&rays[0], 0.001, f32::INFINITY, |_, _, _| None).is_none());
+    }
+}
@@ -0,0 +1,124 @@
+use crate::vec::Vec3;
+
+/// An axis aligned bounding box
+#[derive(Clone, Copy, Debug)]
+pub struct Aabb {
+    min: Vec3,
+    max: Vec3
+}
+
+impl Aabb {
+    pub fn new(min: Vec3, max: Vec3) -> Aabb {
+        Aabb {
+            min: min.min(&max),
+            max: max.max(&min)
+        }
+    }
+
+    /// An inverted box that contains nothing, useful as the start of a union
+    pub fn empty() -> Aabb {
+        Aabb {
+            min: Vec3::new(f32::INFINITY, f32::INFINITY, f32::INFINITY),
+            max: Vec3::new(f32::NEG_INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY)
+        }
+    }
+
+    pub fn from_points(points: &[Vec3]) -> Aabb {
+        points
+            .iter()
+            .fold(Aabb::empty(), |acc, point| {
+                acc.grow(point)
+            })
+    }
+
+    pub fn get_min(&self) -> &Vec3 {
+        &self.min
+    }
+
+    pub fn get_max(&self) -> &Vec3 {
+        &self.max
+    }
+
+    pub fn grow(&self, point: &Vec3) -> Aabb {
+        Aabb {
+            min: self.min.min(point),
+            max: self.max.max(point)
+        }
+    }
+
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb {
+            min: self.min.min(&other.min),
+            max: self.max.max(&other.max)
+        }
+    }
+
+    /// Pads any axis thinner than `delta` so flat primitives still have volume
+    pub fn pad(&self, delta: f32) -> Aabb {
+        let mut min: Vec3 = self.min;
+        let mut max: Vec3 = self.max;
+        if max.x - min.x < delta {
+            min.x -= delta / 2.0;
+            max.x += delta / 2.0;
+        }
+        if max.y - min.y < delta {
+            min.y -= delta / 2.0;
+            max.y += delta / 2.0;
+        }
+        if max.z - min.z < delta {
+            min.z -= delta / 2.0;
+            max.z += delta / 2.0;
+        }
+        Aabb { min, max }
+    }
+
+    pub fn centroid(&self) -> Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+    }
+
+    pub fn surface_area(&self) -> f32 {
+        if self.is_empty() {
+            return 0.0;
+        }
+        let extent: Vec3 = self.max - self.min;
+        2.0 * (extent.x * extent.y + extent.y * extent.z + extent.z * extent.x)
+    }
+
+    /// Returns the index of the axis along which the box is widest
+    pub fn longest_axis(&self) -> usize {
+        let extent: Vec3 = self.max - self.min;
+        if extent.x > extent.y && extent.x > extent.z {
+            0
+        } else if extent.y > extent.z {
+            1
+        } else {
+            2
+        }
+    }
+
+    /// Slab test against a ray given by its origin and the reciprocal of its direction
+    pub fn hit(&self, origin: &Vec3, inverse_direction: &Vec3, t_min: f32, t_max: f32) -> bool {
+        let mut t_min: f32 = t_min;
+        let mut t_max: f32 = t_max;
+        for axis in 0..3 {
+            let inverse: f32 = inverse_direction.get_axis(axis);
+            let origin: f32 = origin.get_axis(axis);
+            let mut t0: f32 = (self.min.get_axis(axis) - origin) * inverse;
+            let mut t1: f32 = (self.max.get_axis(axis) - origin) * inverse;
+            if inverse < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            // written so that a NaN from 0 * inf leaves the interval unchanged
+            t_min = if t0 > t_min { t0 } else { t_min };
+            t_max = if t1 < t_max { t1 } else { t_max };
+            if t_max < t_min {
+                return false;
+            }
+        }
+        true
+    }
+}
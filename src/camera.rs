@@ -5,7 +5,7 @@ use crate::image::Image;
 use crate::vec::Vec3;
 use crate::ray::Ray;
 use rayon::prelude::*;
-use crate::hittable::{HitRecord, Hittable};
+use crate::integrator::Integrator;
 use crate::material::Color;
 use crate::scene::Scene;
 
@@ -15,16 +15,16 @@ pub struct Camera {
     v: Vec3,
     w: Vec3,
     sample_count: u32,
-    max_depth: u32,
-    far_clip: f32,
     pixel_width: u32,
-    pixel_height: u32
+    pixel_height: u32,
+    integrator: Box<dyn Integrator>
 }
 
-const GAMMA: f32 = 1.5;
+/// Display gamma applied when converting linear radiance to pixel values
+const GAMMA: f32 = 2.2;
 
 impl Camera {
-    pub fn new(direction: Ray, aspect_ratio: f32, sample_count: u32, max_depth: u32, pixel_width: u32) -> Camera {
+    pub fn new(direction: Ray, aspect_ratio: f32, sample_count: u32, pixel_width: u32, integrator: Box<dyn Integrator>) -> Camera {
         let pixel_height: u32 = (pixel_width as f32 / aspect_ratio) as u32;
         let w: Vec3 = direction.get_direction().get_normalized() * -1.0;
         let u: Vec3 = Vec3::cross(&Vec3::new(0.0, 0.0, 1.0), &w).get_normalized();
@@ -35,32 +35,9 @@ impl Camera {
             v,
             w,
             sample_count,
-            max_depth,
-            far_clip: 500.0,
             pixel_width,
-            pixel_height
-        }
-    }
-
-    fn ray_color(&self, scene: &Scene, ray: &Ray, depth: u32) -> Color {
-        if depth == 0 {
-            return Color::new(0.0, 0.0, 0.0);
-        }
-        let hit: Option<HitRecord> = scene.hit(&ray, 0.001, self.far_clip);
-        match hit {
-            Some(hit) => {
-                let mut color: Color = Color::new(0.0, 0.0, 0.0);
-                for _ in 0..self.sample_count {
-                    if let Some((scattered, attenuation)) = hit.get_material().scatter(&ray, &hit) {
-                        color = color + self.ray_color(scene, &scattered, depth - 1) * attenuation;
-                    }
-                }
-                color * GAMMA
-            },
-            None => {
-                let a: f32 = (ray.get_direction().z + 1.0) / 2.0;
-                Color::new(1.0, 1.0, 1.0) * (1.0 - a) + Color::new(0.5, 0.7, 1.0) * a
-            }
+            pixel_height,
+            integrator
         }
     }
 
@@ -70,41 +47,13 @@ impl Camera {
         let perf_start: Instant = Instant::now();
 
         let mut image: Image = Image::new(self.pixel_width, self.pixel_height);
-        let pixels: Vec<[u8; 4]> = (0..(self.pixel_height * self.pixel_width)).map(|i| {
-            let x: u32 = i % self.pixel_width;
-            let y: u32 = i / self.pixel_width;
-            let colors: Vec<Color> = (0..self.sample_count).into_iter().map(|_| {
-                let ray: Ray;
-                if self.sample_count > 1 {
-                    ray = Ray::new(
-                        self.position,
-                        (self.w * -1.0) + (self.u * (2.0 * (x as f32 + rand::random::<f32>()) / self.pixel_width as f32 - 1.0)) + (self.v * (2.0 * (y as f32 + rand::random::<f32>()) / self.pixel_height as f32 - 1.0))
-                    );
-                } else {
-                    ray = Ray::new(
-                        self.position,
-                        (self.w * -1.0) + (self.u * (2.0 * (x as f32 + 0.5) / self.pixel_width as f32 - 1.0)) + (self.v * (2.0 * (y as f32 + 0.5) / self.pixel_height as f32 - 1.0))
-                    );
-                }
-                debug!("{:?}", ray);
-                self.ray_color(scene, &ray, self.max_depth)
-            }).collect();
-            image.jump_ray_count(self.sample_count);
-            if x == 0 && y % 100 == 0 {
-                info!("Rendered row {} of {}", y, self.pixel_height);
+        // rows are handed out to the rayon pool, collecting keeps them in order
+        let pixels: Vec<[u8; 4]> = (0..self.pixel_height).into_par_iter().flat_map_iter(|y| {
+            if y.is_multiple_of(100) {
+                info!("Rendering row {} of {}", y, self.pixel_height);
             }
-            // accumulate and average color samples
-            let mut color: Color = Color::new(0.0, 0.0, 0.0);
-            for c in colors {
-                color = color + c;
-            }
-            color = color / self.sample_count as f32;
-            [
-                (color.x * 255.0) as u8,
-                (color.y * 255.0) as u8,
-                (color.z * 255.0) as u8,
-                255
-            ]
+            let image: &Image = &image;
+            (0..self.pixel_width).map(move |x| self.render_pixel(scene, image, x, y))
         }).collect::<Vec<[u8; 4]>>();
         image.set_pixels(pixels);
 
@@ -113,9 +62,75 @@ impl Camera {
         Ok(image)
     }
 
+    fn render_pixel(&self, scene: &Scene, image: &Image, x: u32, y: u32) -> [u8; 4] {
+        let colors: Vec<Color> = (0..self.sample_count).map(|_| {
+            let ray: Ray = if self.sample_count > 1 {
+                Ray::new(
+                    self.position,
+                    (self.w * -1.0) + (self.u * (2.0 * (x as f32 + rand::random::<f32>()) / self.pixel_width as f32 - 1.0)) + (self.v * (2.0 * (y as f32 + rand::random::<f32>()) / self.pixel_height as f32 - 1.0))
+                )
+            } else {
+                Ray::new(
+                    self.position,
+                    (self.w * -1.0) + (self.u * (2.0 * (x as f32 + 0.5) / self.pixel_width as f32 - 1.0)) + (self.v * (2.0 * (y as f32 + 0.5) / self.pixel_height as f32 - 1.0))
+                )
+            };
+            debug!("{:?}", ray);
+            self.integrator.radiance(scene, &ray)
+        }).collect();
+        image.jump_ray_count(self.sample_count);
+        // accumulate and average color samples
+        let mut color: Color = Color::new(0.0, 0.0, 0.0);
+        for c in colors {
+            color += c;
+        }
+        color = color / self.sample_count as f32;
+        [
+            Camera::encode(color.x),
+            Camera::encode(color.y),
+            Camera::encode(color.z),
+            255
+        ]
+    }
+
+    /// Gamma encodes a linear channel value and clamps it into a byte
+    fn encode(value: f32) -> u8 {
+        (value.max(0.0).powf(1.0 / GAMMA).min(1.0) * 255.0).round() as u8
+    }
+
     pub fn render_and_save(&self, scene: &Scene, path: &str) -> Result<(), Box<dyn Error>> {
         let mut image: Image = self.render(scene)?;
         image.save_as_png(path)?;
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use super::*;
+    use crate::hittable::sphere::Sphere;
+    use crate::integrator::normal::NormalIntegrator;
+    use crate::material::diffuse::Diffuse;
+    use crate::texture::constant::Constant;
+
+    #[test]
+    fn parallel_rows_land_where_they_were_rendered() {
+        let mut scene: Scene = Scene::new();
+        let material: Arc<Diffuse> = Arc::new(Diffuse::new(Arc::new(Constant::new(Color::new(1.0, 1.0, 1.0))), 1.0));
+        scene.add_object(Box::new(Sphere::new(Vec3::new(0.0, 0.0, 0.0), 1.0, material)));
+        let camera: Camera = Camera::new(Ray::new(Vec3::new(-4.0, 0.0, 0.5), Vec3::new(1.0, 0.0, 0.0)), 4.0 / 3.0, 1, 40, Box::new(NormalIntegrator));
+        let image: Image = camera.render(&scene).unwrap();
+        assert_eq!(image.get_ray_count(), 40 * 30);
+        // one sample a pixel goes through the middle of it, so rendering again one pixel at a
+        // time has to give the same picture
+        let single: Image = Image::new(40, 30);
+        for y in 0..30 {
+            for x in 0..40 {
+                assert_eq!(image.get_pixel(x, y), camera.render_pixel(&scene, &single, x, y), "pixel {} {} moved", x, y);
+            }
+        }
+        // the camera looks over the middle of the sphere so rows swapped around would show
+        assert_ne!(image.get_pixel(20, 3), image.get_pixel(20, 26));
+    }
+}
@@ -3,9 +3,10 @@ mod mesh;
 use std::clone;
 use std::error::Error;
 use std::path::Path;
-use std::rc::Rc;
+use std::sync::Arc;
 use serde::Deserialize;
 use crate::hittable::Hittable;
+use crate::light::Light;
 use crate::vec::Vec3;
 
 #[derive(Deserialize, Debug)]
@@ -18,25 +19,281 @@ struct Camera {
     max_depth: u32
 }
 
+#[derive(Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Integrator {
+    Path {
+        roulette_depth: Option<u32>,
+        light_sampling: Option<bool>,
+        /// Trace wavelengths rather than RGB so dispersive dielectrics split colors
+        spectral: Option<bool>
+    },
+    Normal
+}
+
+/// A color given either directly or as the name of a texture
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+enum TextureSource {
+    Color(Vec3),
+    Texture(String)
+}
+
+/// An index of refraction given as a number, the name of a preset like "bk7" or "diamond",
+/// or a table of how it changes with wavelength
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+enum IndexSource {
+    Constant(f32),
+    Preset(String),
+    Dispersive(Dispersion)
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "snake_case")]
+enum Dispersion {
+    /// a and b of Cauchy's equation, with wavelengths in µm
+    Cauchy([f32; 2]),
+    /// The b and c coefficients of the Sellmeier equation, with wavelengths in µm
+    Sellmeier {
+        b: [f32; 3],
+        c: [f32; 3]
+    }
+}
+
+/// A number given either directly or as the name of a texture, read from its brightness
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+enum ScalarSource {
+    Value(f32),
+    Texture(String)
+}
+
+#[derive(Deserialize, Debug)]
+struct ConstantTexture {
+    name: String,
+    color: Vec3
+}
+
+#[derive(Deserialize, Debug)]
+struct ImageTexture {
+    name: String,
+    /// A PNG file
+    path: String,
+    wrap: Option<crate::texture::image::Wrap>,
+    filter: Option<crate::texture::image::Filter>,
+    /// Whether the file holds gamma encoded colors rather than linear data
+    srgb: Option<bool>
+}
+
+#[derive(Deserialize, Debug)]
+struct CheckerTexture {
+    name: String,
+    even: TextureSource,
+    odd: TextureSource,
+    /// The size of each square
+    scale: Option<f32>
+}
+
+#[derive(Deserialize, Debug)]
+struct NoiseTexture {
+    name: String,
+    colors: [Vec3; 2],
+    scale: Option<f32>,
+    octaves: Option<u32>,
+    /// Sum the absolute value of each octave for a billowy look
+    turbulence: Option<bool>,
+    seed: Option<u64>
+}
+
+#[derive(Deserialize, Debug)]
+struct MarbleTexture {
+    name: String,
+    colors: [Vec3; 2],
+    scale: Option<f32>,
+    octaves: Option<u32>,
+    /// How far turbulence bends the veins
+    strength: Option<f32>,
+    seed: Option<u64>
+}
+
+#[derive(Deserialize, Debug)]
+struct WoodTexture {
+    name: String,
+    colors: [Vec3; 2],
+    /// Rings per unit of distance from the z axis
+    rings: Option<f32>,
+    scale: Option<f32>,
+    octaves: Option<u32>,
+    strength: Option<f32>,
+    seed: Option<u64>
+}
+
+#[derive(Deserialize, Debug)]
+struct VoronoiTexture {
+    name: String,
+    colors: [Vec3; 2],
+    scale: Option<f32>,
+    edge_width: Option<f32>,
+    edge_color: Option<Vec3>,
+    seed: Option<u64>
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+struct Textures {
+    constant: Vec<ConstantTexture>,
+    image: Vec<ImageTexture>,
+    checker: Vec<CheckerTexture>,
+    noise: Vec<NoiseTexture>,
+    marble: Vec<MarbleTexture>,
+    wood: Vec<WoodTexture>,
+    voronoi: Vec<VoronoiTexture>,
+    #[serde(skip)]
+    cache: crate::texture::image::ImageCache
+}
+
+/// Optional fields shared by materials to tilt their shading normals with a texture
+#[derive(Deserialize, Debug, Default)]
+struct SurfaceDetail {
+    /// A tangent space normal map texture, image textures for it should set `srgb = false`
+    normal_map: Option<String>,
+    /// A texture whose brightness is a height above the surface
+    bump_map: Option<String>,
+    /// Scales the tilt of a normal map or the heights of a bump map
+    bump_strength: Option<f32>
+}
+
+/// Optional fields for an iridescent thin film coating on reflective and dielectric materials
+#[derive(Deserialize, Debug, Default)]
+struct FilmDetail {
+    /// In nm, the film is left out without it
+    film_thickness: Option<f32>,
+    /// Defaults to soapy water
+    film_index: Option<f32>
+}
+
+/// Optional fields for emissive materials and lights giving off the light of a blackbody,
+/// with their color as a tint on top
+#[derive(Deserialize, Debug, Default)]
+struct BlackbodyDetail {
+    /// In K, the color alone is used without it
+    temperature: Option<f32>,
+    /// Whether the blackbody is scaled to a luminance of one so the temperature only changes
+    /// the hue, otherwise hotter is brighter with 6500 K at a luminance of one
+    normalize: Option<bool>
+}
+
 #[derive(Deserialize, Debug)]
 struct Diffuse {
     name: String,
-    color: Vec3,
-    albedo: f32
+    color: TextureSource,
+    albedo: f32,
+    /// Oren-Nayar roughness, the standard deviation of the facet angles in degrees
+    sigma: Option<f32>,
+    #[serde(flatten)]
+    detail: SurfaceDetail
 }
 
 #[derive(Deserialize, Debug)]
 struct Reflective {
     name: String,
-    albedo: Vec3,
-    fuzz: f32
+    albedo: TextureSource,
+    fuzz: f32,
+    #[serde(flatten)]
+    detail: SurfaceDetail,
+    #[serde(flatten)]
+    film: FilmDetail
 }
 
 #[derive(Deserialize, Debug)]
 struct Dielectric {
     name: String,
-    index: f32,
-    color: Vec3
+    index: IndexSource,
+    color: TextureSource,
+    /// Microfacet roughness for frosted glass, left out for smooth glass
+    roughness: Option<f32>,
+    /// How much of each channel is absorbed per unit of distance travelled inside
+    absorption: Option<Vec3>,
+    #[serde(flatten)]
+    detail: SurfaceDetail,
+    #[serde(flatten)]
+    film: FilmDetail
+}
+
+#[derive(Deserialize, Debug)]
+struct Conductor {
+    name: String,
+    /// A named metal such as "gold" or "copper", in place of `eta` and `k`
+    preset: Option<String>,
+    eta: Option<Vec3>,
+    k: Option<Vec3>,
+    roughness: Option<f32>,
+    #[serde(flatten)]
+    detail: SurfaceDetail
+}
+
+#[derive(Deserialize, Debug)]
+struct Principled {
+    name: String,
+    base_color: TextureSource,
+    metallic: Option<ScalarSource>,
+    roughness: Option<ScalarSource>,
+    specular: Option<ScalarSource>,
+    specular_tint: Option<ScalarSource>,
+    sheen: Option<ScalarSource>,
+    sheen_tint: Option<ScalarSource>,
+    clearcoat: Option<ScalarSource>,
+    clearcoat_roughness: Option<ScalarSource>,
+    transmission: Option<ScalarSource>,
+    /// The index of refraction of the transmitted part
+    index: Option<f32>,
+    #[serde(flatten)]
+    detail: SurfaceDetail
+}
+
+#[derive(Deserialize, Debug)]
+struct Subsurface {
+    name: String,
+    /// The chance of scattering rather than being absorbed at each interaction, per channel
+    albedo: Vec3,
+    /// The average distance between interactions, per channel
+    mean_free_path: Vec3,
+    index: Option<f32>,
+    /// Microfacet roughness of the boundary, left out for a smooth one
+    roughness: Option<f32>,
+    #[serde(flatten)]
+    detail: SurfaceDetail
+}
+
+#[derive(Deserialize, Debug)]
+struct Mix {
+    name: String,
+    first: String,
+    second: String,
+    /// How much of the second material there is, from 0 to 1
+    weight: ScalarSource
+}
+
+/// A clear coat over another material
+#[derive(Deserialize, Debug)]
+struct Layered {
+    name: String,
+    base: String,
+    /// The tint of light passing through the coat, clear without it
+    color: Option<TextureSource>,
+    index: Option<f32>,
+    /// Microfacet roughness of the coat, left out for a smooth one
+    roughness: Option<f32>
+}
+
+#[derive(Deserialize, Debug)]
+struct Emissive {
+    name: String,
+    color: Option<TextureSource>,
+    strength: f32,
+    #[serde(flatten)]
+    blackbody: BlackbodyDetail
 }
 
 #[derive(Deserialize, Debug)]
@@ -63,6 +320,7 @@ struct Rectangle {
 
 #[derive(Deserialize, Debug)]
 struct Cube {
+    /// The middle of the box, the edge vectors span its full size
     center: Vec3,
     width: Vec3,
     height: Vec3,
@@ -78,8 +336,13 @@ struct Triangle {
 
 #[derive(Deserialize, Debug)]
 struct Mesh {
+    /// A `.obj` file or the `.tri` format
     path: String,
     material: String,
+    /// Only load the faces of these OBJ groups or objects
+    groups: Option<Vec<String>>,
+    /// Smooth the normals across edges where faces meet at less than this many degrees
+    smooth_angle: Option<f32>,
     translation: Option<Vec3>,
     rotation: Option<Vec3>,
     scale: Option<Vec3>
@@ -99,30 +362,140 @@ struct Objects {
 struct Materials {
     diffuse: Vec<Diffuse>,
     reflective: Vec<Reflective>,
-    dielectric: Vec<Dielectric>
+    dielectric: Vec<Dielectric>,
+    #[serde(default)]
+    emissive: Vec<Emissive>,
+    #[serde(default)]
+    conductor: Vec<Conductor>,
+    #[serde(default)]
+    principled: Vec<Principled>,
+    #[serde(default)]
+    subsurface: Vec<Subsurface>,
+    #[serde(default)]
+    mix: Vec<Mix>,
+    #[serde(default)]
+    layered: Vec<Layered>
+}
+
+#[derive(Deserialize, Debug)]
+struct PointLight {
+    position: Vec3,
+    color: Option<Vec3>,
+    intensity: f32,
+    falloff: Option<f32>,
+    #[serde(flatten)]
+    blackbody: BlackbodyDetail
+}
+
+#[derive(Deserialize, Debug)]
+struct SpotLight {
+    position: Vec3,
+    direction: Vec3,
+    color: Option<Vec3>,
+    intensity: f32,
+    falloff: Option<f32>,
+    /// Degrees from the direction to where the light starts to fade
+    inner_angle: f32,
+    /// Degrees from the direction to where the light has faded out completely
+    outer_angle: f32,
+    #[serde(flatten)]
+    blackbody: BlackbodyDetail
+}
+
+#[derive(Deserialize, Debug)]
+struct Sun {
+    /// The direction the sunlight travels in
+    direction: Vec3,
+    color: Option<Vec3>,
+    intensity: f32,
+    /// Degrees across the disk of the sun, zero gives hard shadows
+    angular_diameter: Option<f32>,
+    #[serde(flatten)]
+    blackbody: BlackbodyDetail
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+struct Lights {
+    point: Vec<PointLight>,
+    spot: Vec<SpotLight>,
+    sun: Vec<Sun>
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Background {
+    Gradient {
+        bottom: Vec3,
+        top: Vec3
+    },
+    Color {
+        color: Vec3
+    },
+    Environment {
+        path: String,
+        /// Degrees the image is turned about the z axis
+        rotation: Option<f32>,
+        intensity: Option<f32>
+    },
+    Sky {
+        /// Degrees of the sun above the horizon
+        elevation: f32,
+        /// Degrees of the sun from +x towards +y
+        azimuth: Option<f32>,
+        turbidity: Option<f32>,
+        intensity: Option<f32>,
+        /// Degrees the sun covers, zero leaves the sun out
+        sun_angular_diameter: Option<f32>
+    }
 }
 
 #[derive(Deserialize, Debug)]
 struct Scene {
     camera: Camera,
+    integrator: Option<Integrator>,
+    #[serde(default)]
+    textures: Textures,
     materials: Materials,
     objects: Objects,
+    #[serde(default)]
+    lights: Lights,
+    background: Option<Background>
 }
 
 pub fn load_scene(path: impl AsRef<Path>) -> Result<(crate::camera::Camera, crate::scene::Scene), Box<dyn Error>> {
     let contents: String = std::fs::read_to_string(path)?;
-    let scene: Scene = toml::from_str(&contents)?;
+    parse_scene(&contents)
+}
+
+/// Builds the camera and scene described by the contents of a scene file
+fn parse_scene(contents: &str) -> Result<(crate::camera::Camera, crate::scene::Scene), Box<dyn Error>> {
+    let scene: Scene = toml::from_str(contents)?;
     let materials = scene.materials;
+    let textures = scene.textures;
     let objects = scene.objects
         .flatten()
         .iter()
         .map(|i| {
-            i.build(&materials)
+            i.build(&materials, &textures)
         })
         .collect::<Result<Vec<Box<dyn Hittable>>, Box<dyn Error>>>()?;
-    let camera = scene.camera.build()?;
+    let lights = scene.lights
+        .flatten()
+        .iter()
+        .map(|i| i.build())
+        .collect::<Result<Vec<Arc<dyn Light>>, Box<dyn Error>>>()?;
+    let camera = scene.camera.build(scene.integrator.as_ref())?;
+    let background = scene.background;
     let mut scene = crate::scene::Scene::new();
     scene.add_objects(objects);
+    for light in lights {
+        scene.add_light(light);
+    }
+    if let Some(background) = &background {
+        background.apply(&mut scene)?;
+    }
+    scene.build_bvh();
     Ok((camera, scene))
 }
 
@@ -151,7 +524,35 @@ impl Objects {
     }
 }
 
+impl Lights {
+    fn flatten(&self) -> Vec<&dyn LightEntry> {
+        let mut lights: Vec<&dyn LightEntry> = Vec::new();
+        for point in &self.point {
+            lights.push(point);
+        }
+        for spot in &self.spot {
+            lights.push(spot);
+        }
+        for sun in &self.sun {
+            lights.push(sun);
+        }
+        lights
+    }
+}
+
 impl Materials {
+    /// Builds the named material, where `parents` are the materials being built that contain
+    /// it so one that ends up containing itself is an error rather than endless recursion
+    fn build(&self, name: &str, textures: &Textures, parents: &[&str]) -> Result<Arc<dyn crate::material::Material>, Box<dyn Error>> {
+        let path: Vec<&str> = [parents, &[name]].concat();
+        if parents.contains(&name) {
+            return Err(format!("Material {} contains itself through {}", name, path.join(" -> ")).into());
+        }
+        self.get(name)
+            .ok_or_else(|| format!("Material {} not found", name))?
+            .build(self, textures, &path)
+    }
+
     fn get(&self, name: &str) -> Option<&dyn MaterialEntry> {
         for diffuse in &self.diffuse {
             if diffuse.name == name {
@@ -168,12 +569,166 @@ impl Materials {
                 return Some(dielectric);
             }
         }
-        None
+        for emissive in &self.emissive {
+            if emissive.name == name {
+                return Some(emissive);
+            }
+        }
+        for conductor in &self.conductor {
+            if conductor.name == name {
+                return Some(conductor);
+            }
+        }
+        for principled in &self.principled {
+            if principled.name == name {
+                return Some(principled);
+            }
+        }
+        for subsurface in &self.subsurface {
+            if subsurface.name == name {
+                return Some(subsurface);
+            }
+        }
+        for mix in &self.mix {
+            if mix.name == name {
+                return Some(mix);
+            }
+        }
+        self.layered
+            .iter()
+            .find(|layered| layered.name == name)
+            .map(|layered| layered as &dyn MaterialEntry)
+    }
+}
+
+impl Textures {
+    /// Builds the named texture, where `parents` are the textures being built that contain it
+    /// so one that ends up containing itself is an error rather than endless recursion
+    fn build(&self, name: &str, parents: &[&str]) -> Result<Arc<dyn crate::texture::Texture>, Box<dyn Error>> {
+        let path: Vec<&str> = [parents, &[name]].concat();
+        if parents.contains(&name) {
+            return Err(format!("Texture {} contains itself through {}", name, path.join(" -> ")).into());
+        }
+        self.get(name)
+            .ok_or_else(|| format!("Texture {} not found", name))?
+            .build(self, &path)
+    }
+
+    fn get(&self, name: &str) -> Option<&dyn TextureEntry> {
+        for constant in &self.constant {
+            if constant.name == name {
+                return Some(constant);
+            }
+        }
+        for image in &self.image {
+            if image.name == name {
+                return Some(image);
+            }
+        }
+        for checker in &self.checker {
+            if checker.name == name {
+                return Some(checker);
+            }
+        }
+        for noise in &self.noise {
+            if noise.name == name {
+                return Some(noise);
+            }
+        }
+        for marble in &self.marble {
+            if marble.name == name {
+                return Some(marble);
+            }
+        }
+        for wood in &self.wood {
+            if wood.name == name {
+                return Some(wood);
+            }
+        }
+        self.voronoi
+            .iter()
+            .find(|voronoi| voronoi.name == name)
+            .map(|voronoi| voronoi as &dyn TextureEntry)
+    }
+}
+
+impl TextureSource {
+    fn build(&self, textures: &Textures) -> Result<Arc<dyn crate::texture::Texture>, Box<dyn Error>> {
+        self.build_within(textures, &[])
+    }
+
+    /// Builds the source as part of the textures named by `parents`
+    fn build_within(&self, textures: &Textures, parents: &[&str]) -> Result<Arc<dyn crate::texture::Texture>, Box<dyn Error>> {
+        match self {
+            TextureSource::Color(color) => Ok(Arc::new(crate::texture::constant::Constant::new(*color))),
+            TextureSource::Texture(name) => textures.build(name, parents)
+        }
+    }
+}
+
+impl ScalarSource {
+    fn build(&self, textures: &Textures) -> Result<Arc<dyn crate::texture::Texture>, Box<dyn Error>> {
+        match self {
+            ScalarSource::Value(value) => Ok(Arc::new(crate::texture::constant::Constant::new(Vec3::new(*value, *value, *value)))),
+            ScalarSource::Texture(name) => textures.build(name, &[])
+        }
+    }
+}
+
+impl IndexSource {
+    fn build(&self) -> Result<crate::material::dielectric::RefractiveIndex, Box<dyn Error>> {
+        use crate::material::dielectric::RefractiveIndex;
+        match self {
+            IndexSource::Constant(index) => Ok(RefractiveIndex::Constant(*index)),
+            IndexSource::Preset(name) => RefractiveIndex::preset(name).ok_or_else(|| format!("Unknown index of refraction preset {}", name).into()),
+            IndexSource::Dispersive(Dispersion::Cauchy([a, b])) => Ok(RefractiveIndex::Cauchy { a: *a, b: *b }),
+            IndexSource::Dispersive(Dispersion::Sellmeier { b, c }) => Ok(RefractiveIndex::Sellmeier { b: *b, c: *c })
+        }
+    }
+}
+
+impl FilmDetail {
+    fn build(&self) -> Result<Option<crate::material::thin_film::ThinFilm>, Box<dyn Error>> {
+        match (self.film_thickness, self.film_index) {
+            (None, None) => Ok(None),
+            (None, Some(_)) => Err("A film index needs a film thickness".into()),
+            (Some(thickness), _) if thickness < 0.0 => Err("Film thickness can't be negative".into()),
+            (Some(thickness), index) => Ok(Some(crate::material::thin_film::ThinFilm::new(thickness, index.unwrap_or(1.33))))
+        }
+    }
+}
+
+impl BlackbodyDetail {
+    fn build(&self) -> Result<Option<crate::spectrum::Blackbody>, Box<dyn Error>> {
+        use crate::spectrum::Blackbody;
+        match (self.temperature, self.normalize) {
+            (None, None) => Ok(None),
+            (None, Some(_)) => Err("Normalizing a blackbody needs a temperature".into()),
+            (Some(temperature), _) if temperature <= 0.0 => Err("Blackbody temperature must be positive".into()),
+            (Some(temperature), Some(false)) => Ok(Some(Blackbody::unnormalized(temperature))),
+            (Some(temperature), _) => Ok(Some(Blackbody::new(temperature)))
+        }
+    }
+}
+
+impl SurfaceDetail {
+    /// Wraps the material in a normal or bump map if one is given
+    fn apply(&self, material: Arc<dyn crate::material::Material>, textures: &Textures) -> Result<Arc<dyn crate::material::Material>, Box<dyn Error>> {
+        use crate::material::normal_map::{NormalMap, Perturbation};
+        let (name, perturbation, default_strength) = match (&self.normal_map, &self.bump_map) {
+            (Some(_), Some(_)) => return Err("Materials can't have both a normal map and a bump map".into()),
+            (Some(name), None) => (name, Perturbation::Normal, 1.0),
+            (None, Some(name)) => (name, Perturbation::Height, 0.02),
+            (None, None) => return Ok(material)
+        };
+        let map = TextureSource::Texture(name.clone()).build(textures)?;
+        Ok(Arc::new(NormalMap::new(material, map, perturbation, self.bump_strength.unwrap_or(default_strength))))
     }
 }
 
 impl Camera {
-    fn build(&self) -> Result<crate::camera::Camera, Box<dyn Error>> {
+    fn build(&self, integrator: Option<&Integrator>) -> Result<crate::camera::Camera, Box<dyn Error>> {
+        let integrator = integrator.unwrap_or(&Integrator::Path { roulette_depth: None, light_sampling: None, spectral: None });
         Ok(
             crate::camera::Camera::new(
                 crate::ray::Ray::from_to(
@@ -182,21 +737,78 @@ impl Camera {
                 ),
                 self.pixel_size[0] as f32 / self.pixel_size[1] as f32,
                 self.samples,
-                self.max_depth,
-                self.pixel_size[0]
+                self.pixel_size[0],
+                integrator.build(self.max_depth)
             )
         )
     }
 }
 
+impl Background {
+    fn apply(&self, scene: &mut crate::scene::Scene) -> Result<(), Box<dyn Error>> {
+        match self {
+            Background::Gradient { bottom, top } => {
+                scene.set_background(Arc::new(crate::background::gradient::Gradient::new(*bottom, *top)));
+            },
+            Background::Color { color } => {
+                scene.set_background(Arc::new(crate::background::gradient::Gradient::new(*color, *color)));
+            },
+            Background::Environment { path, rotation, intensity } => {
+                let environment = Arc::new(crate::background::environment::Environment::new(
+                    crate::hdr::HdrImage::load(path)?,
+                    rotation.unwrap_or(0.0),
+                    intensity.unwrap_or(1.0)
+                ));
+                scene.set_background(environment.clone());
+                scene.add_light(environment);
+            },
+            Background::Sky { elevation, azimuth, turbidity, intensity, sun_angular_diameter } => {
+                let turbidity: f32 = turbidity.unwrap_or(3.0);
+                if !(1.0..=20.0).contains(&turbidity) {
+                    return Err("Sky turbidity must be between 1 and 20".into());
+                }
+                let mut sky = crate::background::sky::Sky::new(
+                    *elevation,
+                    azimuth.unwrap_or(0.0),
+                    turbidity,
+                    intensity.unwrap_or(0.05)
+                );
+                let sun_angular_diameter: f32 = sun_angular_diameter.unwrap_or(0.53);
+                if sun_angular_diameter > 0.0 && *elevation > 0.0 {
+                    let sun = sky.build_sun(sun_angular_diameter);
+                    scene.add_light(sun);
+                }
+                scene.set_background(Arc::new(sky));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Integrator {
+    fn build(&self, max_depth: u32) -> Box<dyn crate::integrator::Integrator> {
+        match self {
+            Integrator::Path { roulette_depth, light_sampling, spectral } => Box::new(
+                crate::integrator::path::PathTracer::new(
+                    max_depth,
+                    roulette_depth.unwrap_or(3),
+                    light_sampling.unwrap_or(true),
+                    spectral.unwrap_or(false)
+                )
+            ),
+            Integrator::Normal => Box::new(crate::integrator::normal::NormalIntegrator)
+        }
+    }
+}
+
 impl HittableEntry for Sphere {
-    fn build(&self, materials: &Materials) -> Result<Box<dyn Hittable>, Box<dyn Error>> {
+    fn build(&self, materials: &Materials, textures: &Textures) -> Result<Box<dyn Hittable>, Box<dyn Error>> {
         Ok(
             Box::new(
                 crate::hittable::sphere::Sphere::new(
                     self.center,
                     self.radius,
-                    materials.get(&self.material).ok_or("Material not found")?.build()?
+                    materials.build(&self.material, textures, &[])?
                 )
             )
         )
@@ -204,99 +816,453 @@ impl HittableEntry for Sphere {
 }
 
 impl HittableEntry for Plane {
-    fn build(&self, materials: &Materials) -> Result<Box<dyn Hittable>, Box<dyn Error>> {
+    fn build(&self, materials: &Materials, textures: &Textures) -> Result<Box<dyn Hittable>, Box<dyn Error>> {
         Ok(Box::new(crate::hittable::plane::Plane::new(
             self.normal,
             self.point,
-            materials.get(&self.material).ok_or("Material not found")?.build()?
+            materials.build(&self.material, textures, &[])?
         )))
     }
 }
 
 impl HittableEntry for Rectangle {
-    fn build(&self, materials: &Materials) -> Result<Box<dyn Hittable>, Box<dyn Error>> {
+    fn build(&self, materials: &Materials, textures: &Textures) -> Result<Box<dyn Hittable>, Box<dyn Error>> {
         Ok(Box::new(crate::hittable::rectangle::Rectangle::new(
             self.point,
             self.width,
             self.height,
-            materials.get(&self.material).ok_or("Material not found")?.build()?
+            materials.build(&self.material, textures, &[])?
         )))
     }
 }
 
 impl HittableEntry for Cube {
-    fn build(&self, materials: &Materials) -> Result<Box<dyn Hittable>, Box<dyn Error>> {
+    fn build(&self, materials: &Materials, textures: &Textures) -> Result<Box<dyn Hittable>, Box<dyn Error>> {
+        if !crate::hittable::cube::Cube::spans_volume(&self.width, &self.height, &self.depth) {
+            return Err(format!("Cube at {:?} has edges that don't span a volume", self.center).into());
+        }
         Ok(Box::new(crate::hittable::cube::Cube::new(
             self.center,
             self.width,
             self.height,
             self.depth,
-            materials.get(&self.material).ok_or("Material not found")?.build()?
+            materials.build(&self.material, textures, &[])?
         )))
     }
 }
 
 impl HittableEntry for Triangle {
-    fn build(&self, materials: &Materials) -> Result<Box<dyn Hittable>, Box<dyn Error>> {
+    fn build(&self, materials: &Materials, textures: &Textures) -> Result<Box<dyn Hittable>, Box<dyn Error>> {
         Ok(Box::new(crate::hittable::triangle::Triangle::new(
             self.vertices[0],
             self.vertices[1],
             self.vertices[2],
-            materials.get(&self.material).ok_or("Material not found")?.build()?
+            materials.build(&self.material, textures, &[])?
         )))
     }
 }
 
 impl HittableEntry for Mesh {
-    fn build(&self, materials: &Materials) -> Result<Box<dyn Hittable>, Box<dyn Error>> {
-        let mut mesh = crate::hittable::mesh::Mesh::load(
-            self.path.clone(),
-            materials.get(&self.material).ok_or("Material not found")?.build()?,
-        )?;
-        if let Some(translation) = &self.translation {
-            mesh.translate(translation.clone());
+    fn build(&self, materials: &Materials, textures: &Textures) -> Result<Box<dyn Hittable>, Box<dyn Error>> {
+        let material = materials.build(&self.material, textures, &[])?;
+        let extension: Option<String> = Path::new(&self.path)
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase());
+        let mut mesh = match extension.as_deref() {
+            Some("obj") => crate::hittable::mesh::obj::load(&self.path, material, self.groups.as_deref())?,
+            Some("tri") => {
+                if self.groups.is_some() {
+                    return Err(format!("{} has no groups to select", self.path).into());
+                }
+                crate::hittable::mesh::Mesh::load(&self.path, material)?
+            },
+            _ => return Err(format!("Unsupported mesh format {}", self.path).into())
+        };
+        if let Some(translation) = self.translation {
+            mesh.translate(translation);
         }
-        if let Some(rotation) = &self.rotation {
-            mesh.rotate(rotation.clone());
+        if let Some(rotation) = self.rotation {
+            mesh.rotate(rotation);
         }
-        if let Some(scale) = &self.scale {
-            mesh.scale(scale.clone());
+        if let Some(scale) = self.scale {
+            mesh.scale(scale);
         }
+        if let Some(smooth_angle) = self.smooth_angle {
+            mesh.compute_normals(smooth_angle);
+        }
+        mesh.build_bvh();
         Ok(Box::new(mesh))
     }
 }
 
+impl TextureEntry for ConstantTexture {
+    fn build(&self, _textures: &Textures, _parents: &[&str]) -> Result<Arc<dyn crate::texture::Texture>, Box<dyn Error>> {
+        Ok(Arc::new(crate::texture::constant::Constant::new(self.color)))
+    }
+}
+
+impl TextureEntry for ImageTexture {
+    fn build(&self, textures: &Textures, _parents: &[&str]) -> Result<Arc<dyn crate::texture::Texture>, Box<dyn Error>> {
+        Ok(Arc::new(crate::texture::image::ImageTexture::new(
+            textures.cache.load(&self.path, self.srgb.unwrap_or(true))?,
+            self.wrap.unwrap_or_default(),
+            self.filter.unwrap_or_default()
+        )))
+    }
+}
+
+impl TextureEntry for CheckerTexture {
+    fn build(&self, textures: &Textures, parents: &[&str]) -> Result<Arc<dyn crate::texture::Texture>, Box<dyn Error>> {
+        Ok(Arc::new(crate::texture::checker::Checker::new(
+            self.even.build_within(textures, parents)?,
+            self.odd.build_within(textures, parents)?,
+            self.scale.unwrap_or(1.0)
+        )))
+    }
+}
+
+impl TextureEntry for NoiseTexture {
+    fn build(&self, _textures: &Textures, _parents: &[&str]) -> Result<Arc<dyn crate::texture::Texture>, Box<dyn Error>> {
+        Ok(Arc::new(crate::texture::noise::NoiseTexture::new(
+            self.seed.unwrap_or(0),
+            self.colors,
+            self.scale.unwrap_or(1.0),
+            self.octaves.unwrap_or(6),
+            self.turbulence.unwrap_or(false)
+        )))
+    }
+}
+
+impl TextureEntry for MarbleTexture {
+    fn build(&self, _textures: &Textures, _parents: &[&str]) -> Result<Arc<dyn crate::texture::Texture>, Box<dyn Error>> {
+        Ok(Arc::new(crate::texture::marble::Marble::new(
+            self.seed.unwrap_or(0),
+            self.colors,
+            self.scale.unwrap_or(1.0),
+            self.octaves.unwrap_or(6),
+            self.strength.unwrap_or(5.0)
+        )))
+    }
+}
+
+impl TextureEntry for WoodTexture {
+    fn build(&self, _textures: &Textures, _parents: &[&str]) -> Result<Arc<dyn crate::texture::Texture>, Box<dyn Error>> {
+        Ok(Arc::new(crate::texture::wood::Wood::new(
+            self.seed.unwrap_or(0),
+            self.colors,
+            self.rings.unwrap_or(8.0),
+            self.scale.unwrap_or(2.0),
+            self.octaves.unwrap_or(3),
+            self.strength.unwrap_or(0.4)
+        )))
+    }
+}
+
+impl TextureEntry for VoronoiTexture {
+    fn build(&self, _textures: &Textures, _parents: &[&str]) -> Result<Arc<dyn crate::texture::Texture>, Box<dyn Error>> {
+        Ok(Arc::new(crate::texture::voronoi::Voronoi::new(
+            self.seed.unwrap_or(0),
+            self.colors,
+            self.scale.unwrap_or(1.0),
+            self.edge_width.unwrap_or(0.0),
+            self.edge_color.unwrap_or(Vec3::new(0.0, 0.0, 0.0))
+        )))
+    }
+}
+
 impl MaterialEntry for Diffuse {
-    fn build(&self) -> Result<Rc<dyn crate::material::Material>, Box<dyn Error>> {
-        Ok(Rc::new(crate::material::diffuse::Diffuse::new(
-            self.color,
+    fn build(&self, _materials: &Materials, textures: &Textures, _parents: &[&str]) -> Result<Arc<dyn crate::material::Material>, Box<dyn Error>> {
+        let mut diffuse: crate::material::diffuse::Diffuse = crate::material::diffuse::Diffuse::new(
+            self.color.build(textures)?,
             self.albedo
-        )))
+        );
+        if let Some(sigma) = self.sigma {
+            diffuse = diffuse.with_oren_nayar(sigma.to_radians());
+        }
+        self.detail.apply(Arc::new(diffuse), textures)
     }
 }
 
 impl MaterialEntry for Reflective {
-    fn build(&self) -> Result<Rc<dyn crate::material::Material>, Box<dyn Error>> {
-        Ok(Rc::new(crate::material::reflective::Reflective::new(
-            self.albedo,
+    fn build(&self, _materials: &Materials, textures: &Textures, _parents: &[&str]) -> Result<Arc<dyn crate::material::Material>, Box<dyn Error>> {
+        let mut reflective: crate::material::reflective::Reflective = crate::material::reflective::Reflective::new(
+            self.albedo.build(textures)?,
             self.fuzz
-        )))
+        );
+        if let Some(film) = self.film.build()? {
+            reflective = reflective.with_thin_film(film);
+        }
+        self.detail.apply(Arc::new(reflective), textures)
     }
 }
 
 impl MaterialEntry for Dielectric {
-    fn build(&self) -> Result<Rc<dyn crate::material::Material>, Box<dyn Error>> {
-        Ok(Rc::new(crate::material::dielectric::Dielectric::new(
-            self.index,
-            self.color
+    fn build(&self, _materials: &Materials, textures: &Textures, _parents: &[&str]) -> Result<Arc<dyn crate::material::Material>, Box<dyn Error>> {
+        use crate::material::dielectric::{RefractiveIndex, D_LINE};
+        let refraction_index: RefractiveIndex = self.index.build()?;
+        let mut dielectric: crate::material::dielectric::Dielectric = crate::material::dielectric::Dielectric::new(
+            refraction_index.at(D_LINE),
+            self.color.build(textures)?,
+            self.roughness.unwrap_or(0.0)
+        ).with_dispersion(refraction_index);
+        if let Some(absorption) = self.absorption {
+            if absorption.x < 0.0 || absorption.y < 0.0 || absorption.z < 0.0 {
+                return Err(format!("Dielectric {} can't have negative absorption", self.name).into());
+            }
+            dielectric = dielectric.with_absorption(absorption);
+        }
+        if let Some(film) = self.film.build()? {
+            dielectric = dielectric.with_thin_film(film);
+        }
+        self.detail.apply(Arc::new(dielectric), textures)
+    }
+}
+
+impl MaterialEntry for Conductor {
+    fn build(&self, _materials: &Materials, textures: &Textures, _parents: &[&str]) -> Result<Arc<dyn crate::material::Material>, Box<dyn Error>> {
+        let (eta, k) = match (&self.preset, self.eta, self.k) {
+            (Some(preset), None, None) => crate::material::conductor::Conductor::preset(preset)
+                .ok_or_else(|| format!("Unknown conductor preset {}", preset))?,
+            (None, Some(eta), Some(k)) => (eta, k),
+            _ => return Err(format!("Conductor {} needs either a preset or both eta and k", self.name).into())
+        };
+        self.detail.apply(Arc::new(crate::material::conductor::Conductor::new(
+            eta,
+            k,
+            self.roughness.unwrap_or(0.0)
+        )), textures)
+    }
+}
+
+impl MaterialEntry for Principled {
+    fn build(&self, _materials: &Materials, textures: &Textures, _parents: &[&str]) -> Result<Arc<dyn crate::material::Material>, Box<dyn Error>> {
+        use crate::material::principled::Principled;
+        type With = fn(Principled, Arc<dyn crate::texture::Texture>) -> Principled;
+        let parameters: [(&Option<ScalarSource>, With); 9] = [
+            (&self.metallic, Principled::with_metallic),
+            (&self.roughness, Principled::with_roughness),
+            (&self.specular, Principled::with_specular),
+            (&self.specular_tint, Principled::with_specular_tint),
+            (&self.sheen, Principled::with_sheen),
+            (&self.sheen_tint, Principled::with_sheen_tint),
+            (&self.clearcoat, Principled::with_clearcoat),
+            (&self.clearcoat_roughness, Principled::with_clearcoat_roughness),
+            (&self.transmission, Principled::with_transmission)
+        ];
+        let mut principled: Principled = Principled::new(self.base_color.build(textures)?);
+        for (source, with) in parameters {
+            if let Some(source) = source {
+                principled = with(principled, source.build(textures)?);
+            }
+        }
+        if let Some(index) = self.index {
+            principled = principled.with_refraction_index(index);
+        }
+        self.detail.apply(Arc::new(principled), textures)
+    }
+}
+
+impl MaterialEntry for Subsurface {
+    fn build(&self, _materials: &Materials, textures: &Textures, _parents: &[&str]) -> Result<Arc<dyn crate::material::Material>, Box<dyn Error>> {
+        let albedo: [f32; 3] = [self.albedo.x, self.albedo.y, self.albedo.z];
+        if albedo.iter().any(|albedo| !(0.0..=1.0).contains(albedo)) {
+            return Err(format!("Subsurface {} needs an albedo between 0 and 1", self.name).into());
+        }
+        let mean_free_path: [f32; 3] = [self.mean_free_path.x, self.mean_free_path.y, self.mean_free_path.z];
+        if mean_free_path.iter().any(|distance| *distance <= 0.0) {
+            return Err(format!("Subsurface {} needs a positive mean free path", self.name).into());
+        }
+        self.detail.apply(Arc::new(crate::material::subsurface::Subsurface::new(
+            self.albedo,
+            self.mean_free_path,
+            self.index.unwrap_or(1.4),
+            self.roughness.unwrap_or(0.0)
+        )), textures)
+    }
+}
+
+impl MaterialEntry for Mix {
+    fn build(&self, materials: &Materials, textures: &Textures, parents: &[&str]) -> Result<Arc<dyn crate::material::Material>, Box<dyn Error>> {
+        Ok(Arc::new(crate::material::mix::Mix::new(
+            materials.build(&self.first, textures, parents)?,
+            materials.build(&self.second, textures, parents)?,
+            self.weight.build(textures)?
         )))
     }
 }
 
+impl MaterialEntry for Layered {
+    fn build(&self, materials: &Materials, textures: &Textures, parents: &[&str]) -> Result<Arc<dyn crate::material::Material>, Box<dyn Error>> {
+        let base: Arc<dyn crate::material::Material> = materials.build(&self.base, textures, parents)?;
+        let color: Arc<dyn crate::texture::Texture> = match &self.color {
+            Some(color) => color.build(textures)?,
+            None => Arc::new(crate::texture::constant::Constant::new(Vec3::new(1.0, 1.0, 1.0)))
+        };
+        Ok(Arc::new(crate::material::layered::Layered::new(
+            base,
+            color,
+            self.index.unwrap_or(1.5),
+            self.roughness.unwrap_or(0.0)
+        )))
+    }
+}
+
+impl MaterialEntry for Emissive {
+    fn build(&self, _materials: &Materials, textures: &Textures, _parents: &[&str]) -> Result<Arc<dyn crate::material::Material>, Box<dyn Error>> {
+        let color: Arc<dyn crate::texture::Texture> = match &self.color {
+            Some(color) => color.build(textures)?,
+            None => Arc::new(crate::texture::constant::Constant::new(Vec3::new(1.0, 1.0, 1.0)))
+        };
+        let mut emissive: crate::material::emissive::Emissive = crate::material::emissive::Emissive::new(color, self.strength);
+        if let Some(blackbody) = self.blackbody.build()? {
+            emissive = emissive.with_blackbody(blackbody);
+        }
+        Ok(Arc::new(emissive))
+    }
+}
+
+impl LightEntry for PointLight {
+    fn build(&self) -> Result<Arc<dyn Light>, Box<dyn Error>> {
+        let mut light: crate::light::point::PointLight = crate::light::point::PointLight::new(
+            self.position,
+            self.color.unwrap_or(Vec3::new(1.0, 1.0, 1.0)),
+            self.intensity,
+            self.falloff.unwrap_or(2.0)
+        );
+        if let Some(blackbody) = self.blackbody.build()? {
+            light = light.with_blackbody(blackbody);
+        }
+        Ok(Arc::new(light))
+    }
+}
+
+impl LightEntry for SpotLight {
+    fn build(&self) -> Result<Arc<dyn Light>, Box<dyn Error>> {
+        let mut light: crate::light::spot::SpotLight = crate::light::spot::SpotLight::new(
+            self.position,
+            self.direction,
+            self.color.unwrap_or(Vec3::new(1.0, 1.0, 1.0)),
+            self.intensity,
+            self.falloff.unwrap_or(2.0),
+            self.inner_angle,
+            self.outer_angle
+        );
+        if let Some(blackbody) = self.blackbody.build()? {
+            light = light.with_blackbody(blackbody);
+        }
+        Ok(Arc::new(light))
+    }
+}
+
+impl LightEntry for Sun {
+    fn build(&self) -> Result<Arc<dyn Light>, Box<dyn Error>> {
+        let mut light: crate::light::directional::DirectionalLight = crate::light::directional::DirectionalLight::new(
+            self.direction,
+            self.color.unwrap_or(Vec3::new(1.0, 1.0, 1.0)),
+            self.intensity,
+            self.angular_diameter.unwrap_or(0.0)
+        );
+        if let Some(blackbody) = self.blackbody.build()? {
+            light = light.with_blackbody(blackbody);
+        }
+        Ok(Arc::new(light))
+    }
+}
+
+trait LightEntry {
+    fn build(&self) -> Result<Arc<dyn Light>, Box<dyn Error>>;
+}
+
+trait TextureEntry {
+    /// Textures can be built from other textures so all of them are passed in, along with the
+    /// names of the textures being built that lead to this one
+    fn build(&self, textures: &Textures, parents: &[&str]) -> Result<Arc<dyn crate::texture::Texture>, Box<dyn Error>>;
+}
+
 trait MaterialEntry {
-    fn build(&self) -> Result<Rc<dyn crate::material::Material>, Box<dyn Error>>;
+    /// Materials can be built from other materials so all of them are passed in, along with
+    /// the names of the materials being built that lead to this one
+    fn build(&self, materials: &Materials, textures: &Textures, parents: &[&str]) -> Result<Arc<dyn crate::material::Material>, Box<dyn Error>>;
 }
 
 trait HittableEntry {
-    fn build(&self, materials: &Materials) -> Result<Box<dyn Hittable>, Box<dyn Error>>;
+    fn build(&self, materials: &Materials, textures: &Textures) -> Result<Box<dyn Hittable>, Box<dyn Error>>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A scene holding the given sections, with empty lists for the required keys they leave
+    /// out and a white diffuse material called "white" as the only diffuse one
+    fn scene(textures: &str, materials: &str, objects: &str) -> String {
+        let missing = |section: &str, keys: &[&str]| -> String {
+            keys.iter()
+                .filter(|key| !section.contains(&format!("{} =", key)))
+                .map(|key| format!("{} = []\n", key))
+                .collect()
+        };
+        format!(
+            "[camera]\nname = \"main\"\nfrom = [0, -5, 0]\nto = [0, 0, 0]\npixel_size = [4, 4]\nsamples = 1\nmax_depth = 2\n\n\
+            [textures]\n{}\n\n\
+            [materials]\ndiffuse = [{{name = \"white\", color = [1, 1, 1], albedo = 1.0}}]\n{}\n{}\n\n\
+            [objects]\n{}\n{}\n",
+            textures,
+            materials,
+            missing(materials, &["reflective", "dielectric"]),
+            objects,
+            missing(objects, &["spheres", "planes", "rectangles", "cubes", "triangles", "meshes"])
+        )
+    }
+
+    /// A sphere made of the named material
+    fn sphere(material: &str) -> String {
+        format!("spheres = [{{center = [0, 0, 0], radius = 1, material = \"{}\"}}]", material)
+    }
+
+    fn error(contents: &str) -> String {
+        match parse_scene(contents) {
+            Ok(_) => panic!("expected the scene to be rejected"),
+            Err(error) => error.to_string()
+        }
+    }
+
+    #[test]
+    fn loads_a_minimal_scene() {
+        assert!(parse_scene(&scene("", "", &sphere("white"))).is_ok());
+        assert!(error(&scene("", "", &sphere("missing"))).contains("Material missing not found"));
+    }
+
+    #[test]
+    fn rejects_cubes_without_volume() {
+        let flat: String = scene("", "", "cubes = [{center = [0, 0, 0], width = [1, 0, 0], height = [0, 1, 0], depth = [1, 1, 0], material = \"white\"}]");
+        assert!(error(&flat).contains("don't span a volume"));
+        let solid: String = scene("", "", "cubes = [{center = [0, 0, 0], width = [1, 0, 0], height = [0, 1, 0], depth = [0, 0, 1], material = \"white\"}]");
+        assert!(parse_scene(&solid).is_ok());
+    }
+
+    #[test]
+    fn rejects_textures_that_contain_themselves() {
+        let textured: &str = "principled = [{name = \"textured\", base_color = \"a\"}]";
+        let direct: &str = "checker = [{name = \"a\", even = \"a\", odd = [0, 0, 0]}]";
+        assert!(error(&scene(direct, textured, &sphere("textured"))).contains("a -> a"));
+        let indirect: &str = "checker = [{name = \"a\", even = \"b\", odd = [0, 0, 0]}, {name = \"b\", even = [1, 1, 1], odd = \"a\"}]";
+        assert!(error(&scene(indirect, textured, &sphere("textured"))).contains("a -> b -> a"));
+        // the same texture twice side by side isn't a cycle
+        let nested: &str = "checker = [{name = \"a\", even = \"b\", odd = \"b\"}, {name = \"b\", even = [1, 1, 1], odd = [0, 0, 0]}]";
+        assert!(parse_scene(&scene(nested, textured, &sphere("textured"))).is_ok());
+    }
+
+    #[test]
+    fn rejects_materials_that_contain_themselves() {
+        let direct: &str = "mix = [{name = \"m\", first = \"white\", second = \"m\", weight = 0.5}]";
+        assert!(error(&scene("", direct, &sphere("m"))).contains("m -> m"));
+        let indirect: &str = "mix = [{name = \"m\", first = \"white\", second = \"m2\", weight = 0.5}]\n\
+            layered = [{name = \"m2\", base = \"m\"}]";
+        assert!(error(&scene("", indirect, &sphere("m"))).contains("m -> m2 -> m"));
+        // the same material on both sides of a mix isn't a cycle
+        let nested: &str = "mix = [{name = \"m\", first = \"m2\", second = \"m2\", weight = 0.5}]\n\
+            layered = [{name = \"m2\", base = \"white\"}]";
+        assert!(parse_scene(&scene("", nested, &sphere("m"))).is_ok());
+    }
 }
@@ -0,0 +1,138 @@
+/// A piecewise constant distribution over [0, 1) built from a list of non-negative weights
+#[derive(Debug)]
+pub struct Distribution1D {
+    weights: Vec<f32>,
+    cumulative: Vec<f32>,
+    total: f32
+}
+
+impl Distribution1D {
+    pub fn new(weights: Vec<f32>) -> Distribution1D {
+        let mut total: f32 = 0.0;
+        let mut cumulative: Vec<f32> = weights
+            .iter()
+            .map(|weight| {
+                total += weight.max(0.0);
+                total
+            })
+            .collect();
+        if total <= 0.0 {
+            // nothing to prefer so fall back to uniform
+            let count: usize = cumulative.len();
+            cumulative.iter_mut().enumerate().for_each(|(i, c)| *c = (i + 1) as f32);
+            total = count as f32;
+            return Distribution1D { weights: vec![1.0; count], cumulative, total };
+        }
+        Distribution1D {
+            weights,
+            cumulative,
+            total
+        }
+    }
+
+    pub fn get_total(&self) -> f32 {
+        self.total
+    }
+
+    pub fn get_count(&self) -> usize {
+        self.weights.len()
+    }
+
+    /// Maps a uniform number in [0, 1) to a bin and a continuous position in [0, 1),
+    /// returning the bin, the position and the density of the position
+    pub fn sample(&self, u: f32) -> (usize, f32, f32) {
+        let target: f32 = u * self.total;
+        let index: usize = self.cumulative
+            .partition_point(|c| *c <= target)
+            .min(self.weights.len() - 1);
+        let start: f32 = if index == 0 { 0.0 } else { self.cumulative[index - 1] };
+        let weight: f32 = self.weights[index].max(0.0);
+        let offset: f32 = if weight > 0.0 { ((target - start) / weight).clamp(0.0, 1.0) } else { 0.5 };
+        let position: f32 = ((index as f32 + offset) / self.get_count() as f32).min(1.0 - f32::EPSILON);
+        (index, position, self.pdf(index))
+    }
+
+    /// The density over [0, 1) of any position within the given bin
+    pub fn pdf(&self, index: usize) -> f32 {
+        self.weights[index].max(0.0) * self.get_count() as f32 / self.total
+    }
+}
+
+/// A piecewise constant distribution over the unit square built from a grid of weights,
+/// sampled by picking a row from the marginal distribution and then a column within it
+#[derive(Debug)]
+pub struct Distribution2D {
+    rows: Vec<Distribution1D>,
+    marginal: Distribution1D
+}
+
+impl Distribution2D {
+    /// `weights` is laid out row by row, each `width` long
+    pub fn new(weights: &[f32], width: usize, height: usize) -> Distribution2D {
+        let rows: Vec<Distribution1D> = (0..height)
+            .map(|y| Distribution1D::new(weights[y * width..(y + 1) * width].to_vec()))
+            .collect();
+        // summed from the weights directly since empty rows fall back to uniform
+        let marginal: Distribution1D = Distribution1D::new(
+            (0..height)
+                .map(|y| weights[y * width..(y + 1) * width].iter().map(|w| w.max(0.0)).sum())
+                .collect()
+        );
+        Distribution2D {
+            rows,
+            marginal
+        }
+    }
+
+    /// Returns a position (u along a row, v down the rows) and its density over the unit square
+    pub fn sample(&self, u: f32, v: f32) -> ((f32, f32), f32) {
+        let (row, position_v, pdf_v) = self.marginal.sample(v);
+        let (_, position_u, pdf_u) = self.rows[row].sample(u);
+        ((position_u, position_v), pdf_v * pdf_u)
+    }
+
+    pub fn pdf(&self, u: f32, v: f32) -> f32 {
+        let row: usize = ((v * self.marginal.get_count() as f32) as usize).min(self.marginal.get_count() - 1);
+        let columns: &Distribution1D = &self.rows[row];
+        let column: usize = ((u * columns.get_count() as f32) as usize).min(columns.get_count() - 1);
+        self.marginal.pdf(row) * columns.pdf(column)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bins_are_picked_by_weight() {
+        let distribution: Distribution1D = Distribution1D::new(vec![1.0, 0.0, 3.0, -2.0]);
+        assert_eq!(distribution.get_total(), 4.0);
+        // the densities of the bins average to one over [0, 1)
+        let average: f32 = (0..4).map(|i| distribution.pdf(i)).sum::<f32>() / 4.0;
+        assert!((average - 1.0).abs() < 1e-6);
+        assert_eq!(distribution.pdf(1), 0.0);
+        assert_eq!(distribution.pdf(3), 0.0);
+        for i in 0..100 {
+            let (index, position, pdf) = distribution.sample(i as f32 / 100.0);
+            assert!(index == 0 || index == 2);
+            assert!((position * 4.0) as usize == index);
+            assert_eq!(pdf, distribution.pdf(index));
+        }
+        assert_eq!(distribution.sample(0.1).0, 0);
+        assert_eq!(distribution.sample(0.5).0, 2);
+    }
+
+    #[test]
+    fn nothing_to_prefer_falls_back_to_uniform() {
+        let distribution: Distribution1D = Distribution1D::new(vec![0.0, 0.0]);
+        assert_eq!(distribution.pdf(0), 1.0);
+        assert_eq!(distribution.sample(0.75).0, 1);
+        let grid: Distribution2D = Distribution2D::new(&[0.0, 0.0, 1.0, 3.0], 2, 2);
+        // the empty top row is never picked, the bottom one by its weights
+        let ((u, v), pdf) = grid.sample(0.5, 0.5);
+        assert!(v >= 0.5 && u >= 0.5);
+        assert_eq!(pdf, grid.pdf(u, v));
+        assert_eq!(grid.pdf(0.2, 0.2), 0.0);
+        assert!((grid.pdf(0.2, 0.7) + grid.pdf(0.7, 0.7) - 4.0).abs() < 1e-5);
+    }
+}
@@ -0,0 +1,199 @@
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+use crate::material::Color;
+
+/// A floating point image loaded from a Radiance RGBE (.hdr) file
+pub struct HdrImage {
+    width: usize,
+    height: usize,
+    pixels: Vec<Color>
+}
+
+impl HdrImage {
+    /// An image from pixels laid out row by row, top row first
+    #[cfg(test)]
+    pub(crate) fn new(width: usize, height: usize, pixels: Vec<Color>) -> HdrImage {
+        assert_eq!(pixels.len(), width * height);
+        HdrImage {
+            width,
+            height,
+            pixels
+        }
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<HdrImage, Box<dyn Error>> {
+        let file: File = File::open(path)?;
+        HdrImage::read(BufReader::new(file))
+    }
+
+    fn read(mut reader: impl BufRead) -> Result<HdrImage, Box<dyn Error>> {
+        let mut line: String = String::new();
+        reader.read_line(&mut line)?;
+        if !line.starts_with("#?") {
+            return Err("Not a Radiance HDR file".into());
+        }
+        // header lines run until a blank line, only the pixel format matters
+        loop {
+            line.clear();
+            if reader.read_line(&mut line)? == 0 {
+                return Err("Unexpected end of HDR header".into());
+            }
+            let trimmed: &str = line.trim();
+            if trimmed.is_empty() {
+                break;
+            }
+            if let Some(format) = trimmed.strip_prefix("FORMAT=") {
+                if format != "32-bit_rle_rgbe" {
+                    return Err(format!("Unsupported HDR format {}", format).into());
+                }
+            }
+        }
+        line.clear();
+        reader.read_line(&mut line)?;
+        let words: Vec<&str> = line.split_whitespace().collect();
+        if words.len() != 4 || words[0] != "-Y" || words[2] != "+X" {
+            return Err(format!("Unsupported HDR orientation {}", line.trim()).into());
+        }
+        let height: usize = words[1].parse()?;
+        let width: usize = words[3].parse()?;
+        if width == 0 || height == 0 {
+            return Err(format!("HDR image has no pixels, its resolution is {}", line.trim()).into());
+        }
+
+        let mut data: Vec<u8> = Vec::new();
+        reader.read_to_end(&mut data)?;
+        let mut pixels: Vec<Color> = Vec::with_capacity(width * height);
+        let mut position: usize = 0;
+        let mut scanline: Vec<[u8; 4]> = vec![[0; 4]; width];
+        for _ in 0..height {
+            position = HdrImage::read_scanline(&data, position, &mut scanline)?;
+            pixels.extend(scanline.iter().map(HdrImage::decode));
+        }
+        Ok(HdrImage {
+            width,
+            height,
+            pixels
+        })
+    }
+
+    /// Reads one scanline starting at `position` and returns the position after it
+    fn read_scanline(data: &[u8], position: usize, scanline: &mut [[u8; 4]]) -> Result<usize, Box<dyn Error>> {
+        let width: usize = scanline.len();
+        let truncated = || -> Box<dyn Error> { "HDR pixel data is truncated".into() };
+        let header: &[u8] = data.get(position..position + 4).ok_or_else(truncated)?;
+        let run_length_encoded: bool = (8..0x8000).contains(&width)
+            && header[0] == 2 && header[1] == 2 && header[2] & 0x80 == 0
+            && ((header[2] as usize) << 8 | header[3] as usize) == width;
+        if !run_length_encoded {
+            // flat pixels, one rgbe quadruple after another
+            for (i, pixel) in scanline.iter_mut().enumerate() {
+                let start: usize = position + i * 4;
+                pixel.copy_from_slice(data.get(start..start + 4).ok_or_else(truncated)?);
+            }
+            return Ok(position + width * 4);
+        }
+        // each channel is stored separately as a mix of runs and literal spans
+        let mut position: usize = position + 4;
+        for channel in 0..4 {
+            let mut x: usize = 0;
+            while x < width {
+                let count: u8 = *data.get(position).ok_or_else(truncated)?;
+                position += 1;
+                if count > 128 {
+                    let count: usize = (count - 128) as usize;
+                    let value: u8 = *data.get(position).ok_or_else(truncated)?;
+                    position += 1;
+                    if x + count > width {
+                        return Err("HDR run overflows scanline".into());
+                    }
+                    scanline[x..x + count].iter_mut().for_each(|pixel| pixel[channel] = value);
+                    x += count;
+                } else {
+                    let count: usize = count as usize;
+                    if count == 0 || x + count > width {
+                        return Err("HDR span overflows scanline".into());
+                    }
+                    let values: &[u8] = data.get(position..position + count).ok_or_else(truncated)?;
+                    scanline[x..x + count].iter_mut().zip(values).for_each(|(pixel, value)| pixel[channel] = *value);
+                    position += count;
+                    x += count;
+                }
+            }
+        }
+        Ok(position)
+    }
+
+    fn decode(rgbe: &[u8; 4]) -> Color {
+        if rgbe[3] == 0 {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+        // the shared exponent is biased by 128 and the mantissas are 8 bit fractions
+        let scale: f32 = 2.0_f32.powi(rgbe[3] as i32 - 136);
+        Color::new(rgbe[0] as f32 * scale, rgbe[1] as f32 * scale, rgbe[2] as f32 * scale)
+    }
+
+    pub fn get_width(&self) -> usize {
+        self.width
+    }
+
+    pub fn get_height(&self) -> usize {
+        self.height
+    }
+
+    pub fn get_pixel(&self, x: usize, y: usize) -> Color {
+        self.pixels[y * self.width + x]
+    }
+
+    pub fn get_pixels(&self) -> &[Color] {
+        &self.pixels
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rgb(color: Color) -> (f32, f32, f32) {
+        (color.x, color.y, color.z)
+    }
+
+    fn file(resolution: &str, data: &[u8]) -> Vec<u8> {
+        let mut bytes: Vec<u8> = format!("#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n{}\n", resolution).into_bytes();
+        bytes.extend_from_slice(data);
+        bytes
+    }
+
+    #[test]
+    fn reads_flat_pixels() {
+        let image: HdrImage = HdrImage::read(&file("-Y 1 +X 2", &[128, 64, 0, 129, 0, 0, 0, 0])[..]).unwrap();
+        assert_eq!((image.get_width(), image.get_height()), (2, 1));
+        assert_eq!(rgb(image.get_pixel(0, 0)), (1.0, 0.5, 0.0));
+        assert_eq!(rgb(image.get_pixel(1, 0)), (0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn reads_run_length_encoded_scanlines() {
+        // eight pixels, each channel one run of eight apart from red which is eight literals
+        let mut data: Vec<u8> = vec![2, 2, 0, 8, 8, 128, 128, 128, 128, 128, 128, 128, 128];
+        data.extend_from_slice(&[136, 0, 136, 0, 136, 129]);
+        let image: HdrImage = HdrImage::read(&file("-Y 1 +X 8", &data)[..]).unwrap();
+        assert!(image.get_pixels().iter().all(|pixel| rgb(*pixel) == (1.0, 0.0, 0.0)));
+    }
+
+    #[test]
+    fn rejects_empty_resolutions() {
+        assert!(HdrImage::read(&file("-Y 0 +X 4", &[])[..]).is_err());
+        assert!(HdrImage::read(&file("-Y 4 +X 0", &[])[..]).is_err());
+    }
+
+    #[test]
+    fn rejects_bad_headers_and_truncated_data() {
+        assert!(HdrImage::read(&b"P6\n"[..]).is_err());
+        assert!(HdrImage::read(&file("+Y 1 +X 1", &[0, 0, 0, 0])[..]).is_err());
+        assert!(HdrImage::read(&file("-Y 1 +X 2", &[128, 64, 0, 129])[..]).is_err());
+        // a run longer than the scanline
+        assert!(HdrImage::read(&file("-Y 1 +X 8", &[2, 2, 0, 8, 137, 1])[..]).is_err());
+    }
+}
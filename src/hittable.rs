@@ -1,4 +1,5 @@
-use std::rc::Rc;
+use std::sync::Arc;
+use crate::bvh::aabb::Aabb;
 use crate::material::Material;
 use crate::ray::Ray;
 use crate::vec::Vec3;
@@ -10,33 +11,115 @@ pub mod cube;
 pub mod triangle;
 pub mod mesh;
 
+/// Surfaces along a direction after which `surface_direction_pdf` stops adding up densities
+const MAX_SURFACE_CROSSINGS: usize = 64;
 
-pub trait Hittable {
-    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord>;
+pub trait Hittable: Send + Sync {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>>;
+
+    /// The bounds of the object, or `None` if it is unbounded like a plane
+    fn bounding_box(&self) -> Option<Aabb>;
+
+    /// Whether the object gives off light and should be registered as an area light
+    fn is_emissive(&self) -> bool {
+        false
+    }
+
+    /// Whether `sample_direction` can pick points on the object, emissive objects that can't,
+    /// like unbounded planes, aren't used as area lights
+    fn is_sampleable(&self) -> bool {
+        false
+    }
+
+    /// Picks a direction from `origin` towards a random point on the object, used for light
+    /// sampling, or `None` if the object can't be sampled
+    fn sample_direction(&self, _origin: &Vec3) -> Option<Vec3> {
+        None
+    }
+
+    /// The solid angle density with which `sample_direction` picks `direction` from `origin`
+    fn direction_pdf(&self, _origin: &Vec3, _direction: &Vec3) -> f32 {
+        0.0
+    }
 
     fn get_object_count(&self) -> usize {
         1
     }
 }
 
+/// Converts the density of uniformly picking a point on a surface of the given area into a
+/// solid angle density as seen from `origin`, for objects that sample their surface uniformly.
+/// Points behind the first surface along the direction are picked as well, like the far side
+/// of a box, so every surface the direction passes through adds to the density
+pub fn surface_direction_pdf(object: &dyn Hittable, area: f32, origin: &Vec3, direction: &Vec3) -> f32 {
+    if area <= 0.0 {
+        return 0.0;
+    }
+    let ray: Ray = Ray::new(*origin, *direction);
+    let length: f32 = ray.get_direction().get_length();
+    let mut pdf: f32 = 0.0;
+    let mut t_min: f32 = 0.001;
+    for _ in 0..MAX_SURFACE_CROSSINGS {
+        let hit: HitRecord = match object.hit(&ray, t_min, f32::INFINITY) {
+            Some(hit) => hit,
+            None => break
+        };
+        let cosine: f32 = hit.get_geometric_normal().dot(ray.get_direction()).abs() / length;
+        if cosine < 1e-6 {
+            // seen edge on the surface covers no solid angle around the direction
+            return 0.0;
+        }
+        let distance: f32 = hit.get_t() * length;
+        pdf += distance * distance / (cosine * area);
+        t_min = hit.get_t() + 0.001;
+    }
+    pdf
+}
+
 #[derive(Debug, Clone)]
 pub struct HitRecord<'a> {
     point: Vec3,
+    /// The normal used for shading, which can be interpolated across the surface
     normal: Vec3,
+    /// The true normal of the surface that was hit
+    geometric_normal: Vec3,
+    /// Texture coordinates of the point on the surface
+    uv: (f32, f32),
+    /// How the point moves with the texture coordinates, the derivatives along u and v
+    tangents: Option<(Vec3, Vec3)>,
     t: f32,
-    material: &'a Rc<dyn Material>
+    material: &'a Arc<dyn Material>
 }
 
 impl HitRecord<'_> {
-    pub fn new(point: Vec3, normal: Vec3, t: f32, material: &Rc<dyn Material>) -> HitRecord {
+    pub fn new(point: Vec3, normal: Vec3, t: f32, material: &Arc<dyn Material>) -> HitRecord<'_> {
         HitRecord {
             point,
             normal,
+            geometric_normal: normal,
+            uv: (0.0, 0.0),
+            tangents: None,
             t,
             material
         }
     }
 
+    /// Replaces the shading normal, keeping the normal passed to `new` as the geometric one
+    pub fn with_shading_normal(mut self, normal: Vec3) -> Self {
+        self.normal = normal;
+        self
+    }
+
+    pub fn with_uv(mut self, u: f32, v: f32) -> Self {
+        self.uv = (u, v);
+        self
+    }
+
+    pub fn with_tangents(mut self, u_tangent: Vec3, v_tangent: Vec3) -> Self {
+        self.tangents = Some((u_tangent, v_tangent));
+        self
+    }
+
     pub fn get_point(&self) -> &Vec3 {
         &self.point
     }
@@ -45,11 +128,69 @@ impl HitRecord<'_> {
         &self.normal
     }
 
+    pub fn get_geometric_normal(&self) -> &Vec3 {
+        &self.geometric_normal
+    }
+
+    /// Whether the ray arrived from the side the geometric normal points to
+    pub fn is_front_face(&self, ray: &Ray) -> bool {
+        self.geometric_normal.dot(ray.get_direction()) <= 0.0
+    }
+
+    /// The shading normal flipped if needed so it lies on the side the ray came from
+    pub fn get_facing_normal(&self, ray: &Ray) -> Vec3 {
+        if !self.is_front_face(ray) {
+            -self.normal
+        } else {
+            self.normal
+        }
+    }
+
+    pub fn get_uv(&self) -> (f32, f32) {
+        self.uv
+    }
+
+    pub fn get_tangents(&self) -> Option<(Vec3, Vec3)> {
+        self.tangents
+    }
+
     pub fn get_t(&self) -> f32 {
         self.t
     }
 
-    pub fn get_material(&self) -> &Rc<dyn Material> {
+    pub fn get_material(&self) -> &Arc<dyn Material> {
         self.material
     }
 }
+
+/// Checks shared by the tests of every primitive
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::material::diffuse::Diffuse;
+    use crate::texture::constant::Constant;
+
+    pub(crate) fn material() -> Arc<dyn Material> {
+        Arc::new(Diffuse::new(Arc::new(Constant::new(Vec3::new(1.0, 1.0, 1.0))), 1.0))
+    }
+
+    /// Checks that the texture coordinates move as the tangents say they do, by hitting the
+    /// surface again a small step along each tangent from where `ray` hits it
+    pub(crate) fn assert_uv_follows_tangents(object: &dyn Hittable, ray: &Ray) -> (f32, f32) {
+        let step: f32 = 1e-3;
+        let hit: HitRecord = object.hit(ray, 0.001, f32::INFINITY).unwrap();
+        let (u, v) = hit.get_uv();
+        let (u_tangent, v_tangent) = hit.get_tangents().unwrap();
+        let normal: Vec3 = *hit.get_geometric_normal();
+        for (tangent, expected) in [(u_tangent, (step, 0.0)), (v_tangent, (0.0, step))] {
+            let target: Vec3 = *hit.get_point() + tangent * step;
+            let moved: HitRecord = object.hit(&Ray::new(target + normal * 0.1, -normal), 0.001, f32::INFINITY).unwrap();
+            let (moved_u, moved_v) = moved.get_uv();
+            assert!(
+                (moved_u - u - expected.0).abs() < 0.02 * step && (moved_v - v - expected.1).abs() < 0.02 * step,
+                "stepping along {:?} moved the texture coordinates by {} {} rather than {:?}", tangent, moved_u - u, moved_v - v, expected
+            );
+        }
+        (u, v)
+    }
+}
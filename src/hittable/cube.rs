@@ -1,44 +1,211 @@
-use std::rc::Rc;
-use crate::hittable::HitRecord;
+use std::sync::Arc;
+use rand::random;
+use crate::bvh::aabb::Aabb;
+use crate::hittable::{HitRecord, surface_direction_pdf};
 use crate::material::Material;
 use crate::ray::Ray;
 use crate::scene::Hittable;
 use crate::vec::Vec3;
 
+/// A box around `center` whose edges span `width`, `height` and `depth`, which don't need to
+/// line up with the axes
 pub struct Cube {
     center: Vec3,
     width: Vec3,
     height: Vec3,
     depth: Vec3,
-    material: Rc<dyn Material>
+    /// Rows of the inverse of the edge matrix, taking offsets from the center into local
+    /// coordinates where the box spans -0.5 to 0.5 on every axis
+    local_axes: [Vec3; 3],
+    material: Arc<dyn Material>
 }
 
 impl Cube {
-    pub fn new(center: Vec3, width: Vec3, height: Vec3, depth: Vec3, material: Rc<dyn Material>) -> Cube {
+    /// The edges need to span a volume, see `spans_volume`
+    pub fn new(center: Vec3, width: Vec3, height: Vec3, depth: Vec3, material: Arc<dyn Material>) -> Cube {
+        let volume: f32 = width.dot(&height.cross(&depth));
+        let local_axes: [Vec3; 3] = [
+            height.cross(&depth) / volume,
+            depth.cross(&width) / volume,
+            width.cross(&height) / volume
+        ];
         Cube {
             center,
             width,
             height,
             depth,
+            local_axes,
             material
         }
     }
+
+    /// Whether the edges span a box rather than lying in a plane or along a line, which leaves
+    /// no way to take points into local coordinates
+    pub fn spans_volume(width: &Vec3, height: &Vec3, depth: &Vec3) -> bool {
+        let volume: f32 = width.dot(&height.cross(depth)).abs();
+        volume > 1e-6 * width.get_length() * height.get_length() * depth.get_length()
+    }
+
+    fn to_local(&self, vector: &Vec3) -> Vec3 {
+        Vec3::new(
+            self.local_axes[0].dot(vector),
+            self.local_axes[1].dot(vector),
+            self.local_axes[2].dot(vector)
+        )
+    }
+
+    fn get_edges(&self) -> [Vec3; 3] {
+        [self.width, self.height, self.depth]
+    }
+
+    /// The areas of the faces across each local axis
+    fn face_areas(&self) -> [f32; 3] {
+        [
+            self.height.cross(&self.depth).get_length(),
+            self.depth.cross(&self.width).get_length(),
+            self.width.cross(&self.height).get_length()
+        ]
+    }
+
+    /// The outward unit normal of the face on the `positive` or negative side of `axis`
+    fn face_normal(&self, axis: usize, positive: bool) -> Vec3 {
+        let normal: Vec3 = self.local_axes[axis] / self.local_axes[axis].get_length();
+        if positive {
+            normal
+        } else {
+            -normal
+        }
+    }
 }
 
 impl Hittable for Cube {
-    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
-        let normal: Vec3 = self.width.cross(&self.height);
-        let t: f32 = (self.center - ray.get_origin()).dot(&normal) / ray.get_direction().dot(&normal);
-        if t < t_min || t > t_max {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        let origin: Vec3 = self.to_local(&(*ray.get_origin() - self.center));
+        let direction: Vec3 = self.to_local(ray.get_direction());
+        // the slab test, keeping track of which face the ray enters and leaves through
+        let mut t_enter: f32 = f32::NEG_INFINITY;
+        let mut t_exit: f32 = f32::INFINITY;
+        let mut enter_face: (usize, bool) = (0, false);
+        let mut exit_face: (usize, bool) = (0, false);
+        for axis in 0..3 {
+            let inverse_direction: f32 = 1.0 / direction.get_axis(axis);
+            let t_low: f32 = (-0.5 - origin.get_axis(axis)) * inverse_direction;
+            let t_high: f32 = (0.5 - origin.get_axis(axis)) * inverse_direction;
+            let (t_near, t_far, near_positive) = if t_low < t_high {
+                (t_low, t_high, false)
+            } else {
+                (t_high, t_low, true)
+            };
+            if t_near > t_enter {
+                t_enter = t_near;
+                enter_face = (axis, near_positive);
+            }
+            if t_far < t_exit {
+                t_exit = t_far;
+                exit_face = (axis, !near_positive);
+            }
+        }
+        if t_enter > t_exit {
             return None;
         }
-        let point: Vec3 = ray.at(t);
-        let to_point: Vec3 = point - self.center;
-        let width: f32 = self.width.dot(&to_point);
-        let height: f32 = self.height.dot(&to_point);
-        if width < 0.0 || width > self.width.length_squared() || height < 0.0 || height > self.height.length_squared() {
+        // a ray starting inside the box, like one refracted into glass, hits it on the way out
+        let (t, (axis, positive)) = if t_enter >= t_min {
+            (t_enter, enter_face)
+        } else {
+            (t_exit, exit_face)
+        };
+        if t < t_min || t > t_max {
             return None;
         }
-        Some(HitRecord::new(point, normal, t, &self.material))
+        // each face is textured across the other two local axes
+        let local: Vec3 = origin + direction * t;
+        let u: f32 = local.get_axis((axis + 1) % 3) + 0.5;
+        let v: f32 = local.get_axis((axis + 2) % 3) + 0.5;
+        let edges: [Vec3; 3] = self.get_edges();
+        Some(HitRecord::new(ray.at(t), self.face_normal(axis, positive), t, &self.material)
+            .with_uv(u, v)
+            .with_tangents(edges[(axis + 1) % 3], edges[(axis + 2) % 3]))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let corners: Vec<Vec3> = (0..8)
+            .map(|i| {
+                let sign = |bit: usize| -> f32 {
+                    if i & bit == 0 { -0.5 } else { 0.5 }
+                };
+                self.center + self.width * sign(1) + self.height * sign(2) + self.depth * sign(4)
+            })
+            .collect();
+        Some(Aabb::from_points(&corners).pad(0.0001))
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.material.is_emissive()
+    }
+
+    fn is_sampleable(&self) -> bool {
+        true
+    }
+
+    fn sample_direction(&self, origin: &Vec3) -> Option<Vec3> {
+        // pick a face by area, then a point on it
+        let areas: [f32; 3] = self.face_areas();
+        let target: f32 = random::<f32>() * (areas[0] + areas[1] + areas[2]);
+        let axis: usize = if target < areas[0] {
+            0
+        } else if target < areas[0] + areas[1] {
+            1
+        } else {
+            2
+        };
+        let edges: [Vec3; 3] = self.get_edges();
+        let side: f32 = if random::<bool>() { 0.5 } else { -0.5 };
+        let point: Vec3 = self.center
+            + edges[axis] * side
+            + edges[(axis + 1) % 3] * (random::<f32>() - 0.5)
+            + edges[(axis + 2) % 3] * (random::<f32>() - 0.5);
+        Some(point - origin)
+    }
+
+    fn direction_pdf(&self, origin: &Vec3, direction: &Vec3) -> f32 {
+        let areas: [f32; 3] = self.face_areas();
+        surface_direction_pdf(self, 2.0 * (areas[0] + areas[1] + areas[2]), origin, direction)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::tests::material;
+
+    fn cube(width: Vec3, height: Vec3, depth: Vec3) -> Cube {
+        Cube::new(Vec3::new(0.0, 0.0, 0.0), width, height, depth, material())
+    }
+
+    #[test]
+    fn flat_or_collapsed_edges_span_no_volume() {
+        let x: Vec3 = Vec3::new(1.0, 0.0, 0.0);
+        let y: Vec3 = Vec3::new(0.0, 2.0, 0.0);
+        let z: Vec3 = Vec3::new(0.0, 0.0, 3.0);
+        assert!(Cube::spans_volume(&x, &y, &z));
+        assert!(Cube::spans_volume(&y, &x, &z));
+        assert!(!Cube::spans_volume(&x, &y, &(x + y)));
+        assert!(!Cube::spans_volume(&x, &y, &Vec3::new(0.0, 0.0, 0.0)));
+        assert!(!Cube::spans_volume(&x, &(x * 2.0), &z));
+    }
+
+    #[test]
+    fn rotated_boxes_are_hit_on_the_facing_side() {
+        let diagonal: f32 = 0.5_f32.sqrt();
+        let cube: Cube = cube(Vec3::new(diagonal, diagonal, 0.0), Vec3::new(-diagonal, diagonal, 0.0), Vec3::new(0.0, 0.0, 1.0));
+        let ray: Ray = Ray::new(Vec3::new(-5.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        let hit: HitRecord = cube.hit(&ray, 0.001, f32::INFINITY).unwrap();
+        // the box is turned 45 degrees so its corner points at the ray
+        assert!((hit.get_point().x + diagonal).abs() < 1e-3);
+        assert!(hit.get_geometric_normal().x < 0.0);
+        // starting inside the box it's hit on the way out
+        let inside: Ray = Ray::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 1.0));
+        let exit: HitRecord = cube.hit(&inside, 0.001, f32::INFINITY).unwrap();
+        assert!((exit.get_point().z - 0.5).abs() < 1e-3);
     }
 }
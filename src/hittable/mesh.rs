@@ -1,26 +1,41 @@
 use std::cmp::min_by;
+use std::collections::HashMap;
 use std::error::Error;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
 use std::path::Path;
-use std::rc::Rc;
+use std::sync::Arc;
+use log::info;
+use rand::random;
+use crate::bvh::aabb::Aabb;
+use crate::bvh::Bvh;
 use crate::hittable::triangle::Triangle;
 use crate::material::Material;
+use crate::hittable::surface_direction_pdf;
 use crate::scene::Hittable;
 use crate::vec::Vec3;
 
+pub mod obj;
+
 pub struct Mesh {
-    triangles: Vec<Triangle>
+    triangles: Vec<Triangle>,
+    bvh: Option<Bvh>,
+    /// Running total of triangle areas, used to pick triangles by area for light sampling
+    cumulative_areas: Vec<f32>
 }
 
 impl Mesh {
     pub fn new(triangles: Vec<Triangle>) -> Mesh {
         Mesh {
-            triangles
+            triangles,
+            bvh: None,
+            cumulative_areas: Vec::new()
         }
     }
 
-    pub fn load(path: impl AsRef<Path>, material: Rc<dyn Material>) -> Result<Mesh, Box<dyn Error>> {
+    /// Loads the project's `.tri` format, a triangle count followed by a blank line and three
+    /// vertex lines for each triangle
+    pub fn load(path: impl AsRef<Path>, material: Arc<dyn Material>) -> Result<Mesh, Box<dyn Error>> {
         let file: File = File::open(path)?;
         let mut reader: BufReader<File> = BufReader::new(file);
         let mut triangles: Vec<Triangle> = Vec::new();
@@ -34,7 +49,7 @@ impl Mesh {
             (0..3).for_each(|_| {
                 line.clear();
                 reader.read_line(&mut line).unwrap();
-                let mut words: Vec<&str> = line.split_whitespace().collect();
+                let words: Vec<&str> = line.split_whitespace().collect();
                 let x: f32 = words[0].parse().unwrap();
                 let y: f32 = words[1].parse().unwrap();
                 let z: f32 = words[2].parse().unwrap();
@@ -49,19 +64,93 @@ impl Mesh {
         Ok(Mesh::new(triangles))
     }
 
+    /// Gives triangles without vertex normals smooth ones by averaging the normals of the
+    /// faces around each vertex, faces more than `angle` degrees apart are left creased
+    pub fn compute_normals(&mut self, angle: f32) {
+        let cos_threshold: f32 = angle.to_radians().cos();
+        let key = |vertex: &Vec3| -> [u32; 3] {
+            [vertex.x.to_bits(), vertex.y.to_bits(), vertex.z.to_bits()]
+        };
+        let mut faces_at: HashMap<[u32; 3], Vec<usize>> = HashMap::new();
+        for (i, triangle) in self.triangles.iter().enumerate() {
+            for vertex in triangle.get_vertices() {
+                faces_at.entry(key(&vertex)).or_default().push(i);
+            }
+        }
+        let normals: Vec<Option<[Vec3; 3]>> = self.triangles
+            .iter()
+            .map(|triangle| {
+                if triangle.get_normals().is_some() {
+                    return None;
+                }
+                let face_normal: Vec3 = *triangle.get_face_normal();
+                Some(triangle.get_vertices().map(|vertex| {
+                    // larger faces pull the normal further towards themselves
+                    let normal: Vec3 = faces_at[&key(&vertex)]
+                        .iter()
+                        .map(|i| &self.triangles[*i])
+                        .filter(|other| other.get_face_normal().dot(&face_normal) >= cos_threshold)
+                        .fold(Vec3::new(0.0, 0.0, 0.0), |normal, other| {
+                            normal + *other.get_face_normal() * other.get_area()
+                        });
+                    let length: f32 = normal.get_length();
+                    if length > 0.0 {
+                        normal / length
+                    } else {
+                        face_normal
+                    }
+                }))
+            })
+            .collect();
+        self.triangles.iter_mut().zip(normals).for_each(|(triangle, normals)| {
+            if let Some(normals) = normals {
+                triangle.set_normals(normals);
+            }
+        });
+    }
+
+    /// Builds the bounding volume hierarchy used by `hit` and the area table used for light
+    /// sampling, this needs to be called again after the mesh is transformed
+    pub fn build_bvh(&mut self) {
+        let mut total_area: f32 = 0.0;
+        self.cumulative_areas = self.triangles
+            .iter()
+            .map(|triangle| {
+                total_area += triangle.get_area();
+                total_area
+            })
+            .collect();
+        let bounds: Vec<(usize, Aabb)> = self.triangles
+            .iter()
+            .enumerate()
+            .filter_map(|(i, triangle)| {
+                triangle.bounding_box().map(|bounds| (i, bounds))
+            })
+            .collect();
+        let bvh: Bvh = Bvh::build(bounds);
+        info!("Built mesh BVH with {} nodes over {} triangles", bvh.get_node_count(), self.triangles.len());
+        self.bvh = Some(bvh);
+    }
+
     pub fn translate(&mut self, translation: Vec3) {
+        self.bvh = None;
+        self.cumulative_areas.clear();
         self.triangles.iter_mut().for_each(|triangle| {
             triangle.translate(translation);
         });
     }
 
     pub fn rotate(&mut self, rotation: Vec3) {
+        self.bvh = None;
+        self.cumulative_areas.clear();
         self.triangles.iter_mut().for_each(|triangle| {
             triangle.rotate(rotation);
         });
     }
 
     pub fn scale(&mut self, scale: Vec3) {
+        self.bvh = None;
+        self.cumulative_areas.clear();
         self.triangles.iter_mut().for_each(|triangle| {
             triangle.scale(scale);
         });
@@ -69,7 +158,12 @@ impl Mesh {
 }
 
 impl Hittable for Mesh {
-    fn hit(&self, ray: &crate::ray::Ray, t_min: f32, t_max: f32) -> Option<crate::hittable::HitRecord> {
+    fn hit(&self, ray: &crate::ray::Ray, t_min: f32, t_max: f32) -> Option<crate::hittable::HitRecord<'_>> {
+        if let Some(bvh) = &self.bvh {
+            return bvh.hit(ray, t_min, t_max, |i, t_min, t_max| {
+                self.triangles[i].hit(ray, t_min, t_max)
+            });
+        }
         self.triangles
             .iter()
             .filter_map(|triangle| {
@@ -80,7 +174,93 @@ impl Hittable for Mesh {
             })
     }
 
+    fn bounding_box(&self) -> Option<Aabb> {
+        if let Some(bvh) = &self.bvh {
+            return bvh.bounding_box();
+        }
+        self.triangles
+            .iter()
+            .filter_map(|triangle| triangle.bounding_box())
+            .reduce(|acc, i| acc.union(&i))
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.triangles.first().is_some_and(|triangle| triangle.is_emissive())
+    }
+
+    fn is_sampleable(&self) -> bool {
+        self.cumulative_areas.last().is_some_and(|total_area| *total_area > 0.0)
+    }
+
+    fn sample_direction(&self, origin: &Vec3) -> Option<Vec3> {
+        let total_area: f32 = *self.cumulative_areas.last().filter(|total_area| **total_area > 0.0)?;
+        let target: f32 = random::<f32>() * total_area;
+        let index: usize = self.cumulative_areas
+            .partition_point(|area| *area < target)
+            .min(self.triangles.len() - 1);
+        Some(self.triangles[index].sample_point() - origin)
+    }
+
+    fn direction_pdf(&self, origin: &Vec3, direction: &Vec3) -> f32 {
+        match self.cumulative_areas.last() {
+            Some(total_area) => surface_direction_pdf(self, *total_area, origin, direction),
+            None => 0.0
+        }
+    }
+
     fn get_object_count(&self) -> usize {
         self.triangles.len()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::tests::material;
+
+    /// Two triangles meeting along the edge from (1, 0, 0) to (0, 1, 0), the second tipped up
+    /// by about 16 degrees
+    fn fold() -> Mesh {
+        let material: Arc<dyn Material> = material();
+        Mesh::new(vec![
+            Triangle::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), material.clone()),
+            Triangle::new(Vec3::new(1.0, 0.0, 0.0), Vec3::new(1.0, 1.0, 0.2), Vec3::new(0.0, 1.0, 0.0), material)
+        ])
+    }
+
+    fn assert_close(a: &Vec3, b: &Vec3) {
+        assert!((*a - *b).get_length() < 1e-4, "{:?} is not {:?}", a, b);
+    }
+
+    #[test]
+    fn computed_normals_are_shared_below_the_crease_angle() {
+        let mut smooth: Mesh = fold();
+        smooth.compute_normals(30.0);
+        let [flat, first] = [&smooth.triangles[0], &smooth.triangles[1]].map(|triangle| *triangle.get_normals().unwrap());
+        // the corner only one face touches keeps that face's normal
+        assert_close(&flat[0], smooth.triangles[0].get_face_normal());
+        // both faces agree along the shared edge, on a normal between theirs
+        assert_close(&flat[1], &first[0]);
+        assert_close(&flat[2], &first[2]);
+        let between: Vec3 = *smooth.triangles[0].get_face_normal() + *smooth.triangles[1].get_face_normal();
+        assert!(flat[1].dot(&(between / between.get_length())) > 0.9999);
+
+        let mut creased: Mesh = fold();
+        creased.compute_normals(10.0);
+        for triangle in &creased.triangles {
+            for normal in triangle.get_normals().unwrap() {
+                assert_close(normal, triangle.get_face_normal());
+            }
+        }
+    }
+
+    #[test]
+    fn computed_normals_keep_the_ones_given() {
+        let mut mesh: Mesh = fold();
+        let given: [Vec3; 3] = [Vec3::new(1.0, 0.0, 0.0); 3];
+        mesh.triangles[1].set_normals(given);
+        mesh.compute_normals(30.0);
+        assert_eq!(mesh.triangles[1].get_normals().unwrap().map(|normal| normal.x), [1.0; 3]);
+        assert!(mesh.triangles[0].get_normals().is_some());
+    }
+}
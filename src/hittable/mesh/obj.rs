@@ -0,0 +1,240 @@
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use std::sync::Arc;
+use log::{info, warn};
+use crate::hittable::mesh::Mesh;
+use crate::hittable::triangle::Triangle;
+use crate::material::Material;
+use crate::vec::Vec3;
+
+/// One corner of a face, as indices into the position, texture coordinate and normal lists
+#[derive(Clone, Copy)]
+struct Corner {
+    position: usize,
+    uv: Option<usize>,
+    normal: Option<usize>
+}
+
+/// Loads the faces of a Wavefront OBJ file as a mesh, polygons are split into fans of triangles.
+/// When `groups` is given only faces in one of the named groups or objects are kept
+pub fn load(path: impl AsRef<Path>, material: Arc<dyn Material>, groups: Option<&[String]>) -> Result<Mesh, Box<dyn Error>> {
+    let file: File = File::open(path.as_ref())?;
+    parse(BufReader::new(file), &path.as_ref().display().to_string(), material, groups)
+}
+
+/// Reads OBJ text as `load` does, `name` is only used in messages
+fn parse(reader: impl BufRead, name: &str, material: Arc<dyn Material>, groups: Option<&[String]>) -> Result<Mesh, Box<dyn Error>> {
+    let mut positions: Vec<Vec3> = Vec::new();
+    let mut uvs: Vec<(f32, f32)> = Vec::new();
+    let mut normals: Vec<Vec3> = Vec::new();
+    let mut triangles: Vec<Triangle> = Vec::new();
+    // faces belong to the last object and every group named by the last group statement
+    let mut current_object: Option<String> = None;
+    let mut current_groups: Vec<String> = Vec::new();
+    let mut degenerate_count: usize = 0;
+    for (number, line) in reader.lines().enumerate() {
+        let line: String = line?;
+        let error = |message: &str| -> Box<dyn Error> {
+            format!("{} line {}: {}", name, number + 1, message).into()
+        };
+        let line: &str = line.split('#').next().unwrap_or("");
+        let mut words = line.split_whitespace();
+        let keyword: &str = match words.next() {
+            Some(keyword) => keyword,
+            None => continue
+        };
+        let words: Vec<&str> = words.collect();
+        match keyword {
+            "v" => positions.push(parse_vec3(&words).ok_or_else(|| error("Expected a position"))?),
+            "vn" => normals.push(parse_vec3(&words).ok_or_else(|| error("Expected a normal"))?),
+            "vt" => {
+                let u: f32 = words.first().and_then(|u| u.parse().ok()).ok_or_else(|| error("Expected a texture coordinate"))?;
+                let v: f32 = match words.get(1) {
+                    Some(v) => v.parse().map_err(|_| error("Expected a texture coordinate"))?,
+                    None => 0.0
+                };
+                uvs.push((u, v));
+            },
+            "o" => {
+                current_object = Some(words.join(" "));
+                current_groups.clear();
+            },
+            "g" => {
+                current_groups = words.iter().map(|word| word.to_string()).collect();
+            },
+            "f" => {
+                let included: bool = match groups {
+                    Some(groups) => current_object.iter().chain(current_groups.iter()).any(|group| groups.contains(group)),
+                    None => true
+                };
+                if !included {
+                    continue;
+                }
+                if words.len() < 3 {
+                    return Err(error("Faces need at least three corners"));
+                }
+                let corners: Vec<Corner> = words
+                    .iter()
+                    .map(|word| parse_corner(word, positions.len(), uvs.len(), normals.len()))
+                    .collect::<Option<Vec<Corner>>>()
+                    .ok_or_else(|| error("Invalid face corner"))?;
+                for i in 1..corners.len() - 1 {
+                    let fan: [Corner; 3] = [corners[0], corners[i], corners[i + 1]];
+                    match build_triangle(&fan, &positions, &uvs, &normals, material.clone()) {
+                        Some(triangle) => triangles.push(triangle),
+                        None => degenerate_count += 1
+                    }
+                }
+            },
+            // materials, smoothing groups, lines and the rest are not used
+            _ => {}
+        }
+    }
+    if degenerate_count > 0 {
+        warn!("Skipped {} degenerate triangles in {}", degenerate_count, name);
+    }
+    if triangles.is_empty() {
+        return Err(format!("{} has no faces to load", name).into());
+    }
+    info!("Loaded {} triangles from {}", triangles.len(), name);
+    Ok(Mesh::new(triangles))
+}
+
+fn parse_vec3(words: &[&str]) -> Option<Vec3> {
+    if words.len() < 3 {
+        return None;
+    }
+    Some(Vec3::new(words[0].parse().ok()?, words[1].parse().ok()?, words[2].parse().ok()?))
+}
+
+/// Turns a one based index, or a negative one counting back from the end, into a zero based one
+fn resolve_index(word: &str, count: usize) -> Option<usize> {
+    let index: i64 = word.parse().ok()?;
+    let index: i64 = if index < 0 {
+        count as i64 + index
+    } else {
+        index - 1
+    };
+    (0..count as i64).contains(&index).then_some(index as usize)
+}
+
+/// Parses a corner written as `v`, `v/vt`, `v//vn` or `v/vt/vn`
+fn parse_corner(word: &str, position_count: usize, uv_count: usize, normal_count: usize) -> Option<Corner> {
+    let mut parts = word.split('/');
+    let position: usize = resolve_index(parts.next()?, position_count)?;
+    let uv: Option<usize> = match parts.next() {
+        Some("") | None => None,
+        Some(uv) => Some(resolve_index(uv, uv_count)?)
+    };
+    let normal: Option<usize> = match parts.next() {
+        Some("") | None => None,
+        Some(normal) => Some(resolve_index(normal, normal_count)?)
+    };
+    Some(Corner {
+        position,
+        uv,
+        normal
+    })
+}
+
+/// Builds a triangle from three corners, vertex normals and texture coordinates are only kept
+/// when every corner has them. Returns `None` for triangles without any area
+fn build_triangle(corners: &[Corner; 3], positions: &[Vec3], uvs: &[(f32, f32)], normals: &[Vec3], material: Arc<dyn Material>) -> Option<Triangle> {
+    let [a, b, c] = corners.map(|corner| positions[corner.position]);
+    if (b - a).cross(&(c - a)).length_squared() <= 0.0 {
+        return None;
+    }
+    let mut triangle: Triangle = Triangle::new(a, b, c, material);
+    if let [Some(a), Some(b), Some(c)] = corners.map(|corner| corner.normal) {
+        let vertex_normals: [Vec3; 3] = [normals[a], normals[b], normals[c]];
+        if vertex_normals.iter().all(|normal| normal.length_squared() > 0.0) {
+            triangle = triangle.with_normals(vertex_normals.map(|normal| normal / normal.get_length()));
+        }
+    }
+    if let [Some(a), Some(b), Some(c)] = corners.map(|corner| corner.uv) {
+        triangle = triangle.with_uvs([uvs[a], uvs[b], uvs[c]]);
+    }
+    Some(triangle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::Hittable;
+    use crate::hittable::tests::material;
+
+    const CUBE_SIDES: &str = "
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+v 0 0 1
+o Box
+g bottom
+f 1 2 3 4
+g top sides
+f 1 2 5
+o Other
+f -5 -4 -3
+";
+
+    fn triangle_count(text: &str, groups: Option<&[String]>) -> Result<usize, Box<dyn Error>> {
+        Ok(parse(text.as_bytes(), "test.obj", material(), groups)?.get_object_count())
+    }
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn loads_every_face_and_splits_polygons() {
+        assert_eq!(triangle_count(CUBE_SIDES, None).unwrap(), 4);
+    }
+
+    #[test]
+    fn objects_stay_selectable_under_groups() {
+        assert_eq!(triangle_count(CUBE_SIDES, Some(&names(&["Box"]))).unwrap(), 3);
+        assert_eq!(triangle_count(CUBE_SIDES, Some(&names(&["Other"]))).unwrap(), 1);
+    }
+
+    #[test]
+    fn groups_are_matched_by_any_name() {
+        assert_eq!(triangle_count(CUBE_SIDES, Some(&names(&["bottom"]))).unwrap(), 2);
+        assert_eq!(triangle_count(CUBE_SIDES, Some(&names(&["sides"]))).unwrap(), 1);
+    }
+
+    #[test]
+    fn new_objects_leave_the_previous_groups() {
+        assert_eq!(triangle_count(CUBE_SIDES, Some(&names(&["top"]))).unwrap(), 1);
+    }
+
+    #[test]
+    fn nothing_selected_is_an_error() {
+        assert!(triangle_count(CUBE_SIDES, Some(&names(&["missing"]))).is_err());
+    }
+
+    #[test]
+    fn out_of_range_indices_are_errors() {
+        assert!(triangle_count("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 4\n", None).is_err());
+        assert!(triangle_count("v 0 0 0\nv 1 0 0\nv 0 1 0\nf -1 -2 -4\n", None).is_err());
+        assert!(triangle_count("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1/1 2/1 3/1\n", None).is_err());
+    }
+
+    #[test]
+    fn degenerate_triangles_are_skipped() {
+        assert_eq!(triangle_count("v 0 0 0\nv 1 0 0\nv 2 0 0\nv 0 1 0\nf 1 2 3\nf 1 2 4\n", None).unwrap(), 1);
+    }
+
+    #[test]
+    fn corners_can_carry_uvs_and_normals() {
+        let text: &str = "v 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0 0\nvt 1 0\nvt 0 1\nvn 0 0 1\nf 1/1/1 2/2/1 3/3/1\nf 1//1 2//1 3//1\n";
+        assert_eq!(triangle_count(text, None).unwrap(), 2);
+    }
+
+    #[test]
+    fn faces_need_three_corners() {
+        assert!(triangle_count("v 0 0 0\nv 1 0 0\nf 1 2\n", None).is_err());
+    }
+}
@@ -1,4 +1,5 @@
-use std::rc::Rc;
+use std::sync::Arc;
+use crate::bvh::aabb::Aabb;
 pub(crate) use crate::hittable::{HitRecord, Hittable};
 use crate::material::Material;
 use crate::vec::Vec3;
@@ -6,11 +7,11 @@ use crate::vec::Vec3;
 pub struct Plane {
     normal: Vec3,
     point: Vec3,
-    material: Rc<dyn Material>
+    material: Arc<dyn Material>
 }
 
 impl Plane {
-    pub fn new(normal: Vec3, point: Vec3, material: Rc<dyn Material>) -> Plane {
+    pub fn new(normal: Vec3, point: Vec3, material: Arc<dyn Material>) -> Plane {
         Plane {
             normal: normal.get_normalized(),
             point,
@@ -18,7 +19,7 @@ impl Plane {
         }
     }
 
-    pub fn from_points(a: Vec3, b: Vec3, c: Vec3, material: Rc<dyn Material>) -> Plane {
+    pub fn from_points(a: Vec3, b: Vec3, c: Vec3, material: Arc<dyn Material>) -> Plane {
         let normal: Vec3 = (b - a).cross(&(c - a)).get_normalized();
         Plane {
             normal,
@@ -37,15 +38,43 @@ impl Plane {
 }
 
 impl Hittable for Plane {
-    fn hit(&self, ray: &crate::ray::Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+    fn hit(&self, ray: &crate::ray::Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
         let denominator: f32 = ray.get_direction().dot(&self.normal);
         if denominator.abs() > 0.0001 {
             let t: f32 = (self.point - *ray.get_origin()).dot(&self.normal) / denominator;
             if t < t_max && t > t_min {
                 let point: Vec3 = ray.at(t);
-                return Some(HitRecord::new(point, self.normal, t, &self.material));
+                // the plane is mapped one unit of texture to one unit of distance
+                let (tangent, bitangent) = self.normal.orthonormal_basis();
+                let offset: Vec3 = point - self.point;
+                return Some(HitRecord::new(point, self.normal, t, &self.material)
+                    .with_uv(offset.dot(&tangent), offset.dot(&bitangent))
+                    .with_tangents(tangent, bitangent));
             }
         }
         None
     }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::tests::{assert_uv_follows_tangents, material};
+    use crate::ray::Ray;
+
+    #[test]
+    fn texture_coordinates_are_distances_across_the_plane() {
+        let normal: Vec3 = Vec3::new(0.0, 0.6, 0.8);
+        let plane: Plane = Plane::new(normal, Vec3::new(1.0, 1.0, 1.0), material());
+        let (u, v) = assert_uv_follows_tangents(&plane, &Ray::new(Vec3::new(1.0, 1.0, 1.0) + normal * 3.0, -normal));
+        assert!(u.abs() < 1e-5 && v.abs() < 1e-5);
+        let (u, v) = assert_uv_follows_tangents(&plane, &Ray::new(Vec3::new(4.0, 2.0, 5.0), Vec3::new(-0.2, -0.5, -1.0)));
+        let hit: HitRecord = plane.hit(&Ray::new(Vec3::new(4.0, 2.0, 5.0), Vec3::new(-0.2, -0.5, -1.0)), 0.001, f32::INFINITY).unwrap();
+        let distance: f32 = (*hit.get_point() - Vec3::new(1.0, 1.0, 1.0)).get_length();
+        assert!(((u * u + v * v).sqrt() - distance).abs() < 1e-4);
+    }
 }
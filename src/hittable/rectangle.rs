@@ -1,5 +1,7 @@
-use std::rc::Rc;
-use crate::hittable::{HitRecord, Hittable};
+use std::sync::Arc;
+use rand::random;
+use crate::bvh::aabb::Aabb;
+use crate::hittable::{HitRecord, Hittable, surface_direction_pdf};
 use crate::material::Material;
 use crate::ray::Ray;
 use crate::vec::Vec3;
@@ -8,11 +10,11 @@ pub struct Rectangle {
     center: Vec3,
     width: Vec3,
     height: Vec3,
-    material: Rc<dyn Material>
+    material: Arc<dyn Material>
 }
 
 impl Rectangle {
-    pub fn new(center: Vec3, width: Vec3, height: Vec3, material: Rc<dyn Material>) -> Rectangle {
+    pub fn new(center: Vec3, width: Vec3, height: Vec3, material: Arc<dyn Material>) -> Rectangle {
         Rectangle {
             center,
             width,
@@ -23,8 +25,8 @@ impl Rectangle {
 }
 
 impl Hittable for Rectangle {
-    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
-        let normal: Vec3 = self.width.cross(&self.height);
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        let normal: Vec3 = self.width.cross(&self.height).get_normalized();
         let t: f32 = (self.center - ray.get_origin()).dot(&normal) / ray.get_direction().dot(&normal);
         if t < t_min || t > t_max {
             return None;
@@ -36,6 +38,54 @@ impl Hittable for Rectangle {
         if width < 0.0 || width > self.width.length_squared() || height < 0.0 || height > self.height.length_squared() {
             return None;
         }
-        Some(HitRecord::new(point, normal, t, &self.material))
+        let u: f32 = width / self.width.length_squared();
+        let v: f32 = height / self.height.length_squared();
+        Some(HitRecord::new(point, normal, t, &self.material).with_uv(u, v).with_tangents(self.width, self.height))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(Aabb::from_points(&[
+            self.center,
+            self.center + self.width,
+            self.center + self.height,
+            self.center + self.width + self.height
+        ]).pad(0.0001))
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.material.is_emissive()
+    }
+
+    fn is_sampleable(&self) -> bool {
+        true
+    }
+
+    fn sample_direction(&self, origin: &Vec3) -> Option<Vec3> {
+        let point: Vec3 = self.center + self.width * random::<f32>() + self.height * random::<f32>();
+        Some(point - origin)
+    }
+
+    fn direction_pdf(&self, origin: &Vec3, direction: &Vec3) -> f32 {
+        let area: f32 = self.width.cross(&self.height).get_length();
+        surface_direction_pdf(self, area, origin, direction)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::tests::{assert_uv_follows_tangents, material};
+
+    #[test]
+    fn texture_coordinates_span_the_rectangle() {
+        let rectangle: Rectangle = Rectangle::new(Vec3::new(1.0, 0.0, 0.0), Vec3::new(2.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 1.0), material());
+        let down = |x: f32, y: f32| -> Ray {
+            Ray::new(Vec3::new(x, y, y + 1.0), Vec3::new(0.0, 0.0, -1.0))
+        };
+        let (u, v) = assert_uv_follows_tangents(&rectangle, &down(1.5, 0.25));
+        assert!((u - 0.25).abs() < 1e-4 && (v - 0.25).abs() < 1e-4);
+        let (u, v) = rectangle.hit(&down(2.999, 0.999), 0.001, f32::INFINITY).unwrap().get_uv();
+        assert!(u > 0.999 && v > 0.998);
+        assert!(rectangle.hit(&down(0.9, 0.5), 0.001, f32::INFINITY).is_none());
     }
 }
@@ -1,18 +1,21 @@
-use std::rc::Rc;
-pub(crate) use crate::hittable::{HitRecord, Hittable};
+use std::f32::consts::PI;
+use std::sync::Arc;
+use crate::bvh::aabb::Aabb;
+pub(crate) use crate::hittable::{HitRecord, Hittable, surface_direction_pdf};
 use crate::material::Material;
 use crate::ray::Ray;
+use crate::sampling::{uniform_cone, uniform_cone_pdf, uniform_sphere};
 use crate::vec::Vec3;
 
 
 pub struct Sphere {
     center: Vec3,
     radius: f32,
-    material: Rc<dyn Material>
+    material: Arc<dyn Material>
 }
 
 impl Sphere {
-    pub fn new(center: Vec3, radius: f32, material: Rc<dyn Material>) -> Sphere {
+    pub fn new(center: Vec3, radius: f32, material: Arc<dyn Material>) -> Sphere {
         Sphere {
             center,
             radius,
@@ -21,8 +24,31 @@ impl Sphere {
     }
 }
 
+impl Sphere {
+    fn hit_record(&self, ray: &Ray, t: f32) -> HitRecord<'_> {
+        let point: Vec3 = ray.at(t);
+        let normal: Vec3 = (point - self.center) / self.radius;
+        // longitude around the z axis and latitude from the bottom to the top
+        let u: f32 = normal.y.atan2(normal.x) / (2.0 * PI) + 0.5;
+        let v: f32 = normal.z.clamp(-1.0, 1.0).asin() / PI + 0.5;
+        let hit: HitRecord = HitRecord::new(point, normal, t, &self.material).with_uv(u, v);
+        let cos_latitude: f32 = (normal.x * normal.x + normal.y * normal.y).sqrt();
+        if cos_latitude < 1e-6 {
+            // the poles have no direction of increasing longitude
+            return hit;
+        }
+        let u_tangent: Vec3 = Vec3::new(-normal.y, normal.x, 0.0) * (2.0 * PI * self.radius);
+        let v_tangent: Vec3 = Vec3::new(
+            -normal.x * normal.z / cos_latitude,
+            -normal.y * normal.z / cos_latitude,
+            cos_latitude
+        ) * (PI * self.radius);
+        hit.with_tangents(u_tangent, v_tangent)
+    }
+}
+
 impl Hittable for Sphere {
-   fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+   fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
         let oc: Vec3 = ray.get_origin() - self.center;
         let a: f32 = ray.get_direction().dot(ray.get_direction());
         let b: f32 = oc.dot(ray.get_direction());
@@ -31,17 +57,75 @@ impl Hittable for Sphere {
         if discriminant > 0.0 {
             let t: f32 = (-b - discriminant.sqrt()) / a;
             if t < t_max && t > t_min {
-                let point: Vec3 = ray.at(t);
-                let normal: Vec3 = (point - self.center) / self.radius;
-                return Some(HitRecord::new(point, normal, t, &self.material));
+                return Some(self.hit_record(ray, t));
             }
             let t: f32 = (-b + discriminant.sqrt()) / a;
             if t < t_max && t > t_min {
-                let point: Vec3 = ray.at(t);
-                let normal: Vec3 = (point - self.center) / self.radius;
-                return Some(HitRecord::new(point, normal, t, &self.material));
+                return Some(self.hit_record(ray, t));
             }
         }
         None
    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let extent: Vec3 = Vec3::new(self.radius, self.radius, self.radius);
+        Some(Aabb::new(self.center - extent, self.center + extent))
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.material.is_emissive()
+    }
+
+    fn is_sampleable(&self) -> bool {
+        true
+    }
+
+    fn sample_direction(&self, origin: &Vec3) -> Option<Vec3> {
+        let to_center: Vec3 = self.center - origin;
+        let distance_squared: f32 = to_center.length_squared();
+        if distance_squared <= self.radius * self.radius {
+            // from inside every point on the surface is visible so pick one uniformly
+            let point: Vec3 = self.center + uniform_sphere() * self.radius;
+            return Some(point - origin);
+        }
+        // otherwise pick uniformly within the cone of directions the sphere covers
+        let cos_theta_max: f32 = (1.0 - self.radius * self.radius / distance_squared).max(0.0).sqrt();
+        let local: Vec3 = uniform_cone(cos_theta_max);
+        let w: Vec3 = to_center.get_normalized();
+        let (u, v) = w.orthonormal_basis();
+        Some(u * local.x + v * local.y + w * local.z)
+    }
+
+    fn direction_pdf(&self, origin: &Vec3, direction: &Vec3) -> f32 {
+        let distance_squared: f32 = (self.center - origin).length_squared();
+        if distance_squared <= self.radius * self.radius {
+            return surface_direction_pdf(self, 4.0 * PI * self.radius * self.radius, origin, direction);
+        }
+        if self.hit(&Ray::new(*origin, *direction), 0.001, f32::INFINITY).is_none() {
+            return 0.0;
+        }
+        let cos_theta_max: f32 = (1.0 - self.radius * self.radius / distance_squared).max(0.0).sqrt();
+        uniform_cone_pdf(cos_theta_max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::tests::{assert_uv_follows_tangents, material};
+
+    #[test]
+    fn texture_coordinates_wrap_around_the_poles() {
+        let sphere: Sphere = Sphere::new(Vec3::new(1.0, 2.0, 3.0), 2.0, material());
+        let toward = |direction: Vec3| -> Ray {
+            Ray::new(Vec3::new(1.0, 2.0, 3.0) + direction * 10.0, -direction)
+        };
+        // longitude runs from -x round through +y and latitude from the bottom pole to the top
+        let (u, v) = assert_uv_follows_tangents(&sphere, &toward(Vec3::new(0.0, 1.0, 0.0)));
+        assert!((u - 0.75).abs() < 1e-4 && (v - 0.5).abs() < 1e-4);
+        let (u, v) = assert_uv_follows_tangents(&sphere, &toward(Vec3::new(0.6, -0.48, 0.64)));
+        assert!((0.0..=1.0).contains(&u) && v > 0.5);
+        let top: HitRecord = sphere.hit(&toward(Vec3::new(0.0, 0.0, 1.0)), 0.001, f32::INFINITY).unwrap();
+        assert!((top.get_uv().1 - 1.0).abs() < 1e-4);
+    }
 }
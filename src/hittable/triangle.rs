@@ -1,5 +1,7 @@
-use std::rc::Rc;
-use crate::hittable::HitRecord;
+use std::sync::Arc;
+use rand::random;
+use crate::bvh::aabb::Aabb;
+use crate::hittable::{HitRecord, surface_direction_pdf};
 use crate::material::Material;
 use crate::ray::Ray;
 use crate::scene::Hittable;
@@ -10,21 +12,93 @@ pub struct Triangle {
     b: Vec3,
     c: Vec3,
     normal: Vec3,
-    material: Rc<dyn Material>
+    /// Per vertex normals in the order of the vertices, when the source provided them
+    normals: Option<[Vec3; 3]>,
+    /// Per vertex texture coordinates in the order of the vertices
+    uvs: Option<[(f32, f32); 3]>,
+    material: Arc<dyn Material>
 }
 
 impl Triangle {
-    pub fn new(a: Vec3, b: Vec3, c: Vec3, material: Rc<dyn Material>) -> Triangle {
-        let normal: Vec3 = (b - a).cross(&(c - a)).get_normalized();
+    pub fn new(a: Vec3, b: Vec3, c: Vec3, material: Arc<dyn Material>) -> Triangle {
+        let normal: Vec3 = Triangle::face_normal(a, b, c);
         Triangle {
             a,
             b,
             c,
             normal,
+            normals: None,
+            uvs: None,
             material
         }
     }
 
+    pub fn with_normals(mut self, normals: [Vec3; 3]) -> Triangle {
+        self.normals = Some(normals);
+        self
+    }
+
+    pub fn set_normals(&mut self, normals: [Vec3; 3]) {
+        self.normals = Some(normals);
+    }
+
+    pub fn with_uvs(mut self, uvs: [(f32, f32); 3]) -> Triangle {
+        self.uvs = Some(uvs);
+        self
+    }
+
+    pub fn get_vertices(&self) -> [Vec3; 3] {
+        [self.a, self.b, self.c]
+    }
+
+    pub fn get_normals(&self) -> Option<&[Vec3; 3]> {
+        self.normals.as_ref()
+    }
+
+    pub fn get_uvs(&self) -> Option<&[(f32, f32); 3]> {
+        self.uvs.as_ref()
+    }
+
+    fn face_normal(a: Vec3, b: Vec3, c: Vec3) -> Vec3 {
+        let normal: Vec3 = (b - a).cross(&(c - a));
+        normal / normal.get_length()
+    }
+
+    /// The normal of the flat face, wound counter clockwise
+    pub fn get_face_normal(&self) -> &Vec3 {
+        &self.normal
+    }
+
+    pub fn get_area(&self) -> f32 {
+        (self.b - self.a).cross(&(self.c - self.a)).get_length() * 0.5
+    }
+
+    /// Picks a point uniformly over the surface of the triangle
+    pub fn sample_point(&self) -> Vec3 {
+        let s: f32 = random::<f32>().sqrt();
+        let t: f32 = random::<f32>();
+        self.a * (1.0 - s) + self.b * (s * (1.0 - t)) + self.c * (s * t)
+    }
+
+    /// The derivatives of the position along the texture coordinates, from the edges and the
+    /// change in texture coordinates along them
+    fn tangents(&self) -> (Vec3, Vec3) {
+        let ab: Vec3 = self.b - self.a;
+        let ac: Vec3 = self.c - self.a;
+        if let Some([a, b, c]) = &self.uvs {
+            let (du_1, dv_1) = (b.0 - a.0, b.1 - a.1);
+            let (du_2, dv_2) = (c.0 - a.0, c.1 - a.1);
+            let determinant: f32 = du_1 * dv_2 - dv_1 * du_2;
+            if determinant.abs() > 1e-12 {
+                return (
+                    (ab * dv_2 - ac * dv_1) / determinant,
+                    (ac * du_1 - ab * du_2) / determinant
+                );
+            }
+        }
+        (ab, ac)
+    }
+
     pub fn translate(&mut self, translation: Vec3) {
         self.a += translation;
         self.b += translation;
@@ -35,34 +109,171 @@ impl Triangle {
         self.a.rotate(rotation);
         self.b.rotate(rotation);
         self.c.rotate(rotation);
+        self.normal = Triangle::face_normal(self.a, self.b, self.c);
+        if let Some(normals) = &mut self.normals {
+            // normals follow the cofactors of the map rather than the map itself, which keeps
+            // them perpendicular to the surface even where the map isn't a rigid rotation
+            let [x, y, z] = [Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.0, 0.0, 1.0)]
+                .map(|mut axis| {
+                    axis.rotate(rotation);
+                    axis
+                });
+            normals.iter_mut().for_each(|normal| {
+                let turned: Vec3 = y.cross(&z) * normal.x + z.cross(&x) * normal.y + x.cross(&y) * normal.z;
+                let length: f32 = turned.get_length();
+                if length > 0.0 {
+                    *normal = turned / length;
+                }
+            });
+        }
     }
 
     pub fn scale(&mut self, scale: Vec3) {
         self.a.scale(scale);
         self.b.scale(scale);
         self.c.scale(scale);
+        self.normal = Triangle::face_normal(self.a, self.b, self.c);
+        if let Some(normals) = &mut self.normals {
+            // normals take the inverse scale to stay perpendicular to the stretched surface
+            let inverse: Vec3 = Vec3::new(1.0 / scale.x, 1.0 / scale.y, 1.0 / scale.z);
+            normals.iter_mut().for_each(|normal| {
+                normal.scale(inverse);
+                *normal = *normal / normal.get_length();
+            });
+        }
     }
 }
 
 impl Hittable for Triangle {
-    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
-        let t: f32 = (self.a - ray.get_origin()).dot(&self.normal) / ray.get_direction().dot(&self.normal);
-        if t < t_min || t > t_max {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        // Möller-Trumbore, solving for the distance and the barycentric coordinates together
+        let ab: Vec3 = self.b - self.a;
+        let ac: Vec3 = self.c - self.a;
+        let p: Vec3 = ray.get_direction().cross(&ac);
+        let determinant: f32 = ab.dot(&p);
+        if determinant.abs() < 1e-12 {
             return None;
         }
-        let point: Vec3 = ray.at(t);
-        let ab: Vec3 = self.b - self.a;
-        let bc: Vec3 = self.c - self.b;
-        let ca: Vec3 = self.a - self.c;
-        let ap: Vec3 = point - self.a;
-        let bp: Vec3 = point - self.b;
-        let cp: Vec3 = point - self.c;
-        let ab_normal: Vec3 = ab.cross(&self.normal);
-        let bc_normal: Vec3 = bc.cross(&self.normal);
-        let ca_normal: Vec3 = ca.cross(&self.normal);
-        if ab_normal.dot(&ap) < 0.0 || bc_normal.dot(&bp) < 0.0 || ca_normal.dot(&cp) < 0.0 {
+        let inverse_determinant: f32 = 1.0 / determinant;
+        let ap: Vec3 = *ray.get_origin() - self.a;
+        let u: f32 = ap.dot(&p) * inverse_determinant;
+        if !(0.0..=1.0).contains(&u) {
             return None;
         }
-        Some(HitRecord::new(point, self.normal, t, &self.material))
+        let q: Vec3 = ap.cross(&ab);
+        let v: f32 = ray.get_direction().dot(&q) * inverse_determinant;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+        let t: f32 = ac.dot(&q) * inverse_determinant;
+        if t < t_min || t > t_max {
+            return None;
+        }
+        // without texture coordinates the barycentric coordinates stand in for them
+        let (uv_u, uv_v) = match &self.uvs {
+            Some([a, b, c]) => (
+                a.0 * (1.0 - u - v) + b.0 * u + c.0 * v,
+                a.1 * (1.0 - u - v) + b.1 * u + c.1 * v
+            ),
+            None => (u, v)
+        };
+        let (u_tangent, v_tangent) = self.tangents();
+        let hit: HitRecord = HitRecord::new(ray.at(t), self.normal, t, &self.material)
+            .with_uv(uv_u, uv_v)
+            .with_tangents(u_tangent, v_tangent);
+        match &self.normals {
+            Some([a, b, c]) => {
+                let normal: Vec3 = *a * (1.0 - u - v) + *b * u + *c * v;
+                let length: f32 = normal.get_length();
+                if length > 0.0 {
+                    Some(hit.with_shading_normal(normal / length))
+                } else {
+                    Some(hit)
+                }
+            },
+            None => Some(hit)
+        }
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(Aabb::from_points(&[self.a, self.b, self.c]).pad(0.0001))
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.material.is_emissive()
+    }
+
+    fn is_sampleable(&self) -> bool {
+        true
+    }
+
+    fn sample_direction(&self, origin: &Vec3) -> Option<Vec3> {
+        Some(self.sample_point() - origin)
+    }
+
+    fn direction_pdf(&self, origin: &Vec3, direction: &Vec3) -> f32 {
+        surface_direction_pdf(self, self.get_area(), origin, direction)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::tests::{assert_uv_follows_tangents, material};
+
+    fn unit(vector: Vec3) -> Vec3 {
+        vector / vector.get_length()
+    }
+
+    fn assert_close(a: &Vec3, b: &Vec3) {
+        assert!((*a - *b).get_length() < 1e-4, "{:?} is not {:?}", a, b);
+    }
+
+    #[test]
+    fn vertex_normals_are_blended_across_the_face() {
+        let normals: [Vec3; 3] = [Vec3::new(0.0, 0.0, 1.0), unit(Vec3::new(1.0, 0.0, 1.0)), unit(Vec3::new(0.0, 1.0, 1.0))];
+        let triangle: Triangle = Triangle::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), material())
+            .with_normals(normals);
+        let down = |x: f32, y: f32| -> Ray {
+            Ray::new(Vec3::new(x, y, 1.0), Vec3::new(0.0, 0.0, -1.0))
+        };
+        let center: HitRecord = triangle.hit(&down(1.0 / 3.0, 1.0 / 3.0), 0.001, f32::INFINITY).unwrap();
+        assert_close(center.get_normal(), &unit(normals[0] + normals[1] + normals[2]));
+        // the shading normal bends but the surface itself stays flat
+        assert_close(center.get_geometric_normal(), &Vec3::new(0.0, 0.0, 1.0));
+        let corner: HitRecord = triangle.hit(&down(0.999, 0.0005), 0.001, f32::INFINITY).unwrap();
+        assert!(corner.get_normal().dot(&normals[1]) > 0.9999);
+        let flat: Triangle = Triangle::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), material());
+        let hit: HitRecord = flat.hit(&down(0.2, 0.3), 0.001, f32::INFINITY).unwrap();
+        assert_close(hit.get_normal(), hit.get_geometric_normal());
+    }
+
+    #[test]
+    fn transformed_normals_stay_perpendicular_to_the_surface() {
+        let mut triangle: Triangle = Triangle::new(Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.0, 0.0, 1.0), material());
+        let face_normal: Vec3 = *triangle.get_face_normal();
+        assert_close(&face_normal, &unit(Vec3::new(1.0, 1.0, 1.0)));
+        triangle.set_normals([face_normal; 3]);
+        triangle.scale(Vec3::new(2.0, 1.0, 0.5));
+        for normal in triangle.get_normals().unwrap() {
+            assert_close(normal, triangle.get_face_normal());
+        }
+        triangle.rotate(Vec3::new(30.0, -45.0, 60.0));
+        for normal in triangle.get_normals().unwrap() {
+            assert_close(normal, triangle.get_face_normal());
+        }
+    }
+
+    #[test]
+    fn texture_coordinates_are_blended_from_the_vertices() {
+        let down: Ray = Ray::new(Vec3::new(0.25, 0.5, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        let plain: Triangle = Triangle::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), material());
+        let (u, v) = assert_uv_follows_tangents(&plain, &down);
+        assert!((u - 0.25).abs() < 1e-5 && (v - 0.5).abs() < 1e-5);
+        // texture coordinates turned and stretched relative to the vertices
+        let mapped: Triangle = Triangle::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), material())
+            .with_uvs([(0.5, 0.5), (0.5, 0.7), (0.1, 0.5)]);
+        let (u, v) = assert_uv_follows_tangents(&mapped, &down);
+        assert!((u - 0.3).abs() < 1e-5 && (v - 0.55).abs() < 1e-5);
     }
 }
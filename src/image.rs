@@ -1,12 +1,14 @@
 use std::error::Error;
 use std::fs::File;
 use std::io::BufWriter;
+use std::sync::atomic::{AtomicU32, Ordering};
 
 pub struct Image {
     width: u32,
     height: u32,
     pixels: Vec<[u8; 4]>,
-    rays: u32
+    /// Atomic so that render threads can count the rays they cast through a shared reference
+    rays: AtomicU32
 }
 
 impl Image {
@@ -15,7 +17,7 @@ impl Image {
             width,
             height,
             pixels: vec![[0, 0, 0, 255]; (width * height) as usize],
-            rays: 0
+            rays: AtomicU32::new(0)
         }
     }
 
@@ -51,20 +53,24 @@ impl Image {
         self.pixels[(y * self.width + x) as usize] = color;
     }
 
-    pub fn increment_ray_count(&mut self) {
-        self.rays += 1;
+    pub fn get_pixel(&self, x: u32, y: u32) -> [u8; 4] {
+        self.pixels[(y * self.width + x) as usize]
     }
 
-    pub fn set_ray_count(&mut self, rays: u32) {
-        self.rays = rays;
+    pub fn increment_ray_count(&self) {
+        self.rays.fetch_add(1, Ordering::Relaxed);
     }
 
-    pub fn jump_ray_count(&mut self, rays: u32) {
-        self.rays += rays;
+    pub fn set_ray_count(&self, rays: u32) {
+        self.rays.store(rays, Ordering::Relaxed);
+    }
+
+    pub fn jump_ray_count(&self, rays: u32) {
+        self.rays.fetch_add(rays, Ordering::Relaxed);
     }
 
     pub fn get_ray_count(&self) -> u32 {
-        self.rays
+        self.rays.load(Ordering::Relaxed)
     }
 
     pub fn set_pixels(&mut self, pixels: Vec<[u8; 4]>) {
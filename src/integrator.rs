@@ -0,0 +1,14 @@
+use crate::material::Color;
+use crate::ray::Ray;
+use crate::scene::Scene;
+
+pub mod path;
+pub mod normal;
+
+/// Offset applied to the start of secondary rays so they don't hit the surface they leave
+pub const RAY_EPSILON: f32 = 0.001;
+
+/// Estimates the light arriving at the camera along a ray, one sample at a time
+pub trait Integrator: Send + Sync {
+    fn radiance(&self, scene: &Scene, ray: &Ray) -> Color;
+}
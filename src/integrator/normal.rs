@@ -0,0 +1,17 @@
+use crate::hittable::Hittable;
+use crate::integrator::{Integrator, RAY_EPSILON};
+use crate::material::Color;
+use crate::ray::Ray;
+use crate::scene::Scene;
+
+/// Shows the surface normal at the first hit mapped into the unit cube, useful for debugging geometry
+pub struct NormalIntegrator;
+
+impl Integrator for NormalIntegrator {
+    fn radiance(&self, scene: &Scene, ray: &Ray) -> Color {
+        match scene.hit(ray, RAY_EPSILON, f32::INFINITY) {
+            Some(hit) => (*hit.get_normal() + Color::new(1.0, 1.0, 1.0)) * 0.5,
+            None => scene.background(ray, None)
+        }
+    }
+}
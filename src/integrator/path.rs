@@ -0,0 +1,337 @@
+use std::sync::Arc;
+use rand::random;
+use crate::hittable::{HitRecord, Hittable};
+use crate::integrator::{Integrator, RAY_EPSILON};
+use crate::light::{Light, LightSample};
+use crate::material::medium::Medium;
+use crate::material::{Color, ScatterRecord};
+use crate::ray::Ray;
+use crate::sampling::uniform_sphere;
+use crate::scene::Scene;
+use crate::spectrum::{sample_wavelengths, samples_to_rgb, upsample};
+use crate::vec::Vec3;
+
+/// Scattering events after which a random walk through a medium is given up as absorbed
+const MAX_WALK_STEPS: u32 = 1024;
+
+/// A unidirectional path tracer that follows a single scattered ray per bounce
+pub struct PathTracer {
+    max_depth: u32,
+    /// Bounces after which paths are randomly terminated based on their throughput
+    roulette_depth: u32,
+    /// Whether lights are sampled directly at every surface that isn't purely specular and
+    /// combined with scattered rays through multiple importance sampling
+    light_sampling: bool,
+    /// Whether paths carry wavelengths rather than RGB, so dispersion can split colors
+    spectral: bool
+}
+
+impl PathTracer {
+    pub fn new(max_depth: u32, roulette_depth: u32, light_sampling: bool, spectral: bool) -> PathTracer {
+        PathTracer {
+            max_depth,
+            roulette_depth,
+            light_sampling,
+            spectral
+        }
+    }
+
+    /// Picks one light uniformly and estimates the light it sends back along the ray
+    fn sample_light(&self, scene: &Scene, ray: &Ray, hit: &HitRecord, wavelengths: Option<Vec3>) -> Color {
+        let black: Color = Color::new(0.0, 0.0, 0.0);
+        let lights: &[Arc<dyn Light>] = scene.get_lights();
+        let index: usize = ((random::<f32>() * lights.len() as f32) as usize).min(lights.len() - 1);
+        let light: &Arc<dyn Light> = &lights[index];
+        let sample: LightSample = match light.sample(hit.get_point(), wavelengths.as_ref()) {
+            Some(sample) => sample,
+            None => return black
+        };
+        if sample.get_pdf() <= 0.0 || sample.get_radiance().max_component() <= 0.0 {
+            return black;
+        }
+        let bsdf: Color = at_wavelengths(hit.get_material().eval(ray, hit, sample.get_direction()), wavelengths);
+        if bsdf.max_component() <= 0.0 {
+            return black;
+        }
+        let shadow: Ray = Ray::new(*hit.get_point(), *sample.get_direction());
+        if scene.hit(&shadow, RAY_EPSILON, sample.get_distance() - RAY_EPSILON).is_some() {
+            return black;
+        }
+        let radiance: Color = sample.get_radiance();
+        if light.is_delta() {
+            return bsdf * radiance * lights.len() as f32;
+        }
+        // weigh by the density of the whole light picking strategy rather than just this light
+        let light_pdf: f32 = scene.light_pdf(hit.get_point(), sample.get_direction());
+        if light_pdf <= 0.0 {
+            return black;
+        }
+        let bsdf_pdf: f32 = hit.get_material().pdf(ray, hit, sample.get_direction());
+        bsdf * radiance * (power_heuristic(light_pdf, bsdf_pdf) / light_pdf)
+    }
+
+    /// Follows the ray through a scattering medium, bouncing it off particles in uniformly
+    /// random directions until it reaches a surface, and weighs the throughput by what was
+    /// absorbed on the way. Distances are picked with the density of one channel at random and
+    /// weighed by the average density of all three
+    fn random_walk<'a>(&self, scene: &'a Scene, ray: &mut Ray, medium: &Medium, throughput: &mut Color, previous: &mut Option<(Vec3, f32)>) -> Option<HitRecord<'a>> {
+        let extinction: Color = medium.get_extinction();
+        let extinctions: [f32; 3] = [extinction.x, extinction.y, extinction.z];
+        for _ in 0..MAX_WALK_STEPS {
+            let channel: f32 = extinctions[((random::<f32>() * 3.0) as usize).min(2)];
+            let distance: f32 = if channel > 0.0 {
+                -(1.0 - random::<f32>()).ln() / channel
+            } else {
+                f32::INFINITY
+            };
+            let hit: Option<HitRecord> = scene.hit(ray, RAY_EPSILON, f32::INFINITY);
+            let length: f32 = ray.get_direction().get_length();
+            let surface_distance: f32 = hit.as_ref().map_or(f32::INFINITY, |hit| hit.get_t() * length);
+            if distance >= surface_distance {
+                let transmittance: Color = medium.transmittance(surface_distance);
+                let pdf: f32 = (transmittance.x + transmittance.y + transmittance.z) / 3.0;
+                if pdf <= 0.0 {
+                    break;
+                }
+                *throughput = *throughput * transmittance / pdf;
+                return hit;
+            }
+            let transmittance: Color = medium.transmittance(distance);
+            let density: Color = extinction * transmittance;
+            let pdf: f32 = (density.x + density.y + density.z) / 3.0;
+            if pdf <= 0.0 {
+                break;
+            }
+            *throughput = *throughput * medium.get_scattering() * transmittance / pdf;
+            *ray = continued(Ray::new(ray.at(distance / length), uniform_sphere()), ray);
+            // lights aren't sampled inside media so whatever the walk finds counts in full
+            *previous = None;
+        }
+        *throughput = Color::new(0.0, 0.0, 0.0);
+        None
+    }
+
+    /// The light arriving along the ray, at the three `wavelengths` in nm of a spectral render
+    /// or in RGB without them
+    fn trace(&self, scene: &Scene, ray: &Ray, wavelengths: Option<Vec3>) -> Color {
+        let light_sampling: bool = self.light_sampling && !scene.get_lights().is_empty();
+        let mut radiance: Color = Color::new(0.0, 0.0, 0.0);
+        let mut throughput: Color = Color::new(1.0, 1.0, 1.0);
+        let mut ray: Ray = *ray;
+        // the previous bounce, its point and the density its material picked the current ray with
+        let mut previous: Option<(Vec3, f32)> = None;
+        // the media the ray is inside, innermost last
+        let mut media: Vec<Medium> = Vec::new();
+        for depth in 0..self.max_depth {
+            let medium: Option<Medium> = media.last().copied();
+            let hit: Option<HitRecord> = match medium {
+                Some(medium) if medium.is_scattering() => self.random_walk(scene, &mut ray, &medium, &mut throughput, &mut previous),
+                _ => scene.hit(&ray, RAY_EPSILON, f32::INFINITY)
+            };
+            let hit: HitRecord = match hit {
+                Some(hit) => hit,
+                None => {
+                    let background: Color = scene.background(&ray, wavelengths.as_ref());
+                    if background.max_component() > 0.0 {
+                        radiance += throughput * background * emission_weight(scene, &ray, previous, light_sampling);
+                    }
+                    break;
+                }
+            };
+            if let Some(medium) = medium.filter(|medium| !medium.is_scattering()) {
+                throughput = throughput * medium.transmittance(hit.get_t() * ray.get_direction().get_length());
+            }
+            let emitted: Color = hit.get_material().emitted(&ray, &hit, wavelengths.as_ref());
+            if emitted.max_component() > 0.0 {
+                radiance += throughput * emitted * emission_weight(scene, &ray, previous, light_sampling);
+            }
+            // a material that picks between specular and other scatters at random covers the
+            // others with light sampling every time, as `eval` leaves out the specular ones
+            if light_sampling && hit.get_material().has_non_specular() {
+                radiance += throughput * self.sample_light(scene, &ray, &hit, wavelengths);
+            }
+            let scatter: ScatterRecord = match hit.get_material().scatter(&ray, &hit) {
+                Some(scatter) => scatter,
+                None => break
+            };
+            if scatter.is_dispersed() && wavelengths.is_some() {
+                // the direction only suits the hero wavelength, which now stands in for all three
+                throughput = Color::new(throughput.x * 3.0, 0.0, 0.0);
+            }
+            previous = if scatter.is_specular() {
+                None
+            } else {
+                Some((*hit.get_point(), hit.get_material().pdf(&ray, &hit, scatter.get_ray().get_direction())))
+            };
+            if let Some(medium) = hit.get_material().medium() {
+                // a scattered ray on the far side of the surface has entered or left the medium
+                let normal: &Vec3 = hit.get_geometric_normal();
+                if normal.dot(ray.get_direction()) * normal.dot(scatter.get_ray().get_direction()) > 0.0 {
+                    if hit.is_front_face(&ray) {
+                        media.push(match wavelengths {
+                            Some(wavelengths) => Medium::new(
+                                upsample(&medium.get_absorption(), &wavelengths),
+                                upsample(&medium.get_scattering(), &wavelengths)
+                            ),
+                            None => medium
+                        });
+                    } else {
+                        media.pop();
+                    }
+                }
+            }
+            throughput = throughput * at_wavelengths(scatter.get_attenuation(), wavelengths);
+            if depth >= self.roulette_depth {
+                // survivors are weighted up so the estimate stays unbiased
+                let survival: f32 = throughput.max_component().min(0.95);
+                if random::<f32>() >= survival {
+                    break;
+                }
+                throughput = throughput / survival;
+            }
+            ray = continued(*scatter.get_ray(), &ray);
+        }
+        radiance
+    }
+}
+
+/// A color as its values at the wavelengths of a spectral render, or as it is without them
+fn at_wavelengths(color: Color, wavelengths: Option<Vec3>) -> Color {
+    match wavelengths {
+        Some(wavelengths) => upsample(&color, &wavelengths),
+        None => color
+    }
+}
+
+/// `ray` carrying on with the wavelength of the ray before it
+fn continued(ray: Ray, previous: &Ray) -> Ray {
+    match previous.get_wavelength() {
+        Some(wavelength) => ray.with_wavelength(wavelength),
+        None => ray
+    }
+}
+
+/// Veach's power heuristic with an exponent of two for one sample from each strategy
+fn power_heuristic(pdf: f32, other_pdf: f32) -> f32 {
+    let a: f32 = pdf * pdf;
+    let b: f32 = other_pdf * other_pdf;
+    if a + b <= 0.0 {
+        return 0.0;
+    }
+    a / (a + b)
+}
+
+/// The MIS weight for light found by a scattered ray, given the point and density of the
+/// bounce it was scattered from, which is `None` for camera rays and specular bounces
+fn emission_weight(scene: &Scene, ray: &Ray, previous: Option<(Vec3, f32)>, light_sampling: bool) -> f32 {
+    match previous {
+        Some((point, bsdf_pdf)) if light_sampling => {
+            power_heuristic(bsdf_pdf, scene.light_pdf(&point, ray.get_direction()))
+        },
+        _ => 1.0
+    }
+}
+
+impl Integrator for PathTracer {
+    fn radiance(&self, scene: &Scene, ray: &Ray) -> Color {
+        if !self.spectral {
+            return self.trace(scene, ray, None);
+        }
+        let wavelengths: Vec3 = sample_wavelengths();
+        let radiance: Vec3 = self.trace(scene, &ray.with_wavelength(wavelengths.x), Some(wavelengths));
+        samples_to_rgb(&wavelengths, &radiance)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::background::gradient::Gradient;
+    use crate::hittable::plane::Plane;
+    use crate::hittable::sphere::Sphere;
+    use crate::material::diffuse::Diffuse;
+    use crate::material::emissive::Emissive;
+    use crate::material::layered::Layered;
+    use crate::material::mix::Mix;
+    use crate::material::reflective::Reflective;
+    use crate::material::subsurface::Subsurface;
+    use crate::material::Material;
+    use crate::material::tests::gray;
+    use crate::texture::constant::Constant;
+
+    /// The average brightness seen looking down at a floor of `material` with a lamp above it,
+    /// out of view of the mirror reflection so only light sampling and rough scatters reach it
+    fn floor_brightness(material: Arc<dyn Material>, light_sampling: bool) -> f32 {
+        let mut scene: Scene = Scene::new();
+        scene.set_background(Arc::new(Gradient::new(Color::new(0.0, 0.0, 0.0), Color::new(0.0, 0.0, 0.0))));
+        scene.add_object(Box::new(Plane::new(Vec3::new(0.0, 0.0, 1.0), Vec3::new(0.0, 0.0, 0.0), material)));
+        scene.add_object(Box::new(Sphere::new(Vec3::new(0.0, 0.0, 2.0), 1.0, Arc::new(Emissive::new(gray(1.0), 1.0)))));
+        let tracer: PathTracer = PathTracer::new(2, 2, light_sampling, false);
+        let ray: Ray = Ray::new(Vec3::new(-2.0, 0.0, 2.0), Vec3::new(1.0, 0.0, -1.0));
+        let samples: u32 = 100_000;
+        let total: Color = (0..samples).fold(Color::new(0.0, 0.0, 0.0), |total, _| total + tracer.radiance(&scene, &ray));
+        (total.x + total.y + total.z) / (3.0 * samples as f32)
+    }
+
+    fn assert_unbiased(material: Arc<dyn Material>) {
+        let sampled: f32 = floor_brightness(material.clone(), true);
+        let scattered: f32 = floor_brightness(material, false);
+        assert!((sampled - scattered).abs() < 0.05 * scattered, "{} with light sampling, {} without", sampled, scattered);
+    }
+
+    #[test]
+    fn light_sampling_agrees_with_scattering_alone() {
+        let diffuse: Arc<dyn Material> = Arc::new(Diffuse::new(gray(0.8), 1.0));
+        let mirror: Arc<dyn Material> = Arc::new(Reflective::new(gray(0.8), 0.0));
+        assert_unbiased(diffuse.clone());
+        assert_unbiased(Arc::new(Mix::new(diffuse.clone(), mirror, gray(0.5))));
+        assert_unbiased(Arc::new(Layered::new(diffuse, gray(1.0), 1.5, 0.0)));
+    }
+
+    /// The average brightness of a unit sphere of `material` lit evenly from every side
+    fn furnace_brightness(material: Arc<dyn Material>, tracer: &PathTracer) -> f32 {
+        let mut scene: Scene = Scene::new();
+        scene.set_background(Arc::new(Gradient::new(Color::new(1.0, 1.0, 1.0), Color::new(1.0, 1.0, 1.0))));
+        scene.add_object(Box::new(Sphere::new(Vec3::new(0.0, 0.0, 0.0), 1.0, material)));
+        let ray: Ray = Ray::new(Vec3::new(0.3, -3.0, 0.2), Vec3::new(0.0, 1.0, 0.0));
+        let samples: u32 = 20_000;
+        let total: Color = (0..samples).fold(Color::new(0.0, 0.0, 0.0), |total, _| total + tracer.radiance(&scene, &ray));
+        (total.x + total.y + total.z) / (3.0 * samples as f32)
+    }
+
+    #[test]
+    fn random_walks_keep_the_light_a_white_medium_scatters() {
+        let tracer: PathTracer = PathTracer::new(256, 256, false, false);
+        // with nothing absorbed all the light that goes in comes back out
+        let white: f32 = furnace_brightness(Arc::new(Subsurface::new(Color::new(1.0, 1.0, 1.0), Color::new(0.2, 0.2, 0.2), 1.4, 0.0)), &tracer);
+        assert!((white - 1.0).abs() < 0.02, "a white medium returns {}", white);
+        let grey: f32 = furnace_brightness(Arc::new(Subsurface::new(Color::new(0.8, 0.8, 0.8), Color::new(0.2, 0.2, 0.2), 1.4, 0.0)), &tracer);
+        assert!(grey < 0.9 * white, "an absorbing medium returns {}", grey);
+    }
+
+    #[test]
+    fn russian_roulette_keeps_the_average() {
+        // light leaving a convex diffuse sphere never comes back to it, so it reflects its albedo
+        let grey: Arc<dyn Material> = Arc::new(Diffuse::new(gray(0.8), 1.0));
+        for tracer in [PathTracer::new(8, 8, false, false), PathTracer::new(8, 0, false, false)] {
+            let brightness: f32 = furnace_brightness(grey.clone(), &tracer);
+            assert!((brightness - 0.8).abs() < 0.02, "the sphere reflects {}", brightness);
+        }
+    }
+
+    #[test]
+    fn emitters_become_lights_and_are_seen_once() {
+        let mut scene: Scene = Scene::new();
+        scene.set_background(Arc::new(Gradient::new(Color::new(0.0, 0.0, 0.0), Color::new(0.0, 0.0, 0.0))));
+        let lamp: Arc<dyn Material> = Arc::new(Emissive::new(Arc::new(Constant::new(Color::new(2.0, 1.0, 0.5))), 3.0));
+        scene.add_object(Box::new(Sphere::new(Vec3::new(0.0, 0.0, 0.0), 1.0, lamp)));
+        scene.add_object(Box::new(Plane::new(Vec3::new(0.0, 0.0, 1.0), Vec3::new(0.0, 0.0, -1.0), Arc::new(Diffuse::new(gray(0.5), 1.0)))));
+        assert_eq!(scene.get_lights().len(), 1);
+        // looking straight at the lamp shows its light once, whether or not lights are sampled
+        let ray: Ray = Ray::new(Vec3::new(-5.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        for light_sampling in [false, true] {
+            let seen: Color = PathTracer::new(4, 4, light_sampling, false).radiance(&scene, &ray);
+            assert!((seen - Color::new(6.0, 3.0, 1.5)).get_length() < 1e-4, "the lamp looks like {:?}", seen);
+        }
+    }
+}
@@ -0,0 +1,61 @@
+use crate::material::Color;
+use crate::vec::Vec3;
+
+pub mod area;
+pub mod point;
+pub mod spot;
+pub mod directional;
+
+/// A direction towards a light picked by `Light::sample` along with what arrives from it
+#[derive(Debug, Clone, Copy)]
+pub struct LightSample {
+    direction: Vec3,
+    distance: f32,
+    radiance: Color,
+    pdf: f32
+}
+
+impl LightSample {
+    pub fn new(direction: Vec3, distance: f32, radiance: Color, pdf: f32) -> LightSample {
+        LightSample {
+            direction,
+            distance,
+            radiance,
+            pdf
+        }
+    }
+
+    pub fn get_direction(&self) -> &Vec3 {
+        &self.direction
+    }
+
+    /// How far along `direction` the light is, shadow rays stop short of this
+    pub fn get_distance(&self) -> f32 {
+        self.distance
+    }
+
+    pub fn get_radiance(&self) -> Color {
+        self.radiance
+    }
+
+    /// The solid angle density of the sample, or 1 for delta lights whose radiance already
+    /// holds the whole contribution, such as the irradiance of a point light at that distance
+    pub fn get_pdf(&self) -> f32 {
+        self.pdf
+    }
+}
+
+/// Anything that can be sampled directly when estimating the light arriving at a point
+pub trait Light: Send + Sync {
+    /// The radiance of the sample is in RGB, or at the `wavelengths` of a spectral render
+    fn sample(&self, point: &Vec3, wavelengths: Option<&Vec3>) -> Option<LightSample>;
+
+    /// The solid angle density with which `sample` picks `direction` from `point`
+    fn pdf(&self, point: &Vec3, direction: &Vec3) -> f32;
+
+    /// Whether the light can't be reached by scattered rays, such as a point light, in which
+    /// case it is only ever found through `sample`
+    fn is_delta(&self) -> bool {
+        false
+    }
+}
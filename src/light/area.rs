@@ -0,0 +1,95 @@
+use std::sync::Arc;
+use crate::hittable::{HitRecord, Hittable};
+use crate::light::{Light, LightSample};
+use crate::material::Color;
+use crate::ray::Ray;
+use crate::vec::Vec3;
+
+/// A light made from an object with an emissive material
+pub struct AreaLight {
+    object: Arc<dyn Hittable>
+}
+
+impl AreaLight {
+    pub fn new(object: Arc<dyn Hittable>) -> AreaLight {
+        AreaLight {
+            object
+        }
+    }
+}
+
+impl Light for AreaLight {
+    fn sample(&self, point: &Vec3, wavelengths: Option<&Vec3>) -> Option<LightSample> {
+        let ray: Ray = Ray::new(*point, self.object.sample_direction(point)?);
+        let pdf: f32 = self.object.direction_pdf(point, ray.get_direction());
+        if pdf <= 0.0 {
+            return None;
+        }
+        let hit: HitRecord = self.object.hit(&ray, 0.001, f32::INFINITY)?;
+        let radiance: Color = hit.get_material().emitted(&ray, &hit, wavelengths);
+        Some(LightSample::new(*ray.get_direction(), hit.get_t(), radiance, pdf))
+    }
+
+    fn pdf(&self, point: &Vec3, direction: &Vec3) -> f32 {
+        self.object.direction_pdf(point, direction)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::cube::Cube;
+    use crate::hittable::rectangle::Rectangle;
+    use crate::hittable::sphere::Sphere;
+    use crate::hittable::triangle::Triangle;
+    use crate::material::tests::lamp;
+    use crate::sampling::tests::mean_and_error;
+    use crate::sampling::{uniform_sphere, uniform_sphere_pdf};
+
+    /// Checks that weighing sampled directions by their density covers the solid angle the
+    /// object takes up as seen from `point`, which it only does when the density is the one
+    /// the directions were really picked with
+    fn assert_samples_match_pdf(object: Arc<dyn Hittable>, point: Vec3) {
+        let light: AreaLight = AreaLight::new(object.clone());
+        let (covered, covered_error) = mean_and_error((0..40_000)
+            .map(|_| {
+                let ray: Ray = Ray::new(point, uniform_sphere());
+                if object.hit(&ray, 0.001, f32::INFINITY).is_some() { 1.0 / uniform_sphere_pdf() } else { 0.0 }
+            })
+            .collect());
+        let (sampled, sampled_error) = mean_and_error((0..40_000)
+            .map(|_| match light.sample(&point, None) {
+                Some(sample) => {
+                    assert!((sample.get_pdf() - light.pdf(&point, sample.get_direction())).abs() <= 1e-3 * sample.get_pdf());
+                    1.0 / sample.get_pdf()
+                },
+                None => 0.0
+            })
+            .collect());
+        let error: f32 = (covered_error * covered_error + sampled_error * sampled_error).sqrt();
+        assert!((sampled - covered).abs() < 0.01 * covered + 4.0 * error, "samples cover {} but the object covers {} +- {}", sampled, covered, error);
+    }
+
+    #[test]
+    fn flat_lights_are_sampled_by_area() {
+        let point: Vec3 = Vec3::new(0.3, 0.2, 1.5);
+        assert_samples_match_pdf(Arc::new(Rectangle::new(Vec3::new(-1.0, -1.0, 0.0), Vec3::new(2.0, 0.0, 0.0), Vec3::new(0.0, 2.0, 0.0), lamp())), point);
+        assert_samples_match_pdf(Arc::new(Triangle::new(Vec3::new(-1.0, -1.0, 0.0), Vec3::new(2.0, -1.0, 0.0), Vec3::new(0.0, 2.0, 0.0), lamp())), point);
+    }
+
+    #[test]
+    fn solid_lights_count_their_far_side() {
+        let cube: Arc<dyn Hittable> = Arc::new(Cube::new(
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(0.0, 2.0, 0.0),
+            Vec3::new(0.0, 0.0, 1.0),
+            lamp()
+        ));
+        assert_samples_match_pdf(cube.clone(), Vec3::new(1.5, 1.0, 2.0));
+        assert_samples_match_pdf(cube, Vec3::new(0.1, 0.2, 0.0));
+        let sphere: Arc<dyn Hittable> = Arc::new(Sphere::new(Vec3::new(0.0, 0.0, 0.0), 1.0, lamp()));
+        assert_samples_match_pdf(sphere.clone(), Vec3::new(0.0, 0.0, 3.0));
+        assert_samples_match_pdf(sphere, Vec3::new(0.2, 0.0, 0.3));
+    }
+}
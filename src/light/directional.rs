@@ -0,0 +1,134 @@
+use std::f32::consts::PI;
+use crate::light::{Light, LightSample};
+use crate::material::Color;
+use crate::sampling::uniform_cone;
+use crate::spectrum::{Blackbody, Emission};
+use crate::vec::Vec3;
+
+/// A light infinitely far away, like the sun, that can cover a small cone of the sky to
+/// give soft shadows
+pub struct DirectionalLight {
+    /// The direction the light travels in
+    direction: Vec3,
+    irradiance: Emission,
+    cos_half_angle: f32,
+    /// Whether the disk of the light is drawn by the background, so scattered rays can find
+    /// it and it takes part in multiple importance sampling instead of being a delta light
+    visible: bool
+}
+
+impl DirectionalLight {
+    /// `angular_diameter` is in degrees, the sun is about half a degree across
+    pub fn new(direction: Vec3, color: Color, intensity: f32, angular_diameter: f32) -> DirectionalLight {
+        DirectionalLight {
+            // normalized exactly as the disk can be far narrower than the error of get_normalized
+            direction: direction / direction.get_length(),
+            irradiance: Emission::new(color * intensity),
+            cos_half_angle: (angular_diameter.to_radians() / 2.0).cos(),
+            visible: false
+        }
+    }
+
+    /// Tints the light with a blackbody as `spectrum::emission` describes
+    pub fn with_blackbody(mut self, blackbody: Blackbody) -> Self {
+        self.irradiance = self.irradiance.with_blackbody(blackbody);
+        self
+    }
+
+    /// A light whose disk is drawn by a background through `disk_radiance`, the disk needs
+    /// some size to be seen so the angular diameter is kept above a hundredth of a degree
+    pub fn visible(direction: Vec3, color: Color, intensity: f32, angular_diameter: f32) -> DirectionalLight {
+        DirectionalLight {
+            visible: true,
+            ..DirectionalLight::new(direction, color, intensity, angular_diameter.max(0.01))
+        }
+    }
+
+    /// The unit direction pointing from the scene towards the light
+    pub fn get_direction_to_light(&self) -> Vec3 {
+        -self.direction
+    }
+
+    fn solid_angle(&self) -> f32 {
+        2.0 * PI * (1.0 - self.cos_half_angle)
+    }
+
+    fn contains(&self, direction: &Vec3) -> bool {
+        (-self.direction).dot(direction) / direction.get_length() >= self.cos_half_angle
+    }
+
+    /// The radiance seen looking at the disk in the given direction, spreading the irradiance
+    /// evenly over the disk, in RGB or at the `wavelengths` of a spectral render
+    pub fn disk_radiance(&self, direction: &Vec3, wavelengths: Option<&Vec3>) -> Color {
+        if !self.visible || !self.contains(direction) {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+        self.irradiance.at(wavelengths) / self.solid_angle()
+    }
+}
+
+impl Light for DirectionalLight {
+    fn sample(&self, _point: &Vec3, wavelengths: Option<&Vec3>) -> Option<LightSample> {
+        let w: Vec3 = -self.direction;
+        let direction: Vec3 = if self.cos_half_angle < 1.0 {
+            // pick uniformly over the disk of the light so the edges of shadows are soft
+            let local: Vec3 = uniform_cone(self.cos_half_angle);
+            let (u, v) = w.orthonormal_basis();
+            u * local.x + v * local.y + w * local.z
+        } else {
+            w
+        };
+        let irradiance: Color = self.irradiance.at(wavelengths);
+        if self.visible {
+            let pdf: f32 = 1.0 / self.solid_angle();
+            return Some(LightSample::new(direction, f32::INFINITY, irradiance * pdf, pdf));
+        }
+        Some(LightSample::new(direction, f32::INFINITY, irradiance, 1.0))
+    }
+
+    fn pdf(&self, _point: &Vec3, direction: &Vec3) -> f32 {
+        if !self.visible || !self.contains(direction) {
+            return 0.0;
+        }
+        1.0 / self.solid_angle()
+    }
+
+    fn is_delta(&self) -> bool {
+        !self.visible
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_disk_shows_the_light_that_is_sampled() {
+        let sun: DirectionalLight = DirectionalLight::visible(Vec3::new(0.0, 0.0, -1.0), Color::new(1.0, 0.9, 0.8), 3.0, 0.5)
+            .with_blackbody(Blackbody::new(3000.0));
+        let point: Vec3 = Vec3::new(0.0, 0.0, 0.0);
+        for wavelengths in [None, Some(Vec3::new(450.0, 550.0, 650.0))] {
+            let sample: LightSample = sun.sample(&point, wavelengths.as_ref()).unwrap();
+            let disk: Color = sun.disk_radiance(sample.get_direction(), wavelengths.as_ref());
+            let radiance: Color = sample.get_radiance();
+            for (seen, sampled) in [(disk.x, radiance.x), (disk.y, radiance.y), (disk.z, radiance.z)] {
+                assert!((seen - sampled).abs() <= 1e-4 * sampled, "{} on the disk, {} sampled", seen, sampled);
+            }
+        }
+        assert!(sun.disk_radiance(&Vec3::new(1.0, 0.0, 0.0), None).max_component() == 0.0);
+    }
+
+    #[test]
+    fn hidden_suns_are_delta_lights_within_their_disk() {
+        let sun: DirectionalLight = DirectionalLight::new(Vec3::new(0.0, 1.0, -1.0), Color::new(1.0, 1.0, 1.0), 2.0, 5.0);
+        assert!(sun.is_delta());
+        let to_sun: Vec3 = sun.get_direction_to_light();
+        for _ in 0..100 {
+            let sample: LightSample = sun.sample(&Vec3::new(0.0, 0.0, 0.0), None).unwrap();
+            assert!(sample.get_direction().dot(&to_sun) >= 2.5_f32.to_radians().cos() - 1e-6);
+            assert!(sample.get_distance().is_infinite());
+            assert!((sample.get_radiance().x - 2.0).abs() < 1e-5);
+        }
+        assert!(sun.disk_radiance(&to_sun, None).max_component() == 0.0);
+    }
+}
@@ -0,0 +1,68 @@
+use crate::light::{Light, LightSample};
+use crate::material::Color;
+use crate::spectrum::{Blackbody, Emission};
+use crate::vec::Vec3;
+
+/// A light that shines equally in every direction from a single point
+pub struct PointLight {
+    position: Vec3,
+    intensity: Emission,
+    /// The exponent of the distance the intensity is divided by, 2 is physically correct
+    falloff: f32
+}
+
+impl PointLight {
+    pub fn new(position: Vec3, color: Color, intensity: f32, falloff: f32) -> PointLight {
+        PointLight {
+            position,
+            intensity: Emission::new(color * intensity),
+            falloff
+        }
+    }
+
+    /// Tints the light with a blackbody as `spectrum::emission` describes
+    pub fn with_blackbody(mut self, blackbody: Blackbody) -> Self {
+        self.intensity = self.intensity.with_blackbody(blackbody);
+        self
+    }
+}
+
+impl Light for PointLight {
+    fn sample(&self, point: &Vec3, wavelengths: Option<&Vec3>) -> Option<LightSample> {
+        let to_light: Vec3 = self.position - point;
+        let distance: f32 = to_light.get_length();
+        if distance <= 0.0 {
+            return None;
+        }
+        let radiance: Color = self.intensity.at(wavelengths) / distance.powf(self.falloff);
+        Some(LightSample::new(to_light / distance, distance, radiance, 1.0))
+    }
+
+    fn pdf(&self, _point: &Vec3, _direction: &Vec3) -> f32 {
+        0.0
+    }
+
+    fn is_delta(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn light_falls_off_with_distance() {
+        let light: PointLight = PointLight::new(Vec3::new(0.0, 0.0, 2.0), Color::new(1.0, 0.5, 0.25), 4.0, 2.0);
+        let near: LightSample = light.sample(&Vec3::new(0.0, 0.0, 1.0), None).unwrap();
+        let far: LightSample = light.sample(&Vec3::new(0.0, 0.0, 0.0), None).unwrap();
+        assert!((near.get_radiance().x - 4.0).abs() < 1e-5 && (near.get_radiance().z - 1.0).abs() < 1e-5);
+        assert!((far.get_radiance().x - 1.0).abs() < 1e-5);
+        assert!((far.get_distance() - 2.0).abs() < 1e-5 && (far.get_direction().z - 1.0).abs() < 1e-5);
+        let linear: PointLight = PointLight::new(Vec3::new(0.0, 0.0, 2.0), Color::new(1.0, 1.0, 1.0), 4.0, 1.0);
+        assert!((linear.sample(&Vec3::new(0.0, 0.0, 0.0), None).unwrap().get_radiance().x - 2.0).abs() < 1e-5);
+        // a point on the light has no direction to it
+        assert!(light.sample(&Vec3::new(0.0, 0.0, 2.0), None).is_none());
+        assert!(light.is_delta());
+    }
+}
@@ -0,0 +1,102 @@
+use crate::light::{Light, LightSample};
+use crate::material::Color;
+use crate::spectrum::{Blackbody, Emission};
+use crate::vec::Vec3;
+
+/// A point light limited to a cone, fading out between the inner and outer cone angles
+pub struct SpotLight {
+    position: Vec3,
+    direction: Vec3,
+    intensity: Emission,
+    falloff: f32,
+    cos_inner: f32,
+    cos_outer: f32
+}
+
+impl SpotLight {
+    /// The cone angles are measured in degrees from the direction to the edge of the cone
+    pub fn new(position: Vec3, direction: Vec3, color: Color, intensity: f32, falloff: f32, inner_angle: f32, outer_angle: f32) -> SpotLight {
+        let outer_angle: f32 = outer_angle.max(inner_angle);
+        SpotLight {
+            position,
+            direction: direction.get_normalized(),
+            intensity: Emission::new(color * intensity),
+            falloff,
+            cos_inner: inner_angle.to_radians().cos(),
+            cos_outer: outer_angle.to_radians().cos()
+        }
+    }
+
+    /// Tints the light with a blackbody as `spectrum::emission` describes
+    pub fn with_blackbody(mut self, blackbody: Blackbody) -> Self {
+        self.intensity = self.intensity.with_blackbody(blackbody);
+        self
+    }
+
+    /// How much of the intensity reaches a point off the axis of the cone by the given cosine
+    fn cone_falloff(&self, cosine: f32) -> f32 {
+        if cosine >= self.cos_inner {
+            return 1.0;
+        }
+        if cosine <= self.cos_outer {
+            return 0.0;
+        }
+        let t: f32 = (cosine - self.cos_outer) / (self.cos_inner - self.cos_outer);
+        t * t * (3.0 - 2.0 * t)
+    }
+}
+
+impl Light for SpotLight {
+    fn sample(&self, point: &Vec3, wavelengths: Option<&Vec3>) -> Option<LightSample> {
+        let to_light: Vec3 = self.position - point;
+        let distance: f32 = to_light.get_length();
+        if distance <= 0.0 {
+            return None;
+        }
+        let direction: Vec3 = to_light / distance;
+        let cone: f32 = self.cone_falloff((-direction).dot(&self.direction));
+        if cone <= 0.0 {
+            return None;
+        }
+        let radiance: Color = self.intensity.at(wavelengths) * (cone / distance.powf(self.falloff));
+        Some(LightSample::new(direction, distance, radiance, 1.0))
+    }
+
+    fn pdf(&self, _point: &Vec3, _direction: &Vec3) -> f32 {
+        0.0
+    }
+
+    fn is_delta(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn light_fades_between_the_cones() {
+        let light: SpotLight = SpotLight::new(Vec3::new(0.0, 0.0, 1.0), Vec3::new(0.0, 0.0, -1.0), Color::new(1.0, 1.0, 1.0), 1.0, 2.0, 20.0, 40.0);
+        let at_angle = |degrees: f32| -> Option<f32> {
+            let point: Vec3 = Vec3::new(degrees.to_radians().tan(), 0.0, 0.0);
+            let distance: f32 = (point - Vec3::new(0.0, 0.0, 1.0)).get_length();
+            light.sample(&point, None).map(|sample| sample.get_radiance().x * distance * distance)
+        };
+        assert!((at_angle(0.0).unwrap() - 1.0).abs() < 1e-5);
+        assert!((at_angle(15.0).unwrap() - 1.0).abs() < 1e-5);
+        let edge: f32 = at_angle(30.0).unwrap();
+        assert!(edge > 0.0 && edge < 1.0);
+        assert!(at_angle(35.0).unwrap() < edge);
+        assert!(at_angle(45.0).is_none());
+        // nothing behind the light
+        assert!(light.sample(&Vec3::new(0.0, 0.0, 2.0), None).is_none());
+    }
+
+    #[test]
+    fn outer_cones_never_end_inside_the_inner_one() {
+        let light: SpotLight = SpotLight::new(Vec3::new(0.0, 0.0, 1.0), Vec3::new(0.0, 0.0, -1.0), Color::new(1.0, 1.0, 1.0), 1.0, 2.0, 30.0, 10.0);
+        assert!(light.sample(&Vec3::new(20.0_f32.to_radians().tan(), 0.0, 0.0), None).is_some());
+        assert!(light.sample(&Vec3::new(40.0_f32.to_radians().tan(), 0.0, 0.0), None).is_none());
+    }
+}
@@ -29,6 +29,15 @@ mod scene;
 mod hittable;
 mod material;
 mod config;
+mod bvh;
+mod integrator;
+mod light;
+mod background;
+mod distribution;
+mod hdr;
+mod texture;
+mod sampling;
+mod spectrum;
 
 struct FileRender {
     path: String
@@ -59,7 +68,9 @@ fn main() -> Result<(), Box<dyn Error>>{
     let mut watcher = recommended_watcher(renderer)?;
     watcher.watch(path.as_ref(), NonRecursive)?;
 
-    loop {}
+    loop {
+        std::thread::park();
+    }
 
     // Ok(())
 }
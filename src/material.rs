@@ -1,16 +1,121 @@
 use std::fmt::Debug;
 use crate::hittable::HitRecord;
+use crate::material::medium::Medium;
 use crate::ray::Ray;
 use crate::vec::Vec3;
 
 pub mod diffuse;
 pub mod reflective;
 pub mod dielectric;
+pub mod emissive;
+pub mod normal_map;
+pub mod microfacet;
+pub mod conductor;
+pub mod principled;
+pub mod medium;
+pub mod subsurface;
+pub mod thin_film;
+pub mod mix;
+pub mod layered;
 
 pub type Color = Vec3;
 
-pub trait Material {
-    fn scatter(&self, ray: &Ray, hit: &HitRecord) -> Option<(Ray, Color)>;
+/// The outcome of a ray scattering off a surface
+#[derive(Debug, Clone, Copy)]
+pub struct ScatterRecord {
+    ray: Ray,
+    attenuation: Color,
+    specular: bool,
+    /// Whether the direction depended on the wavelength of the ray
+    dispersed: bool
+}
+
+impl ScatterRecord {
+    /// `attenuation` is the BSDF times the cosine term divided by the density of picking the ray
+    pub fn new(ray: Ray, attenuation: Color) -> ScatterRecord {
+        ScatterRecord {
+            ray,
+            attenuation,
+            specular: false,
+            dispersed: false
+        }
+    }
+
+    /// A scatter in a direction that `Material::eval` and `Material::pdf` can't describe,
+    /// such as a mirror reflection, so light found along it isn't weighed against light sampling
+    pub fn specular(ray: Ray, attenuation: Color) -> ScatterRecord {
+        ScatterRecord {
+            ray,
+            attenuation,
+            specular: true,
+            dispersed: false
+        }
+    }
+
+    /// Marks the direction as picked for the hero wavelength alone, so a spectral render
+    /// has to drop the others
+    pub fn dispersed(mut self) -> Self {
+        self.dispersed = true;
+        self
+    }
+
+    /// The same scatter with its attenuation replaced, for materials built out of others
+    pub fn with_attenuation(mut self, attenuation: Color) -> Self {
+        self.attenuation = attenuation;
+        self
+    }
+
+    pub fn get_ray(&self) -> &Ray {
+        &self.ray
+    }
+
+    pub fn get_attenuation(&self) -> Color {
+        self.attenuation
+    }
+
+    pub fn is_specular(&self) -> bool {
+        self.specular
+    }
+
+    pub fn is_dispersed(&self) -> bool {
+        self.dispersed
+    }
+}
+
+pub trait Material: Send + Sync {
+    fn scatter(&self, ray: &Ray, hit: &HitRecord) -> Option<ScatterRecord>;
+
+    /// The BSDF for light arriving from `direction` and leaving back along the ray,
+    /// multiplied by the cosine of the angle between `direction` and the normal
+    fn eval(&self, _ray: &Ray, _hit: &HitRecord, _direction: &Vec3) -> Color {
+        Color::new(0.0, 0.0, 0.0)
+    }
+
+    /// The solid angle density with which a non specular `scatter` picks `direction`
+    fn pdf(&self, _ray: &Ray, _hit: &HitRecord, _direction: &Vec3) -> f32 {
+        0.0
+    }
+
+    /// Whether any of the ways the surface scatters can be described by `eval` and `pdf`,
+    /// so lights are worth sampling at it whichever way a given `scatter` went
+    fn has_non_specular(&self) -> bool {
+        false
+    }
+
+    fn is_emissive(&self) -> bool {
+        false
+    }
+
+    /// The medium the surface encloses, or `None` if light passes through the inside unchanged
+    fn medium(&self) -> Option<Medium> {
+        None
+    }
+
+    /// The light given off by the surface towards the origin of the ray, in RGB or at the
+    /// `wavelengths` of a spectral render
+    fn emitted(&self, _ray: &Ray, _hit: &HitRecord, _wavelengths: Option<&Vec3>) -> Color {
+        Color::new(0.0, 0.0, 0.0)
+    }
 }
 
 impl Debug for dyn Material {
@@ -19,3 +124,81 @@ impl Debug for dyn Material {
             .finish()
     }
 }
+
+/// Checks shared by the tests of every material
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::sync::Arc;
+    use super::*;
+    use crate::material::emissive::Emissive;
+    use crate::sampling::tests::mean_and_error;
+    use crate::sampling::{uniform_sphere, uniform_sphere_pdf};
+    use crate::texture::Texture;
+    use crate::texture::constant::Constant;
+
+    /// Angles in radians from the normal that rays arrive at in the checks, the last from
+    /// behind the surface
+    const ANGLES: [f32; 4] = [0.1, 0.8, 1.4, 2.3];
+
+    /// A ray coming down onto a floor facing up at `angle` radians from the normal and the hit
+    /// where it meets the floor at the origin
+    pub(crate) fn floor_hit(material: &Arc<dyn Material>, angle: f32) -> (Ray, HitRecord<'_>) {
+        let direction: Vec3 = Vec3::new(angle.sin(), 0.0, -angle.cos());
+        let ray: Ray = Ray::new(-direction, direction);
+        (ray, HitRecord::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 1.0), 1.0, material))
+    }
+
+    /// A texture of the same gray everywhere
+    pub(crate) fn gray(value: f32) -> Arc<dyn Texture> {
+        Arc::new(Constant::new(Color::new(value, value, value)))
+    }
+
+    /// A white emitter of unit strength
+    pub(crate) fn lamp() -> Arc<dyn Material> {
+        Arc::new(Emissive::new(gray(1.0), 1.0))
+    }
+
+    fn total(color: Color) -> f32 {
+        color.x + color.y + color.z
+    }
+
+    /// Checks that every non specular scatter is weighed by `eval` over `pdf` in its direction
+    /// and that `pdf` integrates to at most one over the sphere
+    pub(crate) fn assert_pdf_matches_eval(material: Arc<dyn Material>) {
+        for angle in ANGLES {
+            let (ray, hit) = floor_hit(&material, angle);
+            for _ in 0..1000 {
+                let scatter: ScatterRecord = match material.scatter(&ray, &hit) {
+                    Some(scatter) if !scatter.is_specular() => scatter,
+                    _ => continue
+                };
+                let direction: &Vec3 = scatter.get_ray().get_direction();
+                let pdf: f32 = material.pdf(&ray, &hit, direction);
+                assert!(pdf > 0.0, "a direction scattered at {} has no density", angle);
+                let expected: f32 = total(material.eval(&ray, &hit, direction)) / pdf;
+                let attenuation: f32 = total(scatter.get_attenuation());
+                assert!((attenuation - expected).abs() <= 1e-3 * expected.max(1.0), "scattered {} at {} but eval over pdf is {}", attenuation, angle, expected);
+            }
+            let (integral, error) = mean_and_error((0..20_000)
+                .map(|_| material.pdf(&ray, &hit, &uniform_sphere()) / uniform_sphere_pdf())
+                .collect());
+            assert!(integral < 1.01 + 4.0 * error, "the density integrates to {} +- {} at {}", integral, error, angle);
+        }
+    }
+
+    /// Checks that scattering reflects as much light on average as `eval` integrates to, for
+    /// materials without specular scatters
+    pub(crate) fn assert_scatter_matches_eval(material: Arc<dyn Material>) {
+        for angle in ANGLES {
+            let (ray, hit) = floor_hit(&material, angle);
+            let (scattered, scattered_error) = mean_and_error((0..40_000)
+                .map(|_| material.scatter(&ray, &hit).map_or(0.0, |scatter| total(scatter.get_attenuation())))
+                .collect());
+            let (evaluated, evaluated_error) = mean_and_error((0..40_000)
+                .map(|_| total(material.eval(&ray, &hit, &uniform_sphere())) / uniform_sphere_pdf())
+                .collect());
+            let error: f32 = (scattered_error * scattered_error + evaluated_error * evaluated_error).sqrt();
+            assert!((scattered - evaluated).abs() < 0.01 * evaluated + 4.0 * error, "scattered {} at {} but eval integrates to {} +- {}", scattered, angle, evaluated, error);
+        }
+    }
+}
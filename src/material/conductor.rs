@@ -0,0 +1,156 @@
+use crate::hittable::HitRecord;
+use crate::material::microfacet::{fresnel_conductor, Frame, Ggx};
+use crate::material::{Color, Material, ScatterRecord};
+use crate::ray::Ray;
+use crate::vec::Vec3;
+
+/// A metal with GGX microfacet roughness and Fresnel reflectance from its complex index of
+/// refraction
+#[derive(Debug, Clone, Copy)]
+pub struct Conductor {
+    /// The real part of the index of refraction for the red, green and blue channels
+    eta: Color,
+    /// The imaginary part, how strongly the metal absorbs
+    k: Color,
+    distribution: Ggx
+}
+
+impl Conductor {
+    pub fn new(eta: Color, k: Color, roughness: f32) -> Conductor {
+        Conductor {
+            eta,
+            k,
+            distribution: Ggx::from_roughness(roughness)
+        }
+    }
+
+    /// Indices of refraction of common metals at 650, 550 and 450 nm
+    pub fn preset(name: &str) -> Option<(Color, Color)> {
+        let (eta, k) = match name {
+            "gold" => ([0.143, 0.374, 1.442], [3.983, 2.385, 1.603]),
+            "silver" => ([0.155, 0.117, 0.138], [4.828, 3.122, 2.147]),
+            "copper" => ([0.200, 0.924, 1.102], [3.912, 2.452, 2.142]),
+            "aluminum" => ([1.657, 0.880, 0.521], [9.224, 6.270, 4.837]),
+            "iron" => ([2.912, 2.950, 2.585], [3.089, 2.932, 2.767]),
+            "titanium" => ([2.160, 1.930, 1.720], [2.930, 2.680, 2.460]),
+            _ => return None
+        };
+        Some((Color::new(eta[0], eta[1], eta[2]), Color::new(k[0], k[1], k[2])))
+    }
+
+    /// The complex index of refraction of a metal with the given head on reflectivity whose
+    /// color shifts towards `edge_tint` at grazing angles, following Gulbrandsen 2014, "Artist
+    /// Friendly Metallic Fresnel"
+    pub fn from_reflectivity(reflectivity: Color, edge_tint: Color) -> (Color, Color) {
+        let channel = |reflectivity: f32, edge_tint: f32| -> (f32, f32) {
+            let r: f32 = reflectivity.clamp(0.0, 0.99);
+            let root: f32 = r.sqrt();
+            let eta: f32 = edge_tint * (1.0 - r) / (1.0 + r) + (1.0 - edge_tint) * (1.0 + root) / (1.0 - root);
+            let k: f32 = ((r * (eta + 1.0) * (eta + 1.0) - (eta - 1.0) * (eta - 1.0)) / (1.0 - r)).max(0.0).sqrt();
+            (eta, k)
+        };
+        let (red, green, blue) = (
+            channel(reflectivity.x, edge_tint.x),
+            channel(reflectivity.y, edge_tint.y),
+            channel(reflectivity.z, edge_tint.z)
+        );
+        (Color::new(red.0, green.0, blue.0), Color::new(red.1, green.1, blue.1))
+    }
+}
+
+impl Material for Conductor {
+    fn scatter(&self, ray: &Ray, hit: &HitRecord) -> Option<ScatterRecord> {
+        let (frame, outgoing) = Frame::around_hit(ray, hit);
+        if outgoing.z <= 0.0 {
+            return None;
+        }
+        if self.distribution.is_smooth() {
+            let incoming: Vec3 = Vec3::new(-outgoing.x, -outgoing.y, outgoing.z);
+            let attenuation: Color = fresnel_conductor(outgoing.z, &self.eta, &self.k);
+            return Some(ScatterRecord::specular(Ray::new(*hit.get_point(), frame.world(&incoming)), attenuation));
+        }
+        let normal: Vec3 = self.distribution.sample_visible_normal(&outgoing);
+        let incoming: Vec3 = (-outgoing).reflect(&normal);
+        if incoming.z <= 0.0 {
+            return None;
+        }
+        // sampling visible normals leaves only Fresnel and the shadowing of the light
+        let attenuation: Color = fresnel_conductor(outgoing.dot(&normal), &self.eta, &self.k)
+            * (self.distribution.masking_shadowing(&outgoing, &incoming) / self.distribution.masking(&outgoing));
+        Some(ScatterRecord::new(Ray::new(*hit.get_point(), frame.world(&incoming)), attenuation))
+    }
+
+    fn eval(&self, ray: &Ray, hit: &HitRecord, direction: &Vec3) -> Color {
+        let black: Color = Color::new(0.0, 0.0, 0.0);
+        if self.distribution.is_smooth() {
+            return black;
+        }
+        let (frame, outgoing) = Frame::around_hit(ray, hit);
+        let incoming: Vec3 = frame.local(&(*direction / direction.get_length()));
+        if outgoing.z <= 0.0 || incoming.z <= 0.0 {
+            return black;
+        }
+        let half: Vec3 = outgoing + incoming;
+        let half: Vec3 = half / half.get_length();
+        // D G F / (4 cos_o cos_i), times cos_i
+        fresnel_conductor(outgoing.dot(&half), &self.eta, &self.k)
+            * (self.distribution.distribution(&half) * self.distribution.masking_shadowing(&outgoing, &incoming) / (4.0 * outgoing.z))
+    }
+
+    fn pdf(&self, ray: &Ray, hit: &HitRecord, direction: &Vec3) -> f32 {
+        if self.distribution.is_smooth() {
+            return 0.0;
+        }
+        let (frame, outgoing) = Frame::around_hit(ray, hit);
+        let incoming: Vec3 = frame.local(&(*direction / direction.get_length()));
+        if outgoing.z <= 0.0 || incoming.z <= 0.0 {
+            return 0.0;
+        }
+        let half: Vec3 = outgoing + incoming;
+        let half: Vec3 = half / half.get_length();
+        // the reflection halves the solid angle of the normal's density
+        self.distribution.visible_normal_pdf(&outgoing, &half) / (4.0 * outgoing.dot(&half))
+    }
+
+    fn has_non_specular(&self) -> bool {
+        !self.distribution.is_smooth()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use super::*;
+    use crate::material::tests::{assert_pdf_matches_eval, assert_scatter_matches_eval, floor_hit};
+
+    #[test]
+    fn rough_metals_sample_what_they_reflect() {
+        let (eta, k) = Conductor::preset("gold").unwrap();
+        for roughness in [0.3, 0.7] {
+            assert_pdf_matches_eval(Arc::new(Conductor::new(eta, k, roughness)));
+            assert_scatter_matches_eval(Arc::new(Conductor::new(eta, k, roughness)));
+        }
+    }
+
+    #[test]
+    fn smooth_metals_only_scatter_specularly() {
+        let (eta, k) = Conductor::preset("silver").unwrap();
+        let material: Arc<dyn Material> = Arc::new(Conductor::new(eta, k, 0.0));
+        assert!(!material.has_non_specular());
+        let (ray, hit) = floor_hit(&material, 0.5);
+        let scatter: ScatterRecord = material.scatter(&ray, &hit).unwrap();
+        assert!(scatter.is_specular());
+        let direction: &Vec3 = scatter.get_ray().get_direction();
+        assert!((direction.x - ray.get_direction().x).abs() < 1e-3 && (direction.z + ray.get_direction().z).abs() < 1e-3);
+    }
+
+    #[test]
+    fn reflectivity_round_trips_through_the_index() {
+        let reflectivity: Color = Color::new(0.9, 0.6, 0.3);
+        let (eta, k) = Conductor::from_reflectivity(reflectivity, reflectivity);
+        let head_on: Color = fresnel_conductor(1.0, &eta, &k);
+        for (result, expected) in [(head_on.x, 0.9), (head_on.y, 0.6), (head_on.z, 0.3)] {
+            assert!((result - expected).abs() < 1e-3, "{} reflected head on instead of {}", result, expected);
+        }
+    }
+}
@@ -1,56 +1,361 @@
 // use std::intrinsics::sqrtf32;
+use std::sync::Arc;
 use crate::hittable::HitRecord;
-use crate::material::{Color, Material};
+use crate::material::microfacet::{fresnel_dielectric, Frame, Ggx};
+use crate::material::medium::Medium;
+use crate::material::thin_film::ThinFilm;
+use crate::material::{Color, Material, ScatterRecord};
 use crate::ray::Ray;
+use crate::texture::Texture;
 use crate::vec::Vec3;
 
-#[derive(Clone, Copy, Debug)]
+/// The wavelength in nm an index of refraction is quoted at and used when rendering without
+/// wavelengths, the yellow helium d line
+pub const D_LINE: f32 = 587.6;
+
+/// How the index of refraction changes with wavelength
+#[derive(Debug, Clone, Copy)]
+pub enum RefractiveIndex {
+    Constant(f32),
+    /// Cauchy's equation a + b / λ² with λ in µm
+    Cauchy { a: f32, b: f32 },
+    /// The Sellmeier equation n² = 1 + Σ bᵢ λ² / (λ² - cᵢ) with λ in µm and c in µm²
+    Sellmeier { b: [f32; 3], c: [f32; 3] }
+}
+
+impl RefractiveIndex {
+    /// The index at a wavelength in nm
+    pub fn at(&self, wavelength: f32) -> f32 {
+        let micrometres_2: f32 = (wavelength / 1000.0).powi(2);
+        match self {
+            RefractiveIndex::Constant(index) => *index,
+            RefractiveIndex::Cauchy { a, b } => a + b / micrometres_2,
+            RefractiveIndex::Sellmeier { b, c } => {
+                let sum: f32 = (0..3).map(|i| b[i] * micrometres_2 / (micrometres_2 - c[i])).sum();
+                (1.0 + sum).max(1.0).sqrt()
+            }
+        }
+    }
+
+    /// Sellmeier coefficients of common transparent materials
+    pub fn preset(name: &str) -> Option<RefractiveIndex> {
+        let (b, c) = match name {
+            "bk7" => ([1.039612, 0.2317923, 1.010469], [0.006000699, 0.02001791, 103.5607]),
+            "fused_silica" => ([0.6961663, 0.4079426, 0.8974794], [0.004679148, 0.01351206, 97.934]),
+            "sapphire" => ([1.431349, 0.6505471, 5.341402], [0.00527993, 0.0142383, 325.0178]),
+            "diamond" => ([0.3306, 4.3356, 0.0], [0.030625, 0.011236, 0.0]),
+            _ => return None
+        };
+        Some(RefractiveIndex::Sellmeier { b, c })
+    }
+
+    pub fn is_dispersive(&self) -> bool {
+        !matches!(self, RefractiveIndex::Constant(_))
+    }
+}
+
+#[derive(Clone, Debug)]
 pub struct Dielectric {
-    refraction_index: f32,
-    color: Color
+    refraction_index: RefractiveIndex,
+    color: Arc<dyn Texture>,
+    /// Microfacet roughness for frosted glass, smooth glass refracts perfectly
+    distribution: Ggx,
+    /// Absorption coefficients of the inside, per unit of distance
+    absorption: Option<Color>,
+    film: Option<ThinFilm>
 }
 
 impl Dielectric {
-    pub fn new(refraction_index: f32, color: Color) -> Dielectric {
+    pub fn new(refraction_index: f32, color: Arc<dyn Texture>, roughness: f32) -> Dielectric {
         Dielectric {
-            refraction_index,
-            color
+            refraction_index: RefractiveIndex::Constant(refraction_index),
+            color,
+            distribution: Ggx::from_roughness(roughness),
+            absorption: None,
+            film: None
         }
     }
 
+    /// Lets the index of refraction change with wavelength so spectral renders split colors
+    pub fn with_dispersion(mut self, refraction_index: RefractiveIndex) -> Self {
+        self.refraction_index = refraction_index;
+        self
+    }
+
+    pub fn with_absorption(mut self, absorption: Color) -> Self {
+        self.absorption = Some(absorption);
+        self
+    }
+
+    pub fn with_thin_film(mut self, film: ThinFilm) -> Self {
+        self.film = Some(film);
+        self
+    }
+
     pub fn get_refraction_index(&self) -> f32 {
-        self.refraction_index
+        self.refraction_index.at(D_LINE)
     }
 
-    pub fn get_color(&self) -> Color {
-        self.color
+    /// Marks the scatter as only right for the hero wavelength when the index depends on it
+    fn disperse(&self, ray: &Ray, scatter: ScatterRecord) -> ScatterRecord {
+        if self.refraction_index.is_dispersive() && ray.get_wavelength().is_some() {
+            scatter.dispersed()
+        } else {
+            scatter
+        }
     }
 
-    fn reflectance(cosine: f32, refraction_index: f32) -> f32 {
-        let r0: f32 = ((1.0 - refraction_index) / (1.0 + refraction_index)).powi(2);
-        r0 + (1.0 - r0) * (1.0 - cosine).powi(5)
+    /// The index of refraction at the hero wavelength of the ray
+    fn index(&self, ray: &Ray) -> f32 {
+        self.refraction_index.at(ray.get_wavelength().unwrap_or(D_LINE))
     }
+
+    pub fn get_color(&self) -> &Arc<dyn Texture> {
+        &self.color
+    }
+
+    /// The reflectance for light arriving at an angle with cosine `cosine` on the side of the
+    /// ray, through the film if there is one
+    fn reflectance(&self, cosine: f32, front_face: bool, refraction_index: f32) -> Color {
+        let (outer, inner) = if front_face {
+            (1.0, refraction_index)
+        } else {
+            (refraction_index, 1.0)
+        };
+        match &self.film {
+            Some(film) => film.reflectance(cosine, outer, (Color::new(inner, inner, inner), Color::new(0.0, 0.0, 0.0))),
+            None => {
+                let fresnel: f32 = fresnel_dielectric(cosine, inner / outer);
+                Color::new(fresnel, fresnel, fresnel)
+            }
+        }
+    }
+
+    /// `Frame::around_hit` along with the ratio of the index on the far side to the index on
+    /// this side
+    fn local(&self, ray: &Ray, hit: &HitRecord) -> (Frame, Vec3, f32) {
+        let (frame, outgoing) = Frame::around_hit(ray, hit);
+        let eta: f32 = if hit.is_front_face(ray) {
+            self.index(ray)
+        } else {
+            1.0 / self.index(ray)
+        };
+        (frame, outgoing, eta)
+    }
+
+    /// The microfacet normal that takes `outgoing` to `incoming`, by reflection when they are
+    /// on the same side and refraction otherwise, facing the side of `outgoing`
+    fn half_vector(outgoing: &Vec3, incoming: &Vec3, eta: f32) -> Option<Vec3> {
+        let half: Vec3 = if incoming.z > 0.0 {
+            *outgoing + *incoming
+        } else {
+            -(*outgoing + *incoming * eta)
+        };
+        let length: f32 = half.get_length();
+        if length <= 0.0 {
+            return None;
+        }
+        let half: Vec3 = half / length;
+        let half: Vec3 = if half.z < 0.0 { -half } else { half };
+        // refraction needs the directions on opposite sides of the microfacet too
+        if outgoing.dot(&half) <= 0.0 || (incoming.z < 0.0 && incoming.dot(&half) >= 0.0) {
+            return None;
+        }
+        Some(half)
+    }
+
+    /// The BSDF times the cosine and the density of sampling it, for rough surfaces following
+    /// Walter et al. 2007, "Microfacet Models for Refraction through Rough Surfaces", where
+    /// `fresnel` gives the reflectance of a microfacet from the cosine with `outgoing`
+    pub fn rough_eval_pdf(distribution: &Ggx, fresnel: &dyn Fn(f32) -> Color, outgoing: &Vec3, incoming: &Vec3, eta: f32) -> (Color, f32) {
+        let black: Color = Color::new(0.0, 0.0, 0.0);
+        if outgoing.z <= 0.0 || incoming.z == 0.0 {
+            return (black, 0.0);
+        }
+        let half: Vec3 = match Dielectric::half_vector(outgoing, incoming, eta) {
+            Some(half) => half,
+            None => return (black, 0.0)
+        };
+        let fresnel: Color = fresnel(outgoing.dot(&half));
+        let probability: f32 = average(&fresnel);
+        let density: f32 = distribution.distribution(&half);
+        let masking: f32 = distribution.masking(outgoing);
+        let masking_shadowing: f32 = distribution.masking_shadowing(outgoing, incoming);
+        if incoming.z > 0.0 {
+            let common: f32 = density / (4.0 * outgoing.z);
+            return (fresnel * (masking_shadowing * common), probability * masking * common);
+        }
+        // the change of variables from the microfacet normal to the refracted direction
+        let denominator: f32 = outgoing.dot(&half) + eta * incoming.dot(&half);
+        let common: f32 = density * eta * eta * incoming.dot(&half).abs() * outgoing.dot(&half)
+            / (outgoing.z * denominator * denominator);
+        ((Color::new(1.0, 1.0, 1.0) - fresnel) * (masking_shadowing * common), (1.0 - probability) * masking * common)
+    }
+
+    /// Picks a direction reflected or refracted by a rough surface with the average of the
+    /// Fresnel term, returning it with what's left of the Fresnel term after dividing by that
+    /// chance, so only the shadowing of the light remains for the caller to weigh by
+    pub fn sample_rough(distribution: &Ggx, fresnel: &dyn Fn(f32) -> Color, outgoing: &Vec3, eta: f32) -> Option<(Vec3, Color)> {
+        let normal: Vec3 = distribution.sample_visible_normal(outgoing);
+        let fresnel: Color = fresnel(outgoing.dot(&normal));
+        let probability: f32 = average(&fresnel);
+        let (incoming, weight) = if rand::random::<f32>() < probability {
+            ((-*outgoing).reflect(&normal), fresnel / probability)
+        } else {
+            ((-*outgoing).refract(&normal, 1.0 / eta), (Color::new(1.0, 1.0, 1.0) - fresnel) / (1.0 - probability))
+        };
+        let reflected: bool = outgoing.dot(&normal) * incoming.dot(&normal) > 0.0;
+        if reflected != (incoming.z > 0.0) {
+            return None;
+        }
+        Some((incoming, weight))
+    }
+}
+
+fn average(color: &Color) -> f32 {
+    (color.x + color.y + color.z) / 3.0
 }
 
 impl Material for Dielectric {
-    fn scatter(&self, ray: &Ray, hit: &HitRecord) -> Option<(Ray, Color)> {
-        let refraction_ratio: f32 = if hit.get_normal().dot(ray.get_direction()) > 0.0 {
-            self.refraction_index
+    fn scatter(&self, ray: &Ray, hit: &HitRecord) -> Option<ScatterRecord> {
+        if !self.distribution.is_smooth() {
+            let (frame, outgoing, eta) = self.local(ray, hit);
+            if outgoing.z <= 0.0 {
+                return None;
+            }
+            let front_face: bool = hit.is_front_face(ray);
+            let refraction_index: f32 = self.index(ray);
+            let fresnel = |cosine: f32| -> Color { self.reflectance(cosine, front_face, refraction_index) };
+            let (incoming, weight) = Dielectric::sample_rough(&self.distribution, &fresnel, &outgoing, eta)?;
+            let attenuation: Color = self.color.value(hit.get_uv(), hit.get_point()) * weight
+                * (self.distribution.masking_shadowing(&outgoing, &incoming) / self.distribution.masking(&outgoing));
+            return Some(self.disperse(ray, ScatterRecord::new(Ray::new(*hit.get_point(), frame.world(&incoming)), attenuation)));
+        }
+        let refraction_index: f32 = self.index(ray);
+        let refraction_ratio: f32 = if hit.is_front_face(ray) {
+            1.0 / refraction_index
         } else {
-            1.0 / self.refraction_index
+            refraction_index
         };
+        let normal: Vec3 = hit.get_facing_normal(ray);
         let unit_direction: Vec3 = ray.get_direction().get_normalized();
-        let cos_theta: f32 = (-unit_direction).dot(hit.get_normal()).min(1.0);
+        let cos_theta: f32 = (-unit_direction).dot(&normal).min(1.0);
         let sin_theta: f32 = 1.0 - cos_theta * cos_theta;
         let sin_theta: f32 = sin_theta.sqrt();
         let cannot_refract: bool = refraction_ratio * sin_theta > 1.0;
-        let direction: Vec3 = if cannot_refract || Dielectric::reflectance(cos_theta, refraction_ratio) > rand::random::<f32>() {
-            unit_direction.reflect(hit.get_normal())
+        let white: Color = Color::new(1.0, 1.0, 1.0);
+        // reflect with the average reflectance, weighing by the rest of it for colored films
+        let reflectance: Color = self.reflectance(cos_theta, hit.is_front_face(ray), refraction_index);
+        let probability: f32 = average(&reflectance);
+        let (direction, weight) = if cannot_refract {
+            (unit_direction.reflect(&normal), white)
+        } else if rand::random::<f32>() < probability {
+            (unit_direction.reflect(&normal), reflectance / probability)
         } else {
-            unit_direction.refract(hit.get_normal(), refraction_ratio)
+            (unit_direction.refract(&normal, refraction_ratio), (white - reflectance) / (1.0 - probability))
         };
-        let scattered: Ray = Ray::from_to(*hit.get_point(), direction);
-        let attenuation: Color = self.color;
-        Some((scattered, attenuation))
+        let scattered: Ray = Ray::new(*hit.get_point(), direction);
+        let attenuation: Color = self.color.value(hit.get_uv(), hit.get_point()) * weight;
+        Some(self.disperse(ray, ScatterRecord::specular(scattered, attenuation)))
+    }
+
+    fn eval(&self, ray: &Ray, hit: &HitRecord, direction: &Vec3) -> Color {
+        if self.distribution.is_smooth() {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+        let (frame, outgoing, eta) = self.local(ray, hit);
+        let incoming: Vec3 = frame.local(&(*direction / direction.get_length()));
+        let front_face: bool = hit.is_front_face(ray);
+        let refraction_index: f32 = self.index(ray);
+        let fresnel = |cosine: f32| -> Color { self.reflectance(cosine, front_face, refraction_index) };
+        let (value, _) = Dielectric::rough_eval_pdf(&self.distribution, &fresnel, &outgoing, &incoming, eta);
+        self.color.value(hit.get_uv(), hit.get_point()) * value
+    }
+
+    fn pdf(&self, ray: &Ray, hit: &HitRecord, direction: &Vec3) -> f32 {
+        if self.distribution.is_smooth() {
+            return 0.0;
+        }
+        let (frame, outgoing, eta) = self.local(ray, hit);
+        let incoming: Vec3 = frame.local(&(*direction / direction.get_length()));
+        let front_face: bool = hit.is_front_face(ray);
+        let refraction_index: f32 = self.index(ray);
+        let fresnel = |cosine: f32| -> Color { self.reflectance(cosine, front_face, refraction_index) };
+        Dielectric::rough_eval_pdf(&self.distribution, &fresnel, &outgoing, &incoming, eta).1
+    }
+
+    fn has_non_specular(&self) -> bool {
+        !self.distribution.is_smooth()
+    }
+
+    fn medium(&self) -> Option<Medium> {
+        self.absorption.map(Medium::absorbing)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::tests::{assert_pdf_matches_eval, assert_scatter_matches_eval, floor_hit};
+    use crate::texture::constant::Constant;
+
+    fn glass(roughness: f32) -> Dielectric {
+        Dielectric::new(1.5, Arc::new(Constant::new(Color::new(1.0, 1.0, 1.0))), roughness)
+    }
+
+    #[test]
+    fn presets_match_their_catalog_indices() {
+        for (name, index) in [("bk7", 1.5168), ("fused_silica", 1.4585), ("sapphire", 1.7682), ("diamond", 2.4175)] {
+            let preset: RefractiveIndex = RefractiveIndex::preset(name).unwrap();
+            assert!((preset.at(D_LINE) - index).abs() < 2e-3, "{} has an index of {} instead of {}", name, preset.at(D_LINE), index);
+            // blue bends more than red in every one of them
+            assert!(preset.at(450.0) > preset.at(650.0));
+        }
+        assert!(RefractiveIndex::preset("unobtainium").is_none());
+        let cauchy: RefractiveIndex = RefractiveIndex::Cauchy { a: 1.5, b: 0.01 };
+        assert!((cauchy.at(1000.0) - 1.51).abs() < 1e-6);
+        assert!(!RefractiveIndex::Constant(1.5).is_dispersive() && cauchy.is_dispersive());
+    }
+
+    #[test]
+    fn dispersion_only_marks_spectral_rays() {
+        let prism: Dielectric = glass(0.0).with_dispersion(RefractiveIndex::preset("bk7").unwrap());
+        let material: Arc<dyn Material> = Arc::new(prism);
+        let (ray, hit) = floor_hit(&material, 0.5);
+        assert!(!material.scatter(&ray, &hit).unwrap().is_dispersed());
+        let spectral: Ray = ray.with_wavelength(450.0);
+        assert!(material.scatter(&spectral, &hit).unwrap().is_dispersed());
+    }
+
+    #[test]
+    fn frosted_glass_samples_what_it_transmits() {
+        for roughness in [0.3, 0.7] {
+            assert_pdf_matches_eval(Arc::new(glass(roughness)));
+            assert_scatter_matches_eval(Arc::new(glass(roughness)));
+        }
+    }
+
+    #[test]
+    fn smooth_glass_keeps_the_light_it_splits() {
+        let material: Arc<dyn Material> = Arc::new(glass(0.0));
+        assert!(!material.has_non_specular());
+        for angle in [0.3, 1.2, 2.0, 2.9] {
+            let (ray, hit) = floor_hit(&material, angle);
+            let scatter: ScatterRecord = material.scatter(&ray, &hit).unwrap();
+            assert!(scatter.is_specular());
+            // reflections and refractions are picked by their share so neither loses light
+            let attenuation: Color = scatter.get_attenuation();
+            assert!((attenuation.x - 1.0).abs() < 1e-4, "{} passed on at {}", attenuation.x, angle);
+        }
+    }
+
+    #[test]
+    fn absorbing_glass_encloses_a_medium() {
+        assert!(glass(0.0).medium().is_none());
+        let medium: Medium = glass(0.0).with_absorption(Color::new(1.0, 0.0, 2.0)).medium().unwrap();
+        let transmittance: Color = medium.transmittance(0.5);
+        assert!((transmittance.x - (-0.5_f32).exp()).abs() < 1e-6);
+        assert!(transmittance.y == 1.0);
+        assert!((transmittance.z - (-1.0_f32).exp()).abs() < 1e-6);
     }
 }
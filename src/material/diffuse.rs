@@ -1,31 +1,127 @@
+use std::f32::consts::PI;
+use std::sync::Arc;
 use crate::hittable::HitRecord;
-use crate::material::{Color, Material};
+use crate::material::microfacet::Frame;
+use crate::material::{Color, Material, ScatterRecord};
 use crate::ray::Ray;
+use crate::sampling::{cosine_hemisphere, cosine_hemisphere_pdf};
+use crate::texture::Texture;
 use crate::vec::Vec3;
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Clone)]
 pub struct Diffuse {
-    color: Color,
-    albedo: f32
+    color: Arc<dyn Texture>,
+    albedo: f32,
+    /// The A and B terms of the Oren-Nayar model for rough surfaces, plain Lambertian without
+    oren_nayar: Option<(f32, f32)>
 }
 
 impl Diffuse {
-    pub fn new(color: Color, albedo: f32) -> Diffuse {
+    pub fn new(color: Arc<dyn Texture>, albedo: f32) -> Diffuse {
         Diffuse {
             color,
-            albedo
+            albedo,
+            oren_nayar: None
         }
     }
+
+    /// Makes the surface rough following Oren and Nayar 1994, "Generalization of Lambert's
+    /// Reflectance Model", where `sigma` is the standard deviation of the facet angles in radians
+    pub fn with_oren_nayar(mut self, sigma: f32) -> Self {
+        let sigma_2: f32 = sigma * sigma;
+        self.oren_nayar = Some((1.0 - sigma_2 / (2.0 * (sigma_2 + 0.33)), 0.45 * sigma_2 / (sigma_2 + 0.09)));
+        self
+    }
+
+    /// How much brighter or darker than Lambertian the surface is between two local directions
+    fn roughness_factor(&self, outgoing: &Vec3, incoming: &Vec3) -> f32 {
+        let (a, b) = match self.oren_nayar {
+            Some(terms) => terms,
+            None => return 1.0
+        };
+        let sin_outgoing: f32 = (1.0 - outgoing.z * outgoing.z).max(0.0).sqrt();
+        let sin_incoming: f32 = (1.0 - incoming.z * incoming.z).max(0.0).sqrt();
+        if sin_outgoing <= 1e-4 || sin_incoming <= 1e-4 {
+            return a;
+        }
+        let cos_phi: f32 = (outgoing.x * incoming.x + outgoing.y * incoming.y) / (sin_outgoing * sin_incoming);
+        // the sine of the larger angle from the normal and the tangent of the smaller
+        let (sin_alpha, tan_beta) = if incoming.z.abs() > outgoing.z.abs() {
+            (sin_outgoing, sin_incoming / incoming.z.abs())
+        } else {
+            (sin_incoming, sin_outgoing / outgoing.z.abs())
+        };
+        a + b * cos_phi.max(0.0) * sin_alpha * tan_beta
+    }
 }
 
 impl Material for Diffuse {
-    fn scatter(&self, _ray: &Ray, hit: &HitRecord) -> Option<(Ray, Color)> {
-        let mut target: Vec3 = hit.get_point() + hit.get_normal() + Vec3::random_in_unit_sphere();
-        if target.near_zero() {
-            target = hit.get_point() + hit.get_normal();
+    fn scatter(&self, ray: &Ray, hit: &HitRecord) -> Option<ScatterRecord> {
+        let (frame, outgoing) = Frame::around_hit(ray, hit);
+        let incoming: Vec3 = cosine_hemisphere();
+        let scattered: Ray = Ray::new(*hit.get_point(), frame.world(&incoming));
+        // the cosine and the 1 / pi cancel against the density
+        let attenuation: Color = self.color.value(hit.get_uv(), hit.get_point())
+            * (self.albedo * self.roughness_factor(&outgoing, &incoming));
+        Some(ScatterRecord::new(scattered, attenuation))
+    }
+
+    fn eval(&self, ray: &Ray, hit: &HitRecord, direction: &Vec3) -> Color {
+        let (frame, outgoing) = Frame::around_hit(ray, hit);
+        let incoming: Vec3 = frame.local(&(*direction / direction.get_length()));
+        if incoming.z <= 0.0 {
+            return Color::new(0.0, 0.0, 0.0);
         }
-        let scattered: Ray = Ray::from_to(*hit.get_point(), target);
-        let attenuation: Color = self.color * self.albedo;
-        Some((scattered, attenuation))
+        self.color.value(hit.get_uv(), hit.get_point())
+            * (self.albedo * self.roughness_factor(&outgoing, &incoming) * incoming.z / PI)
+    }
+
+    fn pdf(&self, ray: &Ray, hit: &HitRecord, direction: &Vec3) -> f32 {
+        let cosine: f32 = hit.get_facing_normal(ray).dot(direction) / direction.get_length();
+        cosine_hemisphere_pdf(cosine)
+    }
+
+    fn has_non_specular(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::tests::{assert_pdf_matches_eval, assert_scatter_matches_eval, floor_hit};
+    use crate::texture::constant::Constant;
+
+    fn diffuse() -> Diffuse {
+        Diffuse::new(Arc::new(Constant::new(Color::new(1.0, 1.0, 1.0))), 0.8)
+    }
+
+    #[test]
+    fn lambertian_and_rough_surfaces_sample_what_they_reflect() {
+        for material in [diffuse(), diffuse().with_oren_nayar(0.5)] {
+            let material: Arc<dyn Material> = Arc::new(material);
+            assert_pdf_matches_eval(material.clone());
+            assert_scatter_matches_eval(material);
+        }
+    }
+
+    #[test]
+    fn lambertian_surfaces_reflect_their_albedo() {
+        let material: Arc<dyn Material> = Arc::new(diffuse());
+        let (ray, hit) = floor_hit(&material, 1.0);
+        for _ in 0..100 {
+            let scatter: ScatterRecord = material.scatter(&ray, &hit).unwrap();
+            assert!(scatter.get_ray().get_direction().z > 0.0);
+            assert!((scatter.get_attenuation().x - 0.8).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn rough_surfaces_scatter_back_towards_the_light() {
+        let material: Arc<dyn Material> = Arc::new(diffuse().with_oren_nayar(0.5));
+        let (ray, hit) = floor_hit(&material, 1.0);
+        let back: Vec3 = Vec3::new(-1.0, 0.0, 1.0);
+        let forward: Vec3 = Vec3::new(1.0, 0.0, 1.0);
+        assert!(material.eval(&ray, &hit, &back).x > material.eval(&ray, &hit, &forward).x);
     }
 }
@@ -0,0 +1,46 @@
+use std::sync::Arc;
+use crate::hittable::HitRecord;
+use crate::material::{Color, Material, ScatterRecord};
+use crate::ray::Ray;
+use crate::spectrum::{emission, Blackbody};
+use crate::texture::Texture;
+use crate::vec::Vec3;
+
+/// A material that gives off light and absorbs everything that hits it
+#[derive(Debug, Clone)]
+pub struct Emissive {
+    color: Arc<dyn Texture>,
+    strength: f32,
+    blackbody: Option<Blackbody>
+}
+
+impl Emissive {
+    pub fn new(color: Arc<dyn Texture>, strength: f32) -> Emissive {
+        Emissive {
+            color,
+            strength,
+            blackbody: None
+        }
+    }
+
+    /// Gives off the light of a blackbody, with the color texture as a tint on top
+    pub fn with_blackbody(mut self, blackbody: Blackbody) -> Self {
+        self.blackbody = Some(blackbody);
+        self
+    }
+}
+
+impl Material for Emissive {
+    fn scatter(&self, _ray: &Ray, _hit: &HitRecord) -> Option<ScatterRecord> {
+        None
+    }
+
+    fn is_emissive(&self) -> bool {
+        true
+    }
+
+    fn emitted(&self, _ray: &Ray, hit: &HitRecord, wavelengths: Option<&Vec3>) -> Color {
+        let color: Color = self.color.value(hit.get_uv(), hit.get_point()) * self.strength;
+        emission(&color, self.blackbody.as_ref(), wavelengths)
+    }
+}
@@ -0,0 +1,161 @@
+use std::sync::Arc;
+use rand::random;
+use crate::hittable::HitRecord;
+use crate::material::medium::Medium;
+use crate::material::microfacet::{fresnel_dielectric, Frame, Ggx};
+use crate::material::{Color, Material, ScatterRecord};
+use crate::ray::Ray;
+use crate::spectrum::emission;
+use crate::texture::Texture;
+use crate::vec::Vec3;
+
+/// A clear coat like varnish over another material. Light either reflects off the coat, by
+/// Fresnel for its index of refraction with GGX roughness, or passes through it to the base
+/// tinted by the color of the coat, losing what the coat reflects on the way in and out. Light
+/// bouncing between the coat and the base is left out
+#[derive(Debug, Clone)]
+pub struct Layered {
+    base: Arc<dyn Material>,
+    color: Arc<dyn Texture>,
+    refraction_index: f32,
+    distribution: Ggx
+}
+
+impl Layered {
+    pub fn new(base: Arc<dyn Material>, color: Arc<dyn Texture>, refraction_index: f32, roughness: f32) -> Layered {
+        Layered {
+            base,
+            color,
+            refraction_index,
+            distribution: Ggx::from_roughness(roughness)
+        }
+    }
+
+    fn fresnel(&self, cosine: f32) -> f32 {
+        fresnel_dielectric(cosine, self.refraction_index)
+    }
+
+    /// What reaches the base and comes back out towards `outgoing` from `incoming`, light
+    /// going through the base only crosses the coat once
+    fn transmittance(&self, hit: &HitRecord, outgoing: &Vec3, incoming: &Vec3) -> Color {
+        let out: f32 = if incoming.z > 0.0 { 1.0 - self.fresnel(incoming.z) } else { 1.0 };
+        self.color.value(hit.get_uv(), hit.get_point()) * ((1.0 - self.fresnel(outgoing.z)) * out)
+    }
+
+    /// The coat's reflection times the cosine and the density of sampling it
+    fn coat_eval_pdf(&self, outgoing: &Vec3, incoming: &Vec3) -> (f32, f32) {
+        if self.distribution.is_smooth() || outgoing.z <= 0.0 || incoming.z <= 0.0 {
+            return (0.0, 0.0);
+        }
+        let half: Vec3 = *outgoing + *incoming;
+        let half: Vec3 = half / half.get_length();
+        let value: f32 = self.fresnel(outgoing.dot(&half)) * self.distribution.distribution(&half)
+            * self.distribution.masking_shadowing(outgoing, incoming) / (4.0 * outgoing.z);
+        (value, self.distribution.visible_normal_pdf(outgoing, &half) / (4.0 * outgoing.dot(&half)))
+    }
+}
+
+impl Material for Layered {
+    fn scatter(&self, ray: &Ray, hit: &HitRecord) -> Option<ScatterRecord> {
+        let (frame, outgoing) = Frame::around_hit(ray, hit);
+        if outgoing.z <= 0.0 {
+            return None;
+        }
+        // the coat is picked as often as it reflects head on to the ray
+        let coat: f32 = self.fresnel(outgoing.z);
+        if random::<f32>() < coat {
+            if self.distribution.is_smooth() {
+                let incoming: Vec3 = Vec3::new(-outgoing.x, -outgoing.y, outgoing.z);
+                return Some(ScatterRecord::specular(Ray::new(*hit.get_point(), frame.world(&incoming)), Color::new(1.0, 1.0, 1.0)));
+            }
+            let incoming: Vec3 = (-outgoing).reflect(&self.distribution.sample_visible_normal(&outgoing));
+            let direction: Vec3 = frame.world(&incoming);
+            let pdf: f32 = self.pdf(ray, hit, &direction);
+            if incoming.z <= 0.0 || pdf <= 0.0 {
+                return None;
+            }
+            return Some(ScatterRecord::new(Ray::new(*hit.get_point(), direction), self.eval(ray, hit, &direction) / pdf));
+        }
+        let scatter: ScatterRecord = self.base.scatter(ray, hit)?;
+        let direction: Vec3 = *scatter.get_ray().get_direction();
+        if scatter.is_specular() {
+            // the chance of missing the coat cancels its reflection on the way in
+            let incoming: Vec3 = frame.local(&(direction / direction.get_length()));
+            let attenuation: Color = scatter.get_attenuation() * self.transmittance(hit, &outgoing, &incoming) / (1.0 - coat);
+            return Some(scatter.with_attenuation(attenuation));
+        }
+        let pdf: f32 = self.pdf(ray, hit, &direction);
+        if pdf <= 0.0 {
+            return None;
+        }
+        let value: Color = self.eval(ray, hit, &direction);
+        Some(scatter.with_attenuation(value / pdf))
+    }
+
+    fn eval(&self, ray: &Ray, hit: &HitRecord, direction: &Vec3) -> Color {
+        let (frame, outgoing) = Frame::around_hit(ray, hit);
+        let incoming: Vec3 = frame.local(&(*direction / direction.get_length()));
+        if outgoing.z <= 0.0 {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+        let (coat, _) = self.coat_eval_pdf(&outgoing, &incoming);
+        self.base.eval(ray, hit, direction) * self.transmittance(hit, &outgoing, &incoming) + Color::new(coat, coat, coat)
+    }
+
+    fn pdf(&self, ray: &Ray, hit: &HitRecord, direction: &Vec3) -> f32 {
+        let (frame, outgoing) = Frame::around_hit(ray, hit);
+        let incoming: Vec3 = frame.local(&(*direction / direction.get_length()));
+        if outgoing.z <= 0.0 {
+            return 0.0;
+        }
+        let coat: f32 = self.fresnel(outgoing.z);
+        let (_, coat_pdf) = self.coat_eval_pdf(&outgoing, &incoming);
+        coat * coat_pdf + (1.0 - coat) * self.base.pdf(ray, hit, direction)
+    }
+
+    fn has_non_specular(&self) -> bool {
+        !self.distribution.is_smooth() || self.base.has_non_specular()
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.base.is_emissive()
+    }
+
+    fn medium(&self) -> Option<Medium> {
+        self.base.medium()
+    }
+
+    fn emitted(&self, ray: &Ray, hit: &HitRecord, wavelengths: Option<&Vec3>) -> Color {
+        let (_, outgoing) = Frame::around_hit(ray, hit);
+        let color: Color = self.color.value(hit.get_uv(), hit.get_point()) * (1.0 - self.fresnel(outgoing.z));
+        self.base.emitted(ray, hit, wavelengths) * emission(&color, None, wavelengths)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::diffuse::Diffuse;
+    use crate::material::reflective::Reflective;
+    use crate::material::tests::{assert_pdf_matches_eval, assert_scatter_matches_eval, gray};
+
+    #[test]
+    fn coats_sample_what_they_evaluate() {
+        let diffuse: Arc<dyn Material> = Arc::new(Diffuse::new(gray(1.0), 0.8));
+        let rough: Arc<dyn Material> = Arc::new(Layered::new(diffuse.clone(), gray(0.9), 1.5, 0.3));
+        assert_pdf_matches_eval(rough.clone());
+        assert_scatter_matches_eval(rough);
+        let smooth: Arc<dyn Material> = Arc::new(Layered::new(diffuse, gray(0.9), 1.5, 0.0));
+        assert!(smooth.has_non_specular());
+        assert_pdf_matches_eval(smooth);
+    }
+
+    #[test]
+    fn only_smooth_coats_over_mirrors_are_specular() {
+        let mirror: Arc<dyn Material> = Arc::new(Reflective::new(gray(1.0), 0.0));
+        assert!(!Layered::new(mirror.clone(), gray(1.0), 1.5, 0.0).has_non_specular());
+        let material: Arc<dyn Material> = Arc::new(Layered::new(mirror, gray(1.0), 1.5, 0.3));
+        assert!(material.has_non_specular());
+        assert_pdf_matches_eval(material);
+    }
+}
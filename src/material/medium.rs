@@ -0,0 +1,53 @@
+use crate::material::Color;
+
+/// A homogeneous participating medium filling the inside of a surface, with coefficients per
+/// unit of distance for each channel
+#[derive(Debug, Clone, Copy)]
+pub struct Medium {
+    absorption: Color,
+    scattering: Color
+}
+
+impl Medium {
+    pub fn new(absorption: Color, scattering: Color) -> Medium {
+        Medium {
+            absorption,
+            scattering
+        }
+    }
+
+    /// A medium that only absorbs, like tinted glass
+    pub fn absorbing(absorption: Color) -> Medium {
+        Medium::new(absorption, Color::new(0.0, 0.0, 0.0))
+    }
+
+    /// A medium where light travels `mean_free_path` on average between interactions and
+    /// scatters rather than being absorbed with probability `albedo`
+    pub fn from_albedo(albedo: Color, mean_free_path: Color) -> Medium {
+        let extinction: Color = Color::new(1.0 / mean_free_path.x, 1.0 / mean_free_path.y, 1.0 / mean_free_path.z);
+        Medium::new(extinction - albedo * extinction, albedo * extinction)
+    }
+
+    pub fn get_absorption(&self) -> Color {
+        self.absorption
+    }
+
+    pub fn get_scattering(&self) -> Color {
+        self.scattering
+    }
+
+    pub fn get_extinction(&self) -> Color {
+        self.absorption + self.scattering
+    }
+
+    pub fn is_scattering(&self) -> bool {
+        self.scattering.max_component() > 0.0
+    }
+
+    /// The fraction of light left after travelling `distance` through the medium, following
+    /// the Beer-Lambert law
+    pub fn transmittance(&self, distance: f32) -> Color {
+        let extinction: Color = self.get_extinction();
+        Color::new((-extinction.x * distance).exp(), (-extinction.y * distance).exp(), (-extinction.z * distance).exp())
+    }
+}
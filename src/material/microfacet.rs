@@ -0,0 +1,167 @@
+use std::f32::consts::PI;
+use rand::random;
+use crate::hittable::HitRecord;
+use crate::material::Color;
+use crate::ray::Ray;
+use crate::vec::Vec3;
+
+/// An orthonormal frame around a normal, local coordinates have the normal along z
+#[derive(Debug, Clone, Copy)]
+pub struct Frame {
+    tangent: Vec3,
+    bitangent: Vec3,
+    normal: Vec3
+}
+
+impl Frame {
+    pub fn new(normal: Vec3) -> Frame {
+        let normal: Vec3 = normal / normal.get_length();
+        let (tangent, bitangent) = normal.orthonormal_basis();
+        Frame {
+            tangent,
+            bitangent,
+            normal
+        }
+    }
+
+    /// The frame around the side of the surface the ray arrived on and the direction back
+    /// along the ray in it
+    pub fn around_hit(ray: &Ray, hit: &HitRecord) -> (Frame, Vec3) {
+        let frame: Frame = Frame::new(hit.get_facing_normal(ray));
+        let outgoing: Vec3 = -*ray.get_direction() / ray.get_direction().get_length();
+        (frame, frame.local(&outgoing))
+    }
+
+    pub fn local(&self, vector: &Vec3) -> Vec3 {
+        Vec3::new(vector.dot(&self.tangent), vector.dot(&self.bitangent), vector.dot(&self.normal))
+    }
+
+    pub fn world(&self, vector: &Vec3) -> Vec3 {
+        self.tangent * vector.x + self.bitangent * vector.y + self.normal * vector.z
+    }
+}
+
+/// The GGX or Trowbridge-Reitz distribution of microfacet normals, with Smith's masking
+/// function, in the local frame of the surface
+#[derive(Debug, Clone, Copy)]
+pub struct Ggx {
+    alpha: f32
+}
+
+impl Ggx {
+    /// Roughness is squared into alpha so it looks roughly linear to artists
+    pub fn from_roughness(roughness: f32) -> Ggx {
+        Ggx {
+            alpha: (roughness * roughness).max(1e-4)
+        }
+    }
+
+    pub fn get_alpha(&self) -> f32 {
+        self.alpha
+    }
+
+    /// Whether the surface is smooth enough to be treated as a perfect mirror
+    pub fn is_smooth(&self) -> bool {
+        self.alpha < 1e-3
+    }
+
+    /// The density of microfacet normals
+    pub fn distribution(&self, normal: &Vec3) -> f32 {
+        if normal.z <= 0.0 {
+            return 0.0;
+        }
+        let alpha_2: f32 = self.alpha * self.alpha;
+        let term: f32 = (normal.x * normal.x + normal.y * normal.y) / alpha_2 + normal.z * normal.z;
+        1.0 / (PI * alpha_2 * term * term)
+    }
+
+    fn lambda(&self, direction: &Vec3) -> f32 {
+        let cos_2: f32 = direction.z * direction.z;
+        if cos_2 <= 0.0 {
+            return f32::INFINITY;
+        }
+        let tan_2: f32 = (direction.x * direction.x + direction.y * direction.y) / cos_2;
+        0.5 * (-1.0 + (1.0 + self.alpha * self.alpha * tan_2).sqrt())
+    }
+
+    /// The fraction of microfacets visible from a direction
+    pub fn masking(&self, direction: &Vec3) -> f32 {
+        1.0 / (1.0 + self.lambda(direction))
+    }
+
+    /// The fraction of microfacets visible from both directions, height correlated
+    pub fn masking_shadowing(&self, outgoing: &Vec3, incoming: &Vec3) -> f32 {
+        1.0 / (1.0 + self.lambda(outgoing) + self.lambda(incoming))
+    }
+
+    /// Picks a microfacet normal in proportion to how much of it `outgoing` sees, following
+    /// Heitz 2018, "Sampling the GGX Distribution of Visible Normals"
+    pub fn sample_visible_normal(&self, outgoing: &Vec3) -> Vec3 {
+        let stretched: Vec3 = Vec3::new(self.alpha * outgoing.x, self.alpha * outgoing.y, outgoing.z);
+        let stretched: Vec3 = stretched / stretched.get_length();
+        let length_2: f32 = stretched.x * stretched.x + stretched.y * stretched.y;
+        let t_1: Vec3 = if length_2 > 0.0 {
+            Vec3::new(-stretched.y, stretched.x, 0.0) / length_2.sqrt()
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let t_2: Vec3 = stretched.cross(&t_1);
+        let r: f32 = random::<f32>().sqrt();
+        let phi: f32 = 2.0 * PI * random::<f32>();
+        let p_1: f32 = r * phi.cos();
+        let s: f32 = 0.5 * (1.0 + stretched.z);
+        let p_2: f32 = (1.0 - s) * (1.0 - p_1 * p_1).max(0.0).sqrt() + s * r * phi.sin();
+        let normal: Vec3 = t_1 * p_1 + t_2 * p_2 + stretched * (1.0 - p_1 * p_1 - p_2 * p_2).max(0.0).sqrt();
+        let normal: Vec3 = Vec3::new(self.alpha * normal.x, self.alpha * normal.y, normal.z.max(1e-6));
+        normal / normal.get_length()
+    }
+
+    /// The density with which `sample_visible_normal` picks `normal`
+    pub fn visible_normal_pdf(&self, outgoing: &Vec3, normal: &Vec3) -> f32 {
+        if outgoing.z <= 0.0 {
+            return 0.0;
+        }
+        self.masking(outgoing) * outgoing.dot(normal).max(0.0) * self.distribution(normal) / outgoing.z
+    }
+}
+
+/// Reflectance of a conductor with complex index of refraction `eta + i k`, for each channel
+pub fn fresnel_conductor(cosine: f32, eta: &Color, k: &Color) -> Color {
+    let cosine: f32 = cosine.clamp(0.0, 1.0);
+    let channel = |eta: f32, k: f32| -> f32 {
+        let cos_2: f32 = cosine * cosine;
+        let sin_2: f32 = 1.0 - cos_2;
+        let eta_2: f32 = eta * eta;
+        let k_2: f32 = k * k;
+        let t_0: f32 = eta_2 - k_2 - sin_2;
+        let a_2_plus_b_2: f32 = (t_0 * t_0 + 4.0 * eta_2 * k_2).max(0.0).sqrt();
+        let t_1: f32 = a_2_plus_b_2 + cos_2;
+        let a: f32 = (0.5 * (a_2_plus_b_2 + t_0)).max(0.0).sqrt();
+        let t_2: f32 = 2.0 * cosine * a;
+        let s_polarized: f32 = (t_1 - t_2) / (t_1 + t_2);
+        let t_3: f32 = cos_2 * a_2_plus_b_2 + sin_2 * sin_2;
+        let t_4: f32 = t_2 * sin_2;
+        let p_polarized: f32 = s_polarized * (t_3 - t_4) / (t_3 + t_4);
+        0.5 * (s_polarized + p_polarized)
+    };
+    Color::new(channel(eta.x, k.x), channel(eta.y, k.y), channel(eta.z, k.z))
+}
+
+/// Reflectance of an interface between dielectrics where `eta` is the ratio of the index on the
+/// far side to the index on the side `cosine` is measured from
+pub fn fresnel_dielectric(cosine: f32, eta: f32) -> f32 {
+    let cosine: f32 = cosine.clamp(-1.0, 1.0);
+    let (cosine, eta) = if cosine < 0.0 {
+        (-cosine, 1.0 / eta)
+    } else {
+        (cosine, eta)
+    };
+    let sin_2_transmitted: f32 = (1.0 - cosine * cosine) / (eta * eta);
+    if sin_2_transmitted >= 1.0 {
+        return 1.0;
+    }
+    let cos_transmitted: f32 = (1.0 - sin_2_transmitted).sqrt();
+    let parallel: f32 = (eta * cosine - cos_transmitted) / (eta * cosine + cos_transmitted);
+    let perpendicular: f32 = (cosine - eta * cos_transmitted) / (cosine + eta * cos_transmitted);
+    0.5 * (parallel * parallel + perpendicular * perpendicular)
+}
@@ -0,0 +1,107 @@
+use std::sync::Arc;
+use rand::random;
+use crate::hittable::HitRecord;
+use crate::material::medium::Medium;
+use crate::material::{Color, Material, ScatterRecord};
+use crate::ray::Ray;
+use crate::texture::Texture;
+use crate::vec::Vec3;
+
+/// A blend of two materials, where the luminance of the weight texture gives how much of the
+/// second there is, so zero is all the first and one is all the second
+#[derive(Debug, Clone)]
+pub struct Mix {
+    first: Arc<dyn Material>,
+    second: Arc<dyn Material>,
+    weight: Arc<dyn Texture>
+}
+
+impl Mix {
+    pub fn new(first: Arc<dyn Material>, second: Arc<dyn Material>, weight: Arc<dyn Texture>) -> Mix {
+        Mix {
+            first,
+            second,
+            weight
+        }
+    }
+
+    fn weight(&self, hit: &HitRecord) -> f32 {
+        self.weight.value(hit.get_uv(), hit.get_point()).luminance().clamp(0.0, 1.0)
+    }
+}
+
+impl Material for Mix {
+    fn scatter(&self, ray: &Ray, hit: &HitRecord) -> Option<ScatterRecord> {
+        let weight: f32 = self.weight(hit);
+        let scatter: ScatterRecord = if random::<f32>() < weight {
+            self.second.scatter(ray, hit)?
+        } else {
+            self.first.scatter(ray, hit)?
+        };
+        // picking a specular scatter as often as its share already weighs it right
+        if scatter.is_specular() {
+            return Some(scatter);
+        }
+        let direction: &Vec3 = scatter.get_ray().get_direction();
+        let pdf: f32 = self.pdf(ray, hit, direction);
+        if pdf <= 0.0 {
+            return None;
+        }
+        let value: Color = self.eval(ray, hit, direction);
+        Some(scatter.with_attenuation(value / pdf))
+    }
+
+    fn eval(&self, ray: &Ray, hit: &HitRecord, direction: &Vec3) -> Color {
+        let weight: f32 = self.weight(hit);
+        self.first.eval(ray, hit, direction) * (1.0 - weight) + self.second.eval(ray, hit, direction) * weight
+    }
+
+    fn pdf(&self, ray: &Ray, hit: &HitRecord, direction: &Vec3) -> f32 {
+        let weight: f32 = self.weight(hit);
+        self.first.pdf(ray, hit, direction) * (1.0 - weight) + self.second.pdf(ray, hit, direction) * weight
+    }
+
+    fn has_non_specular(&self) -> bool {
+        self.first.has_non_specular() || self.second.has_non_specular()
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.first.is_emissive() || self.second.is_emissive()
+    }
+
+    /// Only one medium can be inside, the first's if both have one
+    fn medium(&self) -> Option<Medium> {
+        self.first.medium().or_else(|| self.second.medium())
+    }
+
+    fn emitted(&self, ray: &Ray, hit: &HitRecord, wavelengths: Option<&Vec3>) -> Color {
+        let weight: f32 = self.weight(hit);
+        self.first.emitted(ray, hit, wavelengths) * (1.0 - weight) + self.second.emitted(ray, hit, wavelengths) * weight
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::conductor::Conductor;
+    use crate::material::diffuse::Diffuse;
+    use crate::material::reflective::Reflective;
+    use crate::material::tests::{assert_pdf_matches_eval, assert_scatter_matches_eval, gray};
+
+    #[test]
+    fn blends_sample_what_they_evaluate() {
+        let diffuse: Arc<dyn Material> = Arc::new(Diffuse::new(gray(1.0), 0.8));
+        let (eta, k) = Conductor::preset("copper").unwrap();
+        let metal: Arc<dyn Material> = Arc::new(Conductor::new(eta, k, 0.4));
+        for weight in [0.0, 0.3, 1.0] {
+            let material: Arc<dyn Material> = Arc::new(Mix::new(diffuse.clone(), metal.clone(), gray(weight)));
+            assert_pdf_matches_eval(material.clone());
+            assert_scatter_matches_eval(material);
+        }
+        let mirror: Arc<dyn Material> = Arc::new(Reflective::new(gray(1.0), 0.0));
+        let material: Arc<dyn Material> = Arc::new(Mix::new(diffuse, mirror.clone(), gray(0.5)));
+        assert!(material.has_non_specular());
+        assert_pdf_matches_eval(material);
+        assert!(!Mix::new(mirror.clone(), mirror, gray(0.5)).has_non_specular());
+    }
+}
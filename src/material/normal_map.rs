@@ -0,0 +1,183 @@
+use std::sync::Arc;
+use crate::hittable::HitRecord;
+use crate::material::medium::Medium;
+use crate::material::{Color, Material, ScatterRecord};
+use crate::ray::Ray;
+use crate::texture::Texture;
+use crate::vec::Vec3;
+
+/// How a texture changes the shading normal
+#[derive(Debug, Clone, Copy)]
+pub enum Perturbation {
+    /// Colors are tangent space normals, red along u, green along v and blue out of the surface
+    Normal,
+    /// The brightness times the strength is a height above the surface
+    Height
+}
+
+/// Wraps another material, tilting the shading normal by a texture before handing the hit on
+pub struct NormalMap {
+    material: Arc<dyn Material>,
+    map: Arc<dyn Texture>,
+    perturbation: Perturbation,
+    strength: f32
+}
+
+impl NormalMap {
+    pub fn new(material: Arc<dyn Material>, map: Arc<dyn Texture>, perturbation: Perturbation, strength: f32) -> NormalMap {
+        NormalMap {
+            material,
+            map,
+            perturbation,
+            strength
+        }
+    }
+
+    /// The hit with its shading normal replaced by the mapped one
+    fn perturb<'a>(&self, hit: &HitRecord<'a>) -> HitRecord<'a> {
+        let normal: Vec3 = *hit.get_normal();
+        let (u_tangent, v_tangent) = hit.get_tangents().unwrap_or_else(|| normal.orthonormal_basis());
+        let perturbed: Vec3 = match self.perturbation {
+            Perturbation::Normal => {
+                let value: Color = self.map.value(hit.get_uv(), hit.get_point());
+                let local: Vec3 = Vec3::new(
+                    (value.x * 2.0 - 1.0) * self.strength,
+                    (value.y * 2.0 - 1.0) * self.strength,
+                    value.z * 2.0 - 1.0
+                );
+                // a frame around the shading normal with the tangent following u
+                let tangent: Vec3 = u_tangent - normal * normal.dot(&u_tangent);
+                let tangent: Vec3 = tangent / tangent.get_length();
+                let bitangent: Vec3 = normal.cross(&tangent);
+                let bitangent: Vec3 = if bitangent.dot(&v_tangent) < 0.0 {
+                    -bitangent
+                } else {
+                    bitangent
+                };
+                tangent * local.x + bitangent * local.y + normal * local.z
+            },
+            Perturbation::Height => {
+                // finite differences of the height along u and v, moving the point with them
+                // so textures driven by position see the same step
+                const STEP: f32 = 0.001;
+                let (u, v) = hit.get_uv();
+                let height = |uv: (f32, f32), point: Vec3| -> f32 {
+                    self.map.value(uv, &point).luminance() * self.strength
+                };
+                let base: f32 = height((u, v), *hit.get_point());
+                let du: f32 = (height((u + STEP, v), *hit.get_point() + u_tangent * STEP) - base) / STEP;
+                let dv: f32 = (height((u, v + STEP), *hit.get_point() + v_tangent * STEP) - base) / STEP;
+                let perturbed: Vec3 = (u_tangent + normal * du).cross(&(v_tangent + normal * dv));
+                if perturbed.dot(&normal) < 0.0 {
+                    -perturbed
+                } else {
+                    perturbed
+                }
+            }
+        };
+        let length: f32 = perturbed.get_length();
+        if length <= 0.0 || !length.is_finite() {
+            return hit.clone();
+        }
+        hit.clone().with_shading_normal(perturbed / length)
+    }
+}
+
+impl Material for NormalMap {
+    fn scatter(&self, ray: &Ray, hit: &HitRecord) -> Option<ScatterRecord> {
+        self.material.scatter(ray, &self.perturb(hit))
+    }
+
+    fn eval(&self, ray: &Ray, hit: &HitRecord, direction: &Vec3) -> Color {
+        self.material.eval(ray, &self.perturb(hit), direction)
+    }
+
+    fn pdf(&self, ray: &Ray, hit: &HitRecord, direction: &Vec3) -> f32 {
+        self.material.pdf(ray, &self.perturb(hit), direction)
+    }
+
+    fn has_non_specular(&self) -> bool {
+        self.material.has_non_specular()
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.material.is_emissive()
+    }
+
+    fn medium(&self) -> Option<Medium> {
+        self.material.medium()
+    }
+
+    fn emitted(&self, ray: &Ray, hit: &HitRecord, wavelengths: Option<&Vec3>) -> Color {
+        self.material.emitted(ray, hit, wavelengths)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::diffuse::Diffuse;
+    use crate::material::tests::assert_pdf_matches_eval;
+    use crate::texture::constant::Constant;
+
+    /// A grey whose brightness climbs with u
+    struct Ramp;
+
+    impl Texture for Ramp {
+        fn value(&self, uv: (f32, f32), _point: &Vec3) -> Color {
+            Color::new(uv.0, uv.0, uv.0)
+        }
+    }
+
+    fn mapped(map: Arc<dyn Texture>, perturbation: Perturbation, strength: f32) -> NormalMap {
+        let white: Arc<dyn Texture> = Arc::new(Constant::new(Color::new(1.0, 1.0, 1.0)));
+        NormalMap::new(Arc::new(Diffuse::new(white, 1.0)), map, perturbation, strength)
+    }
+
+    fn tilt(color: Color, strength: f32) -> NormalMap {
+        mapped(Arc::new(Constant::new(color)), Perturbation::Normal, strength)
+    }
+
+    /// The shading normal a map gives a floor facing up with the given tangents
+    fn perturbed_normal(map: &NormalMap, u_tangent: Vec3, v_tangent: Vec3) -> Vec3 {
+        let material: Arc<dyn Material> = Arc::new(Diffuse::new(Arc::new(Constant::new(Color::new(1.0, 1.0, 1.0))), 1.0));
+        let hit: HitRecord = HitRecord::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 1.0), 1.0, &material)
+            .with_uv(0.5, 0.5)
+            .with_tangents(u_tangent, v_tangent);
+        *map.perturb(&hit).get_normal()
+    }
+
+    fn assert_close(a: &Vec3, b: &Vec3) {
+        assert!((*a - *b).get_length() < 1e-3, "{:?} is not {:?}", a, b);
+    }
+
+    #[test]
+    fn normal_maps_tilt_along_the_tangents() {
+        let u: Vec3 = Vec3::new(2.0, 0.0, 0.0);
+        let v: Vec3 = Vec3::new(0.0, 1.0, 0.0);
+        assert_close(&perturbed_normal(&tilt(Color::new(0.5, 0.5, 1.0), 1.0), u, v), &Vec3::new(0.0, 0.0, 1.0));
+        // 30 degrees towards u and then towards v, whichever way round v runs
+        assert_close(&perturbed_normal(&tilt(Color::new(0.75, 0.5, 0.933), 1.0), u, v), &Vec3::new(0.5, 0.0, 0.866));
+        let towards_v: NormalMap = tilt(Color::new(0.5, 0.75, 0.933), 1.0);
+        assert_close(&perturbed_normal(&towards_v, u, v), &Vec3::new(0.0, 0.5, 0.866));
+        assert_close(&perturbed_normal(&towards_v, u, -v * 3.0), &Vec3::new(0.0, -0.5, 0.866));
+        // no strength leaves the tilt out
+        assert_close(&perturbed_normal(&tilt(Color::new(0.75, 0.5, 0.933), 0.0), u, v), &Vec3::new(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn height_maps_lean_away_from_rising_ground() {
+        let bump: NormalMap = mapped(Arc::new(Ramp), Perturbation::Height, 0.5);
+        // the height climbs 0.5 for each unit of u, which spans 2 units of distance
+        let expected: Vec3 = Vec3::new(-0.25, 0.0, 1.0);
+        assert_close(&perturbed_normal(&bump, Vec3::new(2.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0)), &(expected / expected.get_length()));
+        // tangents wound the other way round still give a normal on the side of the surface
+        assert!(perturbed_normal(&bump, Vec3::new(0.0, 2.0, 0.0), Vec3::new(1.0, 0.0, 0.0)).z > 0.0);
+    }
+
+    #[test]
+    fn mapped_materials_sample_what_they_evaluate() {
+        assert_pdf_matches_eval(Arc::new(tilt(Color::new(0.75, 0.4, 0.9), 1.0)));
+        assert_pdf_matches_eval(Arc::new(mapped(Arc::new(Ramp), Perturbation::Height, 2.0)));
+    }
+}
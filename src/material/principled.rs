@@ -0,0 +1,322 @@
+use std::f32::consts::PI;
+use std::sync::Arc;
+use rand::random;
+use crate::hittable::HitRecord;
+use crate::material::dielectric::Dielectric;
+use crate::material::microfacet::{fresnel_dielectric, Frame, Ggx};
+use crate::material::{Color, Material, ScatterRecord};
+use crate::ray::Ray;
+use crate::sampling::{cosine_hemisphere, cosine_hemisphere_pdf};
+use crate::texture::constant::Constant;
+use crate::texture::Texture;
+use crate::vec::Vec3;
+
+/// Rougher than this every lobe stays glossy, perfect mirrors are left to `Conductor` and
+/// `Dielectric`
+const MIN_ROUGHNESS: f32 = 0.05;
+
+/// One material for most surfaces in the style of the Disney principled BSDF, Burley 2012,
+/// "Physically Based Shading at Disney", blending a diffuse base with sheen, a GGX specular
+/// lobe, a clearcoat and rough glass. Every parameter but the index of refraction is a
+/// texture, scalars are read from its luminance
+#[derive(Debug, Clone)]
+pub struct Principled {
+    base_color: Arc<dyn Texture>,
+    /// Blends from a dielectric to a metal tinted by the base color
+    metallic: Arc<dyn Texture>,
+    roughness: Arc<dyn Texture>,
+    /// Reflectance of the dielectric specular lobe, 0.5 is 4 percent head on
+    specular: Arc<dyn Texture>,
+    /// Tints the dielectric specular lobe towards the base color
+    specular_tint: Arc<dyn Texture>,
+    /// A soft rim at grazing angles for cloth
+    sheen: Arc<dyn Texture>,
+    sheen_tint: Arc<dyn Texture>,
+    /// Strength of a second, clear specular layer
+    clearcoat: Arc<dyn Texture>,
+    clearcoat_roughness: Arc<dyn Texture>,
+    /// Blends from an opaque base to glass tinted by the base color
+    transmission: Arc<dyn Texture>,
+    refraction_index: f32
+}
+
+/// The parameters looked up at a hit and the lobes they make
+struct Lobes {
+    base_color: Color,
+    metallic: f32,
+    roughness: f32,
+    /// Head on reflectance of the specular lobe
+    specular_color: Color,
+    sheen_color: Color,
+    clearcoat: f32,
+    transmission: f32,
+    distribution: Ggx,
+    clearcoat_distribution: Ggx,
+    /// The ratio of the index on the far side to the index on the side of the ray
+    eta: f32
+}
+
+/// The Schlick Fresnel weight, going from 0 head on to 1 at grazing angles
+fn schlick_weight(cosine: f32) -> f32 {
+    (1.0 - cosine).clamp(0.0, 1.0).powi(5)
+}
+
+fn lerp(a: Color, b: Color, t: f32) -> Color {
+    a * (1.0 - t) + b * t
+}
+
+/// The density of reflecting off a microfacet picked from the visible normals of `distribution`
+fn reflection_pdf(distribution: &Ggx, outgoing: &Vec3, incoming: &Vec3) -> f32 {
+    let half: Vec3 = *outgoing + *incoming;
+    let half: Vec3 = half / half.get_length();
+    distribution.visible_normal_pdf(outgoing, &half) / (4.0 * outgoing.dot(&half))
+}
+
+/// Reflects `outgoing` off a microfacet picked from the visible normals of `distribution`
+fn sample_reflection(distribution: &Ggx, outgoing: &Vec3) -> Vec3 {
+    (-*outgoing).reflect(&distribution.sample_visible_normal(outgoing))
+}
+
+impl Principled {
+    /// A rough white plastic unless the other parameters are set
+    pub fn new(base_color: Arc<dyn Texture>) -> Principled {
+        let scalar = |value: f32| -> Arc<dyn Texture> { Arc::new(Constant::new(Color::new(value, value, value))) };
+        Principled {
+            base_color,
+            metallic: scalar(0.0),
+            roughness: scalar(0.5),
+            specular: scalar(0.5),
+            specular_tint: scalar(0.0),
+            sheen: scalar(0.0),
+            sheen_tint: scalar(0.5),
+            clearcoat: scalar(0.0),
+            clearcoat_roughness: scalar(0.1),
+            transmission: scalar(0.0),
+            refraction_index: 1.5
+        }
+    }
+
+    pub fn with_metallic(mut self, metallic: Arc<dyn Texture>) -> Self {
+        self.metallic = metallic;
+        self
+    }
+
+    pub fn with_roughness(mut self, roughness: Arc<dyn Texture>) -> Self {
+        self.roughness = roughness;
+        self
+    }
+
+    pub fn with_specular(mut self, specular: Arc<dyn Texture>) -> Self {
+        self.specular = specular;
+        self
+    }
+
+    pub fn with_specular_tint(mut self, specular_tint: Arc<dyn Texture>) -> Self {
+        self.specular_tint = specular_tint;
+        self
+    }
+
+    pub fn with_sheen(mut self, sheen: Arc<dyn Texture>) -> Self {
+        self.sheen = sheen;
+        self
+    }
+
+    pub fn with_sheen_tint(mut self, sheen_tint: Arc<dyn Texture>) -> Self {
+        self.sheen_tint = sheen_tint;
+        self
+    }
+
+    pub fn with_clearcoat(mut self, clearcoat: Arc<dyn Texture>) -> Self {
+        self.clearcoat = clearcoat;
+        self
+    }
+
+    pub fn with_clearcoat_roughness(mut self, clearcoat_roughness: Arc<dyn Texture>) -> Self {
+        self.clearcoat_roughness = clearcoat_roughness;
+        self
+    }
+
+    pub fn with_transmission(mut self, transmission: Arc<dyn Texture>) -> Self {
+        self.transmission = transmission;
+        self
+    }
+
+    pub fn with_refraction_index(mut self, refraction_index: f32) -> Self {
+        self.refraction_index = refraction_index;
+        self
+    }
+
+    /// `Frame::around_hit` along with the lobes at the hit
+    fn local(&self, ray: &Ray, hit: &HitRecord) -> (Frame, Vec3, Lobes) {
+        let (frame, outgoing) = Frame::around_hit(ray, hit);
+        let scalar = |texture: &Arc<dyn Texture>| -> f32 {
+            texture.value(hit.get_uv(), hit.get_point()).luminance().clamp(0.0, 1.0)
+        };
+        let base_color: Color = self.base_color.value(hit.get_uv(), hit.get_point());
+        let metallic: f32 = scalar(&self.metallic);
+        let roughness: f32 = scalar(&self.roughness).max(MIN_ROUGHNESS);
+        // the hue of the base color without its brightness
+        let white: Color = Color::new(1.0, 1.0, 1.0);
+        let luminance: f32 = base_color.luminance();
+        let tint: Color = if luminance > 0.0 { base_color / luminance } else { white };
+        let dielectric_specular: Color = lerp(white, tint, scalar(&self.specular_tint)) * (0.08 * scalar(&self.specular));
+        let lobes: Lobes = Lobes {
+            base_color,
+            metallic,
+            roughness,
+            specular_color: lerp(dielectric_specular, base_color, metallic),
+            sheen_color: lerp(white, tint, scalar(&self.sheen_tint)) * scalar(&self.sheen),
+            clearcoat: scalar(&self.clearcoat),
+            transmission: scalar(&self.transmission),
+            distribution: Ggx::from_roughness(roughness),
+            clearcoat_distribution: Ggx::from_roughness(scalar(&self.clearcoat_roughness).max(MIN_ROUGHNESS)),
+            eta: if hit.is_front_face(ray) {
+                self.refraction_index
+            } else {
+                1.0 / self.refraction_index
+            }
+        };
+        (frame, outgoing, lobes)
+    }
+}
+
+impl Lobes {
+    fn glass_fresnel(&self, cosine: f32) -> Color {
+        let fresnel: f32 = fresnel_dielectric(cosine, self.eta);
+        Color::new(fresnel, fresnel, fresnel)
+    }
+
+    /// The chances of sampling the diffuse, specular, clearcoat and glass lobes, roughly
+    /// following how much light each reflects
+    fn probabilities(&self, outgoing: &Vec3) -> Option<[f32; 4]> {
+        let fresnel: f32 = schlick_weight(outgoing.z);
+        let specular: Color = lerp(self.specular_color, Color::new(1.0, 1.0, 1.0), fresnel);
+        let weights: [f32; 4] = [
+            (1.0 - self.metallic) * (1.0 - self.transmission) * self.base_color.max_component().max(self.sheen_color.max_component()),
+            (1.0 - (1.0 - self.metallic) * self.transmission) * specular.luminance(),
+            0.25 * self.clearcoat * (0.04 + 0.96 * fresnel),
+            (1.0 - self.metallic) * self.transmission
+        ];
+        let total: f32 = weights.iter().sum();
+        if total <= 0.0 {
+            return None;
+        }
+        Some(weights.map(|weight| weight / total))
+    }
+
+    /// The BSDF times the cosine and the density of sampling it, summed over the lobes
+    fn eval_pdf(&self, outgoing: &Vec3, incoming: &Vec3) -> (Color, f32) {
+        let black: Color = Color::new(0.0, 0.0, 0.0);
+        if outgoing.z <= 0.0 || incoming.z == 0.0 {
+            return (black, 0.0);
+        }
+        let probabilities: [f32; 4] = match self.probabilities(outgoing) {
+            Some(probabilities) => probabilities,
+            None => return (black, 0.0)
+        };
+        let (glass, glass_pdf) = Dielectric::rough_eval_pdf(&self.distribution, &|cosine| self.glass_fresnel(cosine), outgoing, incoming, self.eta);
+        let glass_weight: f32 = (1.0 - self.metallic) * self.transmission;
+        if incoming.z < 0.0 {
+            // only the glass lets light through, tinted by the base color
+            return (self.base_color * glass * glass_weight, probabilities[3] * glass_pdf);
+        }
+        let half: Vec3 = *outgoing + *incoming;
+        let half: Vec3 = half / half.get_length();
+        let cosine: f32 = incoming.dot(&half);
+        // Burley's diffuse, darker at grazing angles for smooth surfaces and brighter for rough
+        let retro_reflection: f32 = 0.5 + 2.0 * self.roughness * cosine * cosine;
+        let diffuse: f32 = (1.0 + (retro_reflection - 1.0) * schlick_weight(incoming.z))
+            * (1.0 + (retro_reflection - 1.0) * schlick_weight(outgoing.z));
+        let diffuse: Color = (self.base_color * (diffuse / PI) + self.sheen_color * schlick_weight(cosine))
+            * ((1.0 - self.metallic) * (1.0 - self.transmission) * incoming.z);
+        let specular: Color = lerp(self.specular_color, Color::new(1.0, 1.0, 1.0), schlick_weight(cosine))
+            * ((1.0 - glass_weight) * self.distribution.distribution(&half)
+                * self.distribution.masking_shadowing(outgoing, incoming) / (4.0 * outgoing.z));
+        let clearcoat: f32 = 0.25 * self.clearcoat * (0.04 + 0.96 * schlick_weight(cosine))
+            * self.clearcoat_distribution.distribution(&half)
+            * self.clearcoat_distribution.masking_shadowing(outgoing, incoming) / (4.0 * outgoing.z);
+        let value: Color = diffuse + specular + Color::new(clearcoat, clearcoat, clearcoat) + glass * glass_weight;
+        let pdf: f32 = probabilities[0] * cosine_hemisphere_pdf(incoming.z)
+            + probabilities[1] * reflection_pdf(&self.distribution, outgoing, incoming)
+            + probabilities[2] * reflection_pdf(&self.clearcoat_distribution, outgoing, incoming)
+            + probabilities[3] * glass_pdf;
+        (value, pdf)
+    }
+}
+
+impl Material for Principled {
+    fn scatter(&self, ray: &Ray, hit: &HitRecord) -> Option<ScatterRecord> {
+        let (frame, outgoing, lobes) = self.local(ray, hit);
+        if outgoing.z <= 0.0 {
+            return None;
+        }
+        let probabilities: [f32; 4] = lobes.probabilities(&outgoing)?;
+        let choice: f32 = random::<f32>();
+        let incoming: Vec3 = if choice < probabilities[0] {
+            cosine_hemisphere()
+        } else if choice < probabilities[0] + probabilities[1] {
+            sample_reflection(&lobes.distribution, &outgoing)
+        } else if choice < probabilities[0] + probabilities[1] + probabilities[2] {
+            sample_reflection(&lobes.clearcoat_distribution, &outgoing)
+        } else {
+            Dielectric::sample_rough(&lobes.distribution, &|cosine| lobes.glass_fresnel(cosine), &outgoing, lobes.eta)?.0
+        };
+        // weigh by every lobe that could have picked the direction, not just the one that did
+        let (value, pdf) = lobes.eval_pdf(&outgoing, &incoming);
+        if pdf <= 0.0 {
+            return None;
+        }
+        Some(ScatterRecord::new(Ray::new(*hit.get_point(), frame.world(&incoming)), value / pdf))
+    }
+
+    fn eval(&self, ray: &Ray, hit: &HitRecord, direction: &Vec3) -> Color {
+        let (frame, outgoing, lobes) = self.local(ray, hit);
+        lobes.eval_pdf(&outgoing, &frame.local(&(*direction / direction.get_length()))).0
+    }
+
+    fn pdf(&self, ray: &Ray, hit: &HitRecord, direction: &Vec3) -> f32 {
+        let (frame, outgoing, lobes) = self.local(ray, hit);
+        lobes.eval_pdf(&outgoing, &frame.local(&(*direction / direction.get_length()))).1
+    }
+
+    fn has_non_specular(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::tests::{assert_pdf_matches_eval, assert_scatter_matches_eval};
+
+    fn scalar(value: f32) -> Arc<dyn Texture> {
+        Arc::new(Constant::new(Color::new(value, value, value)))
+    }
+
+    fn principled() -> Principled {
+        Principled::new(Arc::new(Constant::new(Color::new(0.8, 0.4, 0.2)))).with_roughness(scalar(0.4))
+    }
+
+    #[test]
+    fn every_lobe_samples_what_it_evaluates() {
+        let materials: [Principled; 5] = [
+            principled(),
+            principled().with_metallic(scalar(1.0)),
+            principled().with_sheen(scalar(1.0)).with_clearcoat(scalar(1.0)).with_clearcoat_roughness(scalar(0.2)),
+            principled().with_transmission(scalar(1.0)),
+            principled().with_metallic(scalar(0.5)).with_transmission(scalar(0.5)).with_specular_tint(scalar(1.0))
+        ];
+        for material in materials {
+            let material: Arc<dyn Material> = Arc::new(material);
+            assert!(material.has_non_specular());
+            assert_pdf_matches_eval(material.clone());
+            assert_scatter_matches_eval(material);
+        }
+    }
+
+    #[test]
+    fn the_smoothest_lobes_stay_glossy() {
+        let material: Arc<dyn Material> = Arc::new(principled().with_roughness(scalar(0.0)).with_metallic(scalar(1.0)));
+        assert_pdf_matches_eval(material);
+    }
+}
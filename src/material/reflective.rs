@@ -1,30 +1,51 @@
+use std::sync::Arc;
 use crate::hittable::HitRecord;
-use crate::material::{Color, Material};
+use crate::material::conductor::Conductor;
+use crate::material::thin_film::ThinFilm;
+use crate::material::{Color, Material, ScatterRecord};
 use crate::ray::Ray;
+use crate::sampling::unit_ball;
+use crate::texture::Texture;
 use crate::vec::Vec3;
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Clone)]
 pub struct Reflective {
-    albedo: Color,
-    fuzz: f32
+    albedo: Arc<dyn Texture>,
+    fuzz: f32,
+    film: Option<ThinFilm>
 }
 
 impl Reflective {
-    pub fn new(albedo: Color, fuzz: f32) -> Reflective {
+    pub fn new(albedo: Arc<dyn Texture>, fuzz: f32) -> Reflective {
         Reflective {
             albedo,
-            fuzz
+            fuzz,
+            film: None
         }
     }
+
+    /// Coats the metal in a thin film, with the albedo as the metal's reflectivity under it
+    pub fn with_thin_film(mut self, film: ThinFilm) -> Self {
+        self.film = Some(film);
+        self
+    }
 }
 
 impl Material for Reflective {
-    fn scatter(&self, ray: &Ray, hit: &HitRecord) -> Option<(Ray, Color)> {
-        let reflected: Vec3 = ray.get_direction().reflect(hit.get_normal());
-        let scattered: Ray = Ray::from_to(*hit.get_point(), *hit.get_point() + reflected + Vec3::random_in_unit_sphere() * self.fuzz);
-        let attenuation: Color = self.albedo;
-        if scattered.get_direction().dot(hit.get_normal()) > 0.0 {
-            Some((scattered, attenuation))
+    fn scatter(&self, ray: &Ray, hit: &HitRecord) -> Option<ScatterRecord> {
+        let normal: Vec3 = hit.get_facing_normal(ray);
+        let reflected: Vec3 = ray.get_direction().reflect(&normal);
+        let scattered: Ray = Ray::from_to(*hit.get_point(), *hit.get_point() + reflected + unit_ball() * self.fuzz);
+        let albedo: Color = self.albedo.value(hit.get_uv(), hit.get_point());
+        let attenuation: Color = match &self.film {
+            Some(film) => {
+                let cosine: f32 = -ray.get_direction().dot(&normal) / ray.get_direction().get_length();
+                film.reflectance(cosine, 1.0, Conductor::from_reflectivity(albedo, albedo))
+            },
+            None => albedo
+        };
+        if scattered.get_direction().dot(&normal) > 0.0 {
+            Some(ScatterRecord::specular(scattered, attenuation))
         } else {
             None
         }
@@ -0,0 +1,78 @@
+use std::sync::Arc;
+use crate::hittable::HitRecord;
+use crate::material::dielectric::Dielectric;
+use crate::material::medium::Medium;
+use crate::material::{Color, Material, ScatterRecord};
+use crate::ray::Ray;
+use crate::texture::constant::Constant;
+use crate::vec::Vec3;
+
+/// A translucent solid like wax, skin or milk, where light refracts through a dielectric
+/// boundary and takes a random walk through the scattering medium inside before leaving
+#[derive(Debug, Clone)]
+pub struct Subsurface {
+    boundary: Dielectric,
+    medium: Medium
+}
+
+impl Subsurface {
+    /// `albedo` is the chance of scattering rather than being absorbed at each interaction
+    /// and `mean_free_path` the average distance between them, for each channel
+    pub fn new(albedo: Color, mean_free_path: Color, refraction_index: f32, roughness: f32) -> Subsurface {
+        Subsurface {
+            boundary: Dielectric::new(refraction_index, Arc::new(Constant::new(Color::new(1.0, 1.0, 1.0))), roughness),
+            medium: Medium::from_albedo(albedo, mean_free_path)
+        }
+    }
+}
+
+impl Material for Subsurface {
+    fn scatter(&self, ray: &Ray, hit: &HitRecord) -> Option<ScatterRecord> {
+        self.boundary.scatter(ray, hit)
+    }
+
+    fn eval(&self, ray: &Ray, hit: &HitRecord, direction: &Vec3) -> Color {
+        self.boundary.eval(ray, hit, direction)
+    }
+
+    fn pdf(&self, ray: &Ray, hit: &HitRecord, direction: &Vec3) -> f32 {
+        self.boundary.pdf(ray, hit, direction)
+    }
+
+    fn has_non_specular(&self) -> bool {
+        self.boundary.has_non_specular()
+    }
+
+    fn medium(&self) -> Option<Medium> {
+        Some(self.medium)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::tests::assert_pdf_matches_eval;
+
+    #[test]
+    fn the_medium_inside_has_the_albedo_and_free_path_asked_for() {
+        let wax: Subsurface = Subsurface::new(Color::new(0.9, 0.5, 0.0), Color::new(0.5, 0.1, 2.0), 1.4, 0.0);
+        let medium: Medium = wax.medium().unwrap();
+        let extinction: Color = medium.get_extinction();
+        let scattering: Color = medium.get_scattering();
+        for (albedo, free_path, extinction, scattering) in [
+            (0.9, 0.5, extinction.x, scattering.x),
+            (0.5, 0.1, extinction.y, scattering.y),
+            (0.0, 2.0, extinction.z, scattering.z)
+        ] {
+            assert!((1.0 / extinction - free_path).abs() < 1e-5 && (scattering / extinction - albedo).abs() < 1e-5);
+        }
+        assert!(!wax.has_non_specular());
+    }
+
+    #[test]
+    fn rough_boundaries_sample_what_they_evaluate() {
+        let skin: Arc<dyn Material> = Arc::new(Subsurface::new(Color::new(0.8, 0.6, 0.5), Color::new(0.3, 0.2, 0.1), 1.4, 0.4));
+        assert!(skin.has_non_specular());
+        assert_pdf_matches_eval(skin);
+    }
+}
@@ -0,0 +1,178 @@
+use std::f32::consts::PI;
+use std::ops::{Add, Div, Mul, Sub};
+use crate::material::Color;
+use crate::spectrum::to_rgb;
+
+/// Just enough complex arithmetic for the amplitudes of waves in absorbing media
+#[derive(Debug, Clone, Copy)]
+struct Complex {
+    re: f32,
+    im: f32
+}
+
+impl Complex {
+    fn new(re: f32, im: f32) -> Complex {
+        Complex { re, im }
+    }
+
+    fn real(re: f32) -> Complex {
+        Complex::new(re, 0.0)
+    }
+
+    fn norm_squared(&self) -> f32 {
+        self.re * self.re + self.im * self.im
+    }
+
+    /// The root with a non negative real part
+    fn sqrt(&self) -> Complex {
+        let norm: f32 = self.norm_squared().sqrt();
+        let re: f32 = (0.5 * (norm + self.re)).max(0.0).sqrt();
+        let im: f32 = (0.5 * (norm - self.re)).max(0.0).sqrt();
+        Complex::new(re, if self.im < 0.0 { -im } else { im })
+    }
+
+    /// e to the power of i times self
+    fn exp_i(&self) -> Complex {
+        let magnitude: f32 = (-self.im).exp();
+        Complex::new(magnitude * self.re.cos(), magnitude * self.re.sin())
+    }
+}
+
+impl Add for Complex {
+    type Output = Complex;
+
+    fn add(self, other: Complex) -> Complex {
+        Complex::new(self.re + other.re, self.im + other.im)
+    }
+}
+
+impl Sub for Complex {
+    type Output = Complex;
+
+    fn sub(self, other: Complex) -> Complex {
+        Complex::new(self.re - other.re, self.im - other.im)
+    }
+}
+
+impl Mul for Complex {
+    type Output = Complex;
+
+    fn mul(self, other: Complex) -> Complex {
+        Complex::new(self.re * other.re - self.im * other.im, self.re * other.im + self.im * other.re)
+    }
+}
+
+impl Div for Complex {
+    type Output = Complex;
+
+    fn div(self, other: Complex) -> Complex {
+        let norm: f32 = other.norm_squared();
+        Complex::new(
+            (self.re * other.re + self.im * other.im) / norm,
+            (self.im * other.re - self.re * other.im) / norm
+        )
+    }
+}
+
+/// The s and p polarized amplitudes reflected at the interface from a medium to the next,
+/// given the cosines of the angles in each
+fn reflected_amplitudes(index: Complex, cosine: Complex, next_index: Complex, next_cosine: Complex) -> (Complex, Complex) {
+    let s: Complex = (index * cosine - next_index * next_cosine) / (index * cosine + next_index * next_cosine);
+    let p: Complex = (next_index * cosine - index * next_cosine) / (next_index * cosine + index * next_cosine);
+    (s, p)
+}
+
+/// A transparent film on a surface, like a soap bubble or an anti reflective coating, whose
+/// reflections off its top and bottom interfere so the reflectance changes with wavelength
+#[derive(Debug, Clone, Copy)]
+pub struct ThinFilm {
+    /// In nm
+    thickness: f32,
+    refraction_index: f32
+}
+
+impl ThinFilm {
+    pub fn new(thickness: f32, refraction_index: f32) -> ThinFilm {
+        ThinFilm {
+            thickness,
+            refraction_index
+        }
+    }
+
+    /// The reflectance at a wavelength in nm, coming from a medium with index `outer` at
+    /// an angle with cosine `cosine` onto the film over a substrate with complex index
+    /// `substrate.0 + i substrate.1`, summing every reflection inside the film following Airy
+    pub fn reflectance_at(&self, cosine: f32, wavelength: f32, outer: f32, substrate: (f32, f32)) -> f32 {
+        let cosine: f32 = cosine.clamp(0.0, 1.0);
+        let outer_index: Complex = Complex::real(outer);
+        let film_index: Complex = Complex::real(self.refraction_index);
+        let substrate_index: Complex = Complex::new(substrate.0, substrate.1);
+        // Snell's law keeps index times sine the same in every layer
+        let sin_2: Complex = Complex::real(outer * outer * (1.0 - cosine * cosine));
+        let layer_cosine = |index: Complex| -> Complex { (Complex::real(1.0) - sin_2 / (index * index)).sqrt() };
+        let outer_cosine: Complex = Complex::real(cosine);
+        let film_cosine: Complex = layer_cosine(film_index);
+        let substrate_cosine: Complex = layer_cosine(substrate_index);
+        let (top_s, top_p) = reflected_amplitudes(outer_index, outer_cosine, film_index, film_cosine);
+        let (bottom_s, bottom_p) = reflected_amplitudes(film_index, film_cosine, substrate_index, substrate_cosine);
+        // the phase picked up going down through the film and back up
+        let phase: Complex = Complex::real(4.0 * PI * self.thickness / wavelength) * film_index * film_cosine;
+        let delay: Complex = phase.exp_i();
+        let total = |top: Complex, bottom: Complex| -> f32 {
+            ((top + bottom * delay) / (Complex::real(1.0) + top * bottom * delay)).norm_squared()
+        };
+        (0.5 * (total(top_s, bottom_s) + total(top_p, bottom_p))).clamp(0.0, 1.0)
+    }
+
+    /// The reflectance over the visible spectrum as a color, where `substrate` gives the
+    /// complex index of the substrate for the red, green and blue channels as in
+    /// `reflectance_at`, taken to be at 650, 550 and 450 nm
+    pub fn reflectance(&self, cosine: f32, outer: f32, substrate: (Color, Color)) -> Color {
+        let (eta, k) = substrate;
+        to_rgb(|wavelength: f32| -> f32 {
+            // linear in between the channels and constant past them
+            let lerp = |channels: &Color| -> f32 {
+                let t: f32 = (wavelength - 450.0) / 100.0;
+                if t < 1.0 {
+                    channels.z + (channels.y - channels.z) * t.clamp(0.0, 1.0)
+                } else {
+                    channels.y + (channels.x - channels.y) * (t - 1.0).min(1.0)
+                }
+            };
+            self.reflectance_at(cosine, wavelength, outer, (lerp(&eta), lerp(&k)))
+        }).max(&Color::new(0.0, 0.0, 0.0)).min(&Color::new(1.0, 1.0, 1.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::microfacet::fresnel_dielectric;
+
+    #[test]
+    fn films_without_a_top_reflection_leave_the_substrate_alone() {
+        for cosine in [1.0, 0.7, 0.2] {
+            let fresnel: f32 = fresnel_dielectric(cosine, 1.5);
+            let empty: f32 = ThinFilm::new(0.0, 1.33).reflectance_at(cosine, 550.0, 1.0, (1.5, 0.0));
+            let matched: f32 = ThinFilm::new(300.0, 1.0).reflectance_at(cosine, 550.0, 1.0, (1.5, 0.0));
+            assert!((empty - fresnel).abs() < 1e-4, "{} through no film instead of {}", empty, fresnel);
+            assert!((matched - fresnel).abs() < 1e-4, "{} through a film matching the air instead of {}", matched, fresnel);
+        }
+    }
+
+    #[test]
+    fn quarter_wave_coatings_cancel_reflections() {
+        let index: f32 = 1.5_f32.sqrt();
+        let coating: ThinFilm = ThinFilm::new(550.0 / (4.0 * index), index);
+        assert!(coating.reflectance_at(1.0, 550.0, 1.0, (1.5, 0.0)) < 1e-4);
+        assert!(coating.reflectance_at(1.0, 450.0, 1.0, (1.5, 0.0)) > 1e-3);
+    }
+
+    #[test]
+    fn soap_films_are_colored() {
+        let bubble: Color = ThinFilm::new(400.0, 1.33).reflectance(0.9, 1.0, (Color::new(1.0, 1.0, 1.0), Color::new(0.0, 0.0, 0.0)));
+        let darkest: f32 = bubble.x.min(bubble.y).min(bubble.z);
+        assert!(darkest >= 0.0 && bubble.max_component() <= 1.0);
+        assert!(bubble.max_component() - darkest > 0.01);
+    }
+}
@@ -1,26 +1,35 @@
 use crate::vec::Vec3;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub struct Ray {
     origin: Vec3,
-    direction: Vec3
+    direction: Vec3,
+    /// The hero wavelength in nm of a spectral render
+    wavelength: Option<f32>
 }
 
 impl Ray {
     pub fn new(origin: Vec3, direction: Vec3) -> Ray {
         Ray {
             origin,
-            direction: direction.get_normalized()
+            direction: direction.get_normalized(),
+            wavelength: None
         }
     }
 
     pub fn from_to(from: Vec3, to: Vec3) -> Ray {
         Ray {
             origin: from,
-            direction: (to - from).get_normalized()
+            direction: (to - from).get_normalized(),
+            wavelength: None
         }
     }
 
+    pub fn with_wavelength(mut self, wavelength: f32) -> Self {
+        self.wavelength = Some(wavelength);
+        self
+    }
+
     pub fn at(&self, t: f32) -> Vec3 {
         self.origin + self.direction * t
     }
@@ -32,4 +41,8 @@ impl Ray {
     pub fn get_direction(&self) -> &Vec3 {
         &self.direction
     }
+
+    pub fn get_wavelength(&self) -> Option<f32> {
+        self.wavelength
+    }
 }
@@ -0,0 +1,95 @@
+use std::f32::consts::PI;
+use rand::random;
+use crate::vec::Vec3;
+
+// Directions are returned in a local frame with z up, `Frame` or `orthonormal_basis` carry
+// them into the world
+
+/// A direction picked uniformly over the whole sphere
+pub fn uniform_sphere() -> Vec3 {
+    uniform_cone(-1.0)
+}
+
+pub fn uniform_sphere_pdf() -> f32 {
+    1.0 / (4.0 * PI)
+}
+
+/// A direction picked uniformly over the hemisphere around z
+pub fn uniform_hemisphere() -> Vec3 {
+    uniform_cone(0.0)
+}
+
+pub fn uniform_hemisphere_pdf() -> f32 {
+    1.0 / (2.0 * PI)
+}
+
+/// A direction picked uniformly within the cone around z whose half angle has cosine `cos_max`
+pub fn uniform_cone(cos_max: f32) -> Vec3 {
+    let z: f32 = 1.0 - random::<f32>() * (1.0 - cos_max);
+    let r: f32 = (1.0 - z * z).max(0.0).sqrt();
+    let phi: f32 = 2.0 * PI * random::<f32>();
+    Vec3::new(r * phi.cos(), r * phi.sin(), z)
+}
+
+pub fn uniform_cone_pdf(cos_max: f32) -> f32 {
+    1.0 / (2.0 * PI * (1.0 - cos_max))
+}
+
+/// A direction over the hemisphere around z picked in proportion to its cosine with z, by
+/// lifting a uniform point on the unit disk onto the hemisphere
+pub fn cosine_hemisphere() -> Vec3 {
+    let r: f32 = random::<f32>().sqrt();
+    let phi: f32 = 2.0 * PI * random::<f32>();
+    let x: f32 = r * phi.cos();
+    let y: f32 = r * phi.sin();
+    Vec3::new(x, y, (1.0 - x * x - y * y).max(0.0).sqrt())
+}
+
+pub fn cosine_hemisphere_pdf(cosine: f32) -> f32 {
+    cosine.max(0.0) / PI
+}
+
+/// A point picked uniformly inside the unit ball
+pub fn unit_ball() -> Vec3 {
+    uniform_sphere() * random::<f32>().cbrt()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// The mean of Monte Carlo estimates and its standard error, as narrow lobes and small
+    /// lights make some estimates noisy enough that checks have to allow for it
+    pub(crate) fn mean_and_error(estimates: Vec<f32>) -> (f32, f32) {
+        let count: f32 = estimates.len() as f32;
+        let mean: f32 = estimates.iter().sum::<f32>() / count;
+        let variance: f32 = estimates.iter().map(|estimate| (estimate - mean) * (estimate - mean)).sum::<f32>() / (count - 1.0);
+        (mean, (variance / count).sqrt())
+    }
+
+    #[test]
+    fn directions_stay_in_their_domain() {
+        for _ in 0..10_000 {
+            for direction in [uniform_sphere(), uniform_hemisphere(), uniform_cone(0.9), cosine_hemisphere()] {
+                assert!((direction.get_length() - 1.0).abs() < 1e-4);
+            }
+            assert!(uniform_hemisphere().z >= 0.0);
+            assert!(uniform_cone(0.9).z >= 0.9 - 1e-6);
+            assert!(cosine_hemisphere().z >= 0.0);
+            assert!(unit_ball().get_length() <= 1.0 + 1e-6);
+        }
+    }
+
+    #[test]
+    fn directions_follow_their_density() {
+        // the average cosine with z is the integral of the cosine times the density
+        let average = |sample: fn() -> Vec3| -> f32 { mean_and_error((0..100_000).map(|_| sample().z).collect()).0 };
+        assert!((average(uniform_sphere)).abs() < 0.01);
+        assert!((average(uniform_hemisphere) - 0.5).abs() < 0.01);
+        assert!((average(cosine_hemisphere) - 2.0 / 3.0).abs() < 0.01);
+        // the densities integrate to one over their domains
+        assert!((uniform_sphere_pdf() * 4.0 * PI - 1.0).abs() < 1e-6);
+        assert!((uniform_hemisphere_pdf() * 2.0 * PI - 1.0).abs() < 1e-6);
+        assert!((uniform_cone_pdf(0.0) - uniform_hemisphere_pdf()).abs() < 1e-6);
+    }
+}
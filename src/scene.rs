@@ -1,25 +1,105 @@
 use std::cmp::min_by;
+use std::sync::Arc;
+use log::{info, warn};
+use crate::background::Background;
+use crate::background::gradient::Gradient;
+use crate::bvh::aabb::Aabb;
+use crate::bvh::Bvh;
+use crate::light::area::AreaLight;
+use crate::light::Light;
+use crate::material::Color;
 use crate::ray::Ray;
 use crate::vec::Vec3;
 pub(crate) use crate::hittable::{HitRecord, Hittable};
 
 pub struct Scene {
-    objects: Vec<Box<dyn Hittable>>
+    objects: Vec<Arc<dyn Hittable>>,
+    bvh: Option<Bvh>,
+    /// Objects without bounds, these are tested against every ray
+    unbounded: Vec<usize>,
+    lights: Vec<Arc<dyn Light>>,
+    background: Arc<dyn Background>
 }
 
 impl Scene {
     pub fn new() -> Scene {
         Scene {
-            objects: Vec::new()
+            objects: Vec::new(),
+            bvh: None,
+            unbounded: Vec::new(),
+            lights: Vec::new(),
+            background: Arc::new(Gradient::default())
         }
     }
 
+    /// Adds an object to the scene, emissive objects that can be sampled are also registered
+    /// as area lights, others are only found by scattered rays
     pub fn add_object(&mut self, object: Box<dyn Hittable>) {
+        self.bvh = None;
+        let object: Arc<dyn Hittable> = Arc::from(object);
+        if object.is_emissive() {
+            if object.is_sampleable() {
+                self.lights.push(Arc::new(AreaLight::new(object.clone())));
+            } else {
+                warn!("An emissive object can't be sampled so it is left out of light sampling");
+            }
+        }
         self.objects.push(object);
     }
 
     pub fn add_objects(&mut self, objects: Vec<Box<dyn Hittable>>) {
-        self.objects.extend(objects);
+        for object in objects {
+            self.add_object(object);
+        }
+    }
+
+    pub fn add_light(&mut self, light: Arc<dyn Light>) {
+        self.lights.push(light);
+    }
+
+    pub fn get_lights(&self) -> &[Arc<dyn Light>] {
+        &self.lights
+    }
+
+    /// The solid angle density of picking a light uniformly and then sampling `direction` from it
+    pub fn light_pdf(&self, point: &Vec3, direction: &Vec3) -> f32 {
+        if self.lights.is_empty() {
+            return 0.0;
+        }
+        let total: f32 = self.lights
+            .iter()
+            .filter(|light| !light.is_delta())
+            .map(|light| light.pdf(point, direction))
+            .sum();
+        total / self.lights.len() as f32
+    }
+
+    /// Builds the bounding volume hierarchy over every bounded object, until this is
+    /// called `hit` tests every object in turn
+    pub fn build_bvh(&mut self) {
+        let mut bounds: Vec<(usize, Aabb)> = Vec::new();
+        self.unbounded.clear();
+        for (i, object) in self.objects.iter().enumerate() {
+            match object.bounding_box() {
+                Some(bounding_box) => bounds.push((i, bounding_box)),
+                None => self.unbounded.push(i)
+            }
+        }
+        let bvh: Bvh = Bvh::build(bounds);
+        info!("Built scene BVH with {} nodes, {} objects are unbounded", bvh.get_node_count(), self.unbounded.len());
+        self.bvh = Some(bvh);
+    }
+
+    /// Sets what rays leaving the scene see, a background that should also light the scene
+    /// like an environment map needs adding with `add_light` as well
+    pub fn set_background(&mut self, background: Arc<dyn Background>) {
+        self.background = background;
+    }
+
+    /// The light arriving from a ray that leaves the scene without hitting anything, in RGB or
+    /// at the `wavelengths` of a spectral render
+    pub fn background(&self, ray: &Ray, wavelengths: Option<&Vec3>) -> Color {
+        self.background.color(ray.get_direction(), wavelengths)
     }
 
     pub fn get_object_count(&self) -> usize {
@@ -33,16 +113,85 @@ impl Scene {
 }
 
 impl Hittable for Scene {
-    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
         let mut closest_hit: Option<HitRecord> = None;
         let mut closest_t: f32 = t_max;
-        for object in &self.objects {
-            if let Some(hit) = object.hit(ray, t_min, closest_t) {
-                closest_t = hit.get_t();
-                closest_hit = Some(hit);
+        match &self.bvh {
+            Some(bvh) => {
+                for i in &self.unbounded {
+                    if let Some(hit) = self.objects[*i].hit(ray, t_min, closest_t) {
+                        closest_t = hit.get_t();
+                        closest_hit = Some(hit);
+                    }
+                }
+                if let Some(hit) = bvh.hit(ray, t_min, closest_t, |i, t_min, t_max| {
+                    self.objects[i].hit(ray, t_min, t_max)
+                }) {
+                    closest_hit = Some(hit);
+                }
+            },
+            None => {
+                for object in &self.objects {
+                    if let Some(hit) = object.hit(ray, t_min, closest_t) {
+                        closest_t = hit.get_t();
+                        closest_hit = Some(hit);
+                    }
+                }
             }
         }
         closest_hit
     }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.objects
+            .iter()
+            .map(|object| object.bounding_box())
+            .reduce(|acc, i| Some(acc?.union(&i?)))
+            .flatten()
+    }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::plane::Plane;
+    use crate::hittable::sphere::Sphere;
+    use crate::material::tests::lamp;
+
+    #[test]
+    fn only_sampleable_emitters_become_lights() {
+        let mut scene: Scene = Scene::new();
+        scene.add_object(Box::new(Plane::new(Vec3::new(0.0, 0.0, 1.0), Vec3::new(0.0, 0.0, 0.0), lamp())));
+        assert!(scene.get_lights().is_empty());
+        scene.add_object(Box::new(Sphere::new(Vec3::new(0.0, 0.0, 2.0), 0.5, lamp())));
+        assert_eq!(scene.get_lights().len(), 1);
+        let sample = scene.get_lights()[0].sample(&Vec3::new(0.0, 0.0, 0.0), None).unwrap();
+        assert!(sample.get_pdf() > 0.0);
+    }
+
+    #[test]
+    fn the_bvh_finds_what_testing_every_object_finds() {
+        let mut scene: Scene = Scene::new();
+        scene.add_object(Box::new(Plane::new(Vec3::new(0.0, 0.0, 1.0), Vec3::new(0.0, 0.0, -2.0), lamp())));
+        for i in 0..50 {
+            let angle: f32 = i as f32 * 0.7;
+            scene.add_object(Box::new(Sphere::new(Vec3::new(angle.cos() * 3.0, angle.sin() * 3.0, (i % 5) as f32 - 1.0), 0.4, lamp())));
+        }
+        let rays: Vec<Ray> = (0..500)
+            .map(|i| {
+                let angle: f32 = i as f32 * 0.37;
+                Ray::new(Vec3::new(0.0, 0.0, 0.5), Vec3::new(angle.cos(), angle.sin(), (i % 7) as f32 * 0.2 - 0.9))
+            })
+            .collect();
+        // the distance to each hit and the height it was at
+        let hits = |scene: &Scene| -> Vec<Option<(f32, f32)>> {
+            rays.iter().map(|ray| scene.hit(ray, 0.001, f32::INFINITY).map(|hit| (hit.get_t(), hit.get_point().z))).collect()
+        };
+        let unsorted: Vec<Option<(f32, f32)>> = hits(&scene);
+        scene.build_bvh();
+        assert_eq!(hits(&scene), unsorted);
+        // both the spheres in the hierarchy and the plane outside it get hit
+        assert!(unsorted.iter().flatten().any(|(_, z)| *z > -1.9));
+        assert!(unsorted.iter().flatten().any(|(_, z)| *z < -1.9));
+    }
+}
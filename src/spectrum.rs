@@ -0,0 +1,288 @@
+use std::sync::OnceLock;
+use rand::random;
+use crate::material::Color;
+use crate::vec::Vec3;
+
+/// The visible range spectra are integrated over, in nm
+pub const WAVELENGTH_MIN: f32 = 380.0;
+pub const WAVELENGTH_MAX: f32 = 720.0;
+const WAVELENGTH_SAMPLES: usize = 35;
+/// hc / k from Planck's law, in nm K
+const SECOND_RADIATION_CONSTANT: f64 = 1.438_777e7;
+/// Where blackbody spectra are measured relative to, near the peak of the eye's sensitivity
+const REFERENCE_WAVELENGTH: f64 = 555.0;
+/// The temperature that comes out with a luminance of one when blackbodies aren't normalized
+const REFERENCE_TEMPERATURE: f32 = 6500.0;
+
+/// The CIE 1931 color matching functions at a wavelength in nm, from the multi lobe fit of
+/// Wyman, Sloan and Shirley 2013, "Simple Analytic Approximations to the CIE XYZ Color Matching
+/// Functions"
+pub fn cie_xyz(wavelength: f32) -> Vec3 {
+    let lobe = |mean: f32, below: f32, above: f32| -> f32 {
+        let t: f32 = (wavelength - mean) / if wavelength < mean { below } else { above };
+        (-0.5 * t * t).exp()
+    };
+    Vec3::new(
+        1.056 * lobe(599.8, 37.9, 31.0) + 0.362 * lobe(442.0, 16.0, 26.7) - 0.065 * lobe(501.1, 20.4, 26.2),
+        0.821 * lobe(568.8, 46.9, 40.5) + 0.286 * lobe(530.9, 16.3, 31.1),
+        1.217 * lobe(437.0, 11.8, 36.0) + 0.681 * lobe(459.0, 26.0, 13.8)
+    )
+}
+
+/// Converts CIE XYZ to linear sRGB, which can be negative for colors outside its gamut
+pub fn xyz_to_rgb(xyz: &Vec3) -> Color {
+    Color::new(
+        3.2406 * xyz.x - 1.5372 * xyz.y - 0.4986 * xyz.z,
+        -0.9689 * xyz.x + 1.8758 * xyz.y + 0.0415 * xyz.z,
+        0.0557 * xyz.x - 0.2040 * xyz.y + 1.0570 * xyz.z
+    )
+}
+
+/// Integrates a spectrum given as a function of wavelength in nm against the color matching
+/// functions, giving linear RGB
+fn integrate(spectrum: &dyn Fn(f32) -> f32) -> Color {
+    let step: f32 = (WAVELENGTH_MAX - WAVELENGTH_MIN) / WAVELENGTH_SAMPLES as f32;
+    let mut xyz: Vec3 = Vec3::new(0.0, 0.0, 0.0);
+    for i in 0..WAVELENGTH_SAMPLES {
+        let wavelength: f32 = WAVELENGTH_MIN + (i as f32 + 0.5) * step;
+        xyz += cie_xyz(wavelength) * spectrum(wavelength);
+    }
+    xyz_to_rgb(&(xyz * step))
+}
+
+/// Divides out the color of a spectrum that is one everywhere so it comes out white
+fn white_balance(rgb: &Color) -> Color {
+    static WHITE: OnceLock<Color> = OnceLock::new();
+    let white: &Color = WHITE.get_or_init(|| integrate(&|_| 1.0));
+    Color::new(rgb.x / white.x, rgb.y / white.y, rgb.z / white.z)
+}
+
+/// The linear RGB color of a spectrum given as a function of wavelength in nm, scaled so a
+/// spectrum that is one everywhere comes out white
+pub fn to_rgb(spectrum: impl Fn(f32) -> f32) -> Color {
+    white_balance(&integrate(&spectrum))
+}
+
+/// Planck's law without its constant factor, for a wavelength in nm and a temperature in K
+fn planck(wavelength: f64, temperature: f64) -> f64 {
+    1.0 / (wavelength.powi(5) * (SECOND_RADIATION_CONSTANT / (wavelength * temperature)).exp_m1())
+}
+
+/// The light given off by an ideal blackbody at some temperature, with its RGB color worked
+/// out up front through the same color matching as spectral renders
+#[derive(Debug, Clone, Copy)]
+pub struct Blackbody {
+    /// In K
+    temperature: f32,
+    /// What the spectrum relative to its value at `REFERENCE_WAVELENGTH` is multiplied by
+    scale: f32,
+    color: Color
+}
+
+impl Blackbody {
+    /// A blackbody scaled to a luminance of one, so the temperature only changes the hue
+    pub fn new(temperature: f32) -> Blackbody {
+        let luminance: f32 = Blackbody::with_scale(temperature, 1.0).color.luminance();
+        Blackbody::with_scale(temperature, if luminance > 0.0 { 1.0 / luminance } else { 0.0 })
+    }
+
+    /// A blackbody that keeps how much brighter hotter bodies are, scaled so one at 6500 K has
+    /// a luminance of one
+    pub fn unnormalized(temperature: f32) -> Blackbody {
+        let reference: Blackbody = Blackbody::new(REFERENCE_TEMPERATURE);
+        let brightness: f64 = planck(REFERENCE_WAVELENGTH, temperature as f64) / planck(REFERENCE_WAVELENGTH, REFERENCE_TEMPERATURE as f64);
+        Blackbody::with_scale(temperature, reference.scale * brightness as f32)
+    }
+
+    fn with_scale(temperature: f32, scale: f32) -> Blackbody {
+        let mut blackbody: Blackbody = Blackbody {
+            temperature,
+            scale,
+            color: Color::new(0.0, 0.0, 0.0)
+        };
+        // cool bodies are redder than sRGB can show, so what falls outside is cut off
+        blackbody.color = to_rgb(|wavelength: f32| -> f32 { blackbody.radiance(wavelength) }).max(&Color::new(0.0, 0.0, 0.0));
+        blackbody
+    }
+
+    pub fn get_temperature(&self) -> f32 {
+        self.temperature
+    }
+
+    /// The radiance at a wavelength in nm
+    pub fn radiance(&self, wavelength: f32) -> f32 {
+        let temperature: f64 = self.temperature as f64;
+        let relative: f64 = planck(wavelength as f64, temperature) / planck(REFERENCE_WAVELENGTH, temperature);
+        if relative.is_finite() { relative as f32 * self.scale } else { 0.0 }
+    }
+
+    pub fn get_color(&self) -> Color {
+        self.color
+    }
+
+    /// The radiance at each of the wavelengths of a spectral render
+    pub fn at(&self, wavelengths: &Vec3) -> Vec3 {
+        Vec3::new(self.radiance(wavelengths.x), self.radiance(wavelengths.y), self.radiance(wavelengths.z))
+    }
+}
+
+/// Light of an RGB color times the color of a blackbody if there is one, so the color acts as
+/// a filter over the blackbody, in RGB or as its values at the `wavelengths` of a spectral
+/// render, where the blackbody gives its exact spectrum
+pub fn emission(color: &Color, blackbody: Option<&Blackbody>, wavelengths: Option<&Vec3>) -> Color {
+    match (wavelengths, blackbody) {
+        (Some(wavelengths), Some(blackbody)) => upsample(color, wavelengths) * blackbody.at(wavelengths),
+        (Some(wavelengths), None) => upsample(color, wavelengths),
+        (None, Some(blackbody)) => *color * blackbody.get_color(),
+        (None, None) => *color
+    }
+}
+
+/// The color of a light and the blackbody it may be tinted with, given off following `emission`
+#[derive(Debug, Clone, Copy)]
+pub struct Emission {
+    color: Color,
+    blackbody: Option<Blackbody>
+}
+
+impl Emission {
+    pub fn new(color: Color) -> Emission {
+        Emission {
+            color,
+            blackbody: None
+        }
+    }
+
+    pub fn with_blackbody(mut self, blackbody: Blackbody) -> Self {
+        self.blackbody = Some(blackbody);
+        self
+    }
+
+    /// The light in RGB or at the `wavelengths` of a spectral render
+    pub fn at(&self, wavelengths: Option<&Vec3>) -> Color {
+        emission(&self.color, self.blackbody.as_ref(), wavelengths)
+    }
+}
+
+/// Three wavelengths in nm spread evenly over the visible range from a random hero wavelength,
+/// following Wilkie et al. 2014, "Hero Wavelength Spectral Sampling"
+pub fn sample_wavelengths() -> Vec3 {
+    let range: f32 = WAVELENGTH_MAX - WAVELENGTH_MIN;
+    let hero: f32 = random::<f32>() * range;
+    let rotated = |i: f32| -> f32 { WAVELENGTH_MIN + (hero + i * range / 3.0) % range };
+    Vec3::new(rotated(0.0), rotated(1.0), rotated(2.0))
+}
+
+/// How much of the red, green and blue channels make up a spectrum at a wavelength in nm,
+/// smooth steps from blue to green to red that always add up to one, so white becomes a flat
+/// spectrum and reflectances stay below one
+fn rgb_basis(wavelength: f32) -> Color {
+    let step = |center: f32| -> f32 {
+        let t: f32 = ((wavelength - center) / 40.0 + 0.5).clamp(0.0, 1.0);
+        t * t * (3.0 - 2.0 * t)
+    };
+    let red: f32 = step(590.0);
+    let blue: f32 = 1.0 - step(485.0);
+    Color::new(red, 1.0 - red - blue, blue)
+}
+
+/// The values at `wavelengths` of a smooth spectrum with roughly the given RGB color
+pub fn upsample(color: &Color, wavelengths: &Vec3) -> Vec3 {
+    Vec3::new(
+        rgb_basis(wavelengths.x).dot(color),
+        rgb_basis(wavelengths.y).dot(color),
+        rgb_basis(wavelengths.z).dot(color)
+    )
+}
+
+/// The linear RGB estimate from a spectrum's `values` at `wavelengths` picked by
+/// `sample_wavelengths`, scaled like `to_rgb`
+pub fn samples_to_rgb(wavelengths: &Vec3, values: &Vec3) -> Color {
+    let xyz: Vec3 = cie_xyz(wavelengths.x) * values.x + cie_xyz(wavelengths.y) * values.y + cie_xyz(wavelengths.z) * values.z;
+    // each wavelength was picked with density one over the range
+    white_balance(&xyz_to_rgb(&(xyz * ((WAVELENGTH_MAX - WAVELENGTH_MIN) / 3.0))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(color: Color, expected: Color, tolerance: f32) {
+        for (value, target) in [(color.x, expected.x), (color.y, expected.y), (color.z, expected.z)] {
+            assert!((value - target).abs() <= tolerance, "{:?} instead of {:?}", (color.x, color.y, color.z), (expected.x, expected.y, expected.z));
+        }
+    }
+
+    /// The average RGB estimate of a spectrum over many random sets of wavelengths
+    fn estimate(spectrum: impl Fn(&Vec3) -> Vec3) -> Color {
+        let samples: u32 = 100_000;
+        let total: Color = (0..samples).fold(Color::new(0.0, 0.0, 0.0), |total, _| {
+            let wavelengths: Vec3 = sample_wavelengths();
+            total + samples_to_rgb(&wavelengths, &spectrum(&wavelengths))
+        });
+        total / samples as f32
+    }
+
+    #[test]
+    fn flat_spectra_are_white() {
+        let white: Color = Color::new(1.0, 1.0, 1.0);
+        assert_close(to_rgb(|_| 1.0), white, 1e-5);
+        assert_close(estimate(|_| white), white, 0.02);
+        for wavelength in [380.0, 480.0, 550.0, 600.0, 719.0] {
+            assert!((rgb_basis(wavelength).dot(&white) - 1.0).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn upsampled_colors_come_back_roughly_the_same() {
+        for color in [Color::new(0.8, 0.2, 0.1), Color::new(0.1, 0.6, 0.2), Color::new(0.2, 0.3, 0.9)] {
+            let rgb: Color = estimate(|wavelengths| upsample(&color, wavelengths));
+            assert_close(rgb, color, 0.06);
+            // the strongest channel stays the strongest
+            let strongest = |color: &Color| -> usize {
+                (0..3).max_by(|a, b| color.get_axis(*a).total_cmp(&color.get_axis(*b))).unwrap()
+            };
+            assert_eq!(strongest(&rgb), strongest(&color));
+        }
+    }
+
+    #[test]
+    fn wavelengths_are_spread_over_the_visible_range() {
+        let range: f32 = WAVELENGTH_MAX - WAVELENGTH_MIN;
+        for _ in 0..1000 {
+            let wavelengths: Vec3 = sample_wavelengths();
+            for wavelength in [wavelengths.x, wavelengths.y, wavelengths.z] {
+                assert!((WAVELENGTH_MIN..WAVELENGTH_MAX).contains(&wavelength));
+            }
+            let gap: f32 = (wavelengths.y - wavelengths.x).rem_euclid(range);
+            assert!((gap - range / 3.0).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn blackbodies_shift_from_red_to_blue_at_a_luminance_of_one() {
+        let candle: Color = Blackbody::new(1900.0).get_color();
+        let sky: Color = Blackbody::new(12000.0).get_color();
+        assert!(candle.x > candle.y && candle.y > candle.z);
+        assert!(sky.z > sky.x);
+        for color in [candle, sky, Blackbody::new(6500.0).get_color()] {
+            assert!((color.luminance() - 1.0).abs() < 1e-3);
+        }
+        let reference: f32 = Blackbody::unnormalized(6500.0).get_color().luminance();
+        assert!((reference - 1.0).abs() < 1e-3);
+        assert!(Blackbody::unnormalized(3000.0).get_color().luminance() < 0.1);
+    }
+
+    #[test]
+    fn spectral_blackbodies_average_to_their_color() {
+        let blackbody: Blackbody = Blackbody::new(3000.0);
+        assert_close(estimate(|wavelengths| blackbody.at(wavelengths)), blackbody.get_color(), 0.03);
+        // a tint on top is upsampled and multiplied in
+        let tint: Color = Color::new(0.5, 1.0, 1.0);
+        let rgb: Color = emission(&tint, Some(&blackbody), None);
+        assert_close(rgb, blackbody.get_color() * tint, 1e-6);
+        let wavelengths: Vec3 = Vec3::new(650.0, 550.0, 450.0);
+        let spectral: Vec3 = emission(&tint, Some(&blackbody), Some(&wavelengths));
+        assert_close(spectral, upsample(&tint, &wavelengths) * blackbody.at(&wavelengths), 1e-6);
+    }
+}
@@ -0,0 +1,48 @@
+use std::fmt::Debug;
+use crate::material::Color;
+use crate::vec::Vec3;
+
+pub mod constant;
+pub mod image;
+pub mod checker;
+pub mod noise;
+pub mod marble;
+pub mod wood;
+pub mod voronoi;
+
+/// A color that varies over a surface, looked up by texture coordinates or by position
+pub trait Texture: Send + Sync {
+    fn value(&self, uv: (f32, f32), point: &Vec3) -> Color;
+}
+
+impl Debug for dyn Texture {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Texture")
+            .finish()
+    }
+}
+
+/// Checks shared by the tests of the procedural textures
+#[cfg(test)]
+pub(crate) mod tests {
+    use rand::random;
+    use super::*;
+
+    pub(crate) const DARK: Color = Color { x: 0.1, y: 0.2, z: 0.0 };
+    pub(crate) const LIGHT: Color = Color { x: 0.9, y: 0.6, z: 1.0 };
+
+    /// Checks that the texture only ever blends between `DARK` and `LIGHT` over random points,
+    /// and returns how far along the blend each point was
+    pub(crate) fn assert_blends_between_colors(texture: &dyn Texture) -> Vec<f32> {
+        (0..2000)
+            .map(|_| {
+                let point: Vec3 = Vec3::new(random::<f32>() - 0.5, random::<f32>() - 0.5, random::<f32>() - 0.5) * 20.0;
+                let color: Color = texture.value((0.0, 0.0), &point);
+                let t: f32 = (color.z - DARK.z) / (LIGHT.z - DARK.z);
+                let blend: Color = DARK * (1.0 - t) + LIGHT * t;
+                assert!((-1e-5..=1.0 + 1e-5).contains(&t) && (color - blend).get_length() < 1e-4, "{:?} at {:?} is not a blend of the colors", color, point);
+                t
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,55 @@
+use std::sync::Arc;
+use crate::material::Color;
+use crate::texture::Texture;
+use crate::vec::Vec3;
+
+/// Alternating cubes of two textures filling space
+pub struct Checker {
+    even: Arc<dyn Texture>,
+    odd: Arc<dyn Texture>,
+    /// The size of each cube
+    scale: f32
+}
+
+impl Checker {
+    pub fn new(even: Arc<dyn Texture>, odd: Arc<dyn Texture>, scale: f32) -> Checker {
+        Checker {
+            even,
+            odd,
+            scale
+        }
+    }
+}
+
+impl Texture for Checker {
+    fn value(&self, uv: (f32, f32), point: &Vec3) -> Color {
+        let cell = |value: f32| -> i64 { (value / self.scale).floor() as i64 };
+        if (cell(point.x) + cell(point.y) + cell(point.z)).rem_euclid(2) == 0 {
+            self.even.value(uv, point)
+        } else {
+            self.odd.value(uv, point)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::texture::constant::Constant;
+
+    #[test]
+    fn neighbouring_cells_alternate_on_both_sides_of_zero() {
+        let checker: Checker = Checker::new(
+            Arc::new(Constant::new(Color::new(1.0, 1.0, 1.0))),
+            Arc::new(Constant::new(Color::new(0.0, 0.0, 0.0))),
+            0.5
+        );
+        let shade = |x: f32, y: f32, z: f32| -> f32 { checker.value((0.0, 0.0), &Vec3::new(x, y, z)).x };
+        assert_eq!(shade(0.1, 0.1, 0.1), 1.0);
+        assert_eq!(shade(0.6, 0.1, 0.1), 0.0);
+        assert_eq!(shade(-0.1, 0.1, 0.1), 0.0);
+        assert_eq!(shade(-0.1, -0.1, 0.1), 1.0);
+        assert_eq!(shade(-0.1, -0.1, -0.1), 0.0);
+        assert_eq!(shade(1.1, 0.6, -0.1), 1.0);
+    }
+}
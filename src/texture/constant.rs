@@ -0,0 +1,23 @@
+use crate::material::Color;
+use crate::texture::Texture;
+use crate::vec::Vec3;
+
+/// The same color everywhere
+#[derive(Debug, Copy, Clone)]
+pub struct Constant {
+    color: Color
+}
+
+impl Constant {
+    pub fn new(color: Color) -> Constant {
+        Constant {
+            color
+        }
+    }
+}
+
+impl Texture for Constant {
+    fn value(&self, _uv: (f32, f32), _point: &Vec3) -> Color {
+        self.color
+    }
+}
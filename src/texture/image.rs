@@ -0,0 +1,262 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use log::info;
+use serde::Deserialize;
+use crate::material::Color;
+use crate::texture::Texture;
+use crate::vec::Vec3;
+
+/// How texture coordinates outside 0 to 1 are brought back onto the image
+#[derive(Deserialize, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Wrap {
+    #[default]
+    Repeat,
+    Clamp,
+    Mirror
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Filter {
+    Nearest,
+    #[default]
+    Bilinear
+}
+
+/// Decoded pixels of a PNG file in linear color, top row first
+#[derive(Debug)]
+pub struct TextureImage {
+    width: usize,
+    height: usize,
+    pixels: Vec<Color>
+}
+
+impl TextureImage {
+    /// Loads a PNG file, `srgb` decodes the gamma of color images so the values are linear,
+    /// which should be turned off for data like normal maps
+    pub fn load(path: impl AsRef<Path>, srgb: bool) -> Result<TextureImage, Box<dyn Error>> {
+        let file: File = File::open(path)?;
+        let mut decoder = png::Decoder::new(BufReader::new(file));
+        // palettes are expanded and 16 bit channels cut down so every pixel is 8 bits a channel
+        decoder.set_transformations(png::Transformations::normalize_to_color8());
+        let mut reader = decoder.read_info()?;
+        let mut buffer: Vec<u8> = vec![0; reader.output_buffer_size()];
+        let frame: png::OutputInfo = reader.next_frame(&mut buffer)?;
+        let channels: usize = match frame.color_type {
+            png::ColorType::Grayscale => 1,
+            png::ColorType::GrayscaleAlpha => 2,
+            png::ColorType::Rgb => 3,
+            png::ColorType::Rgba => 4,
+            png::ColorType::Indexed => return Err("Indexed PNG was not expanded".into())
+        };
+        let decode: [f32; 256] = std::array::from_fn(|i| {
+            let value: f32 = i as f32 / 255.0;
+            if !srgb {
+                value
+            } else if value <= 0.04045 {
+                value / 12.92
+            } else {
+                ((value + 0.055) / 1.055).powf(2.4)
+            }
+        });
+        let width: usize = frame.width as usize;
+        let height: usize = frame.height as usize;
+        let pixels: Vec<Color> = buffer[..frame.buffer_size()]
+            .chunks(frame.line_size)
+            .flat_map(|row| row[..width * channels].chunks(channels))
+            .map(|pixel| {
+                // alpha is dropped, grey images fill every channel
+                if channels < 3 {
+                    let grey: f32 = decode[pixel[0] as usize];
+                    Color::new(grey, grey, grey)
+                } else {
+                    Color::new(decode[pixel[0] as usize], decode[pixel[1] as usize], decode[pixel[2] as usize])
+                }
+            })
+            .collect();
+        Ok(TextureImage {
+            width,
+            height,
+            pixels
+        })
+    }
+
+    /// An image from linear pixels laid out row by row, top row first
+    #[cfg(test)]
+    pub(crate) fn new(width: usize, height: usize, pixels: Vec<Color>) -> TextureImage {
+        assert_eq!(pixels.len(), width * height);
+        TextureImage {
+            width,
+            height,
+            pixels
+        }
+    }
+
+    pub fn get_width(&self) -> usize {
+        self.width
+    }
+
+    pub fn get_height(&self) -> usize {
+        self.height
+    }
+
+    pub fn get_pixel(&self, x: usize, y: usize) -> Color {
+        self.pixels[y * self.width + x]
+    }
+}
+
+/// Images already loaded while building a scene, so a file used by many materials is only
+/// decoded once
+#[derive(Debug, Default)]
+pub struct ImageCache {
+    images: RefCell<HashMap<(PathBuf, bool), Arc<TextureImage>>>
+}
+
+impl ImageCache {
+    pub fn load(&self, path: impl AsRef<Path>, srgb: bool) -> Result<Arc<TextureImage>, Box<dyn Error>> {
+        let key: (PathBuf, bool) = (path.as_ref().to_path_buf(), srgb);
+        if let Some(image) = self.images.borrow().get(&key) {
+            return Ok(image.clone());
+        }
+        let image: Arc<TextureImage> = Arc::new(TextureImage::load(&path, srgb)?);
+        info!("Loaded {}x{} texture {}", image.get_width(), image.get_height(), path.as_ref().display());
+        self.images.borrow_mut().insert(key, image.clone());
+        Ok(image)
+    }
+}
+
+pub struct ImageTexture {
+    image: Arc<TextureImage>,
+    wrap: Wrap,
+    filter: Filter
+}
+
+impl ImageTexture {
+    pub fn new(image: Arc<TextureImage>, wrap: Wrap, filter: Filter) -> ImageTexture {
+        ImageTexture {
+            image,
+            wrap,
+            filter
+        }
+    }
+
+    /// Brings a pixel index that may lie off the image back onto it
+    fn wrap_index(&self, index: i64, size: usize) -> usize {
+        let size: i64 = size as i64;
+        let index: i64 = match self.wrap {
+            Wrap::Repeat => index.rem_euclid(size),
+            Wrap::Clamp => index.clamp(0, size - 1),
+            Wrap::Mirror => {
+                let index: i64 = index.rem_euclid(2 * size);
+                if index < size {
+                    index
+                } else {
+                    2 * size - 1 - index
+                }
+            }
+        };
+        index as usize
+    }
+
+    fn texel(&self, x: i64, y: i64) -> Color {
+        self.image.get_pixel(
+            self.wrap_index(x, self.image.get_width()),
+            self.wrap_index(y, self.image.get_height())
+        )
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, uv: (f32, f32), _point: &Vec3) -> Color {
+        // v runs up the image while rows run down it
+        let x: f32 = uv.0 * self.image.get_width() as f32;
+        let y: f32 = (1.0 - uv.1) * self.image.get_height() as f32;
+        match self.filter {
+            Filter::Nearest => self.texel(x.floor() as i64, y.floor() as i64),
+            Filter::Bilinear => {
+                // blend the four texels whose centers surround the point
+                let x: f32 = x - 0.5;
+                let y: f32 = y - 0.5;
+                let x0: i64 = x.floor() as i64;
+                let y0: i64 = y.floor() as i64;
+                let fx: f32 = x - x0 as f32;
+                let fy: f32 = y - y0 as f32;
+                let top: Color = self.texel(x0, y0) * (1.0 - fx) + self.texel(x0 + 1, y0) * fx;
+                let bottom: Color = self.texel(x0, y0 + 1) * (1.0 - fx) + self.texel(x0 + 1, y0 + 1) * fx;
+                top * (1.0 - fy) + bottom * fy
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Four pixels in a row, each red value being the pixel's index
+    fn strip(wrap: Wrap, filter: Filter) -> ImageTexture {
+        let pixels: Vec<Color> = (0..4).map(|i| Color::new(i as f32, 0.0, 1.0)).collect();
+        ImageTexture::new(Arc::new(TextureImage::new(4, 1, pixels)), wrap, filter)
+    }
+
+    /// The red value at each horizontal texture coordinate
+    fn reds(texture: &ImageTexture, us: &[f32]) -> Vec<f32> {
+        us.iter().map(|u| texture.value((*u, 0.5), &Vec3::new(0.0, 0.0, 0.0)).x).collect()
+    }
+
+    #[test]
+    fn wrap_modes_bring_coordinates_back_onto_the_image() {
+        let us: [f32; 5] = [-0.375, 0.125, 0.875, 1.125, 1.625];
+        assert_eq!(reds(&strip(Wrap::Repeat, Filter::Nearest), &us), [2.0, 0.0, 3.0, 0.0, 2.0]);
+        assert_eq!(reds(&strip(Wrap::Clamp, Filter::Nearest), &us), [0.0, 0.0, 3.0, 3.0, 3.0]);
+        assert_eq!(reds(&strip(Wrap::Mirror, Filter::Nearest), &us), [1.0, 0.0, 3.0, 3.0, 1.0]);
+    }
+
+    #[test]
+    fn bilinear_filtering_blends_between_pixel_centers() {
+        let clamped: ImageTexture = strip(Wrap::Clamp, Filter::Bilinear);
+        // pixel centers keep their value, halfway between them is the average
+        assert_eq!(reds(&clamped, &[0.125, 0.375, 0.25, 0.3125]), [0.0, 1.0, 0.5, 0.75]);
+        // at the edges the far neighbour depends on the wrap
+        assert_eq!(reds(&clamped, &[0.0, 1.0]), [0.0, 3.0]);
+        assert_eq!(reds(&strip(Wrap::Repeat, Filter::Bilinear), &[0.0, 1.0]), [1.5, 1.5]);
+        assert_eq!(reds(&strip(Wrap::Mirror, Filter::Bilinear), &[0.0, 1.0]), [0.0, 3.0]);
+    }
+
+    #[test]
+    fn rows_run_down_the_image_while_v_runs_up() {
+        let pixels: Vec<Color> = vec![Color::new(1.0, 0.0, 0.0), Color::new(0.0, 1.0, 0.0)];
+        let texture: ImageTexture = ImageTexture::new(Arc::new(TextureImage::new(1, 2, pixels)), Wrap::Repeat, Filter::Nearest);
+        assert_eq!(texture.value((0.5, 0.9), &Vec3::new(0.0, 0.0, 0.0)).x, 1.0);
+        assert_eq!(texture.value((0.5, 0.1), &Vec3::new(0.0, 0.0, 0.0)).y, 1.0);
+    }
+
+    #[test]
+    fn loads_png_files_once_and_decodes_srgb() {
+        let path: PathBuf = std::env::temp_dir().join(format!("texture-test-{}.png", std::process::id()));
+        let mut encoder = png::Encoder::new(File::create(&path).unwrap(), 2, 1);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.write_header().unwrap().write_image_data(&[255, 188, 0, 10, 128, 64]).unwrap();
+
+        let cache: ImageCache = ImageCache::default();
+        let srgb: Arc<TextureImage> = cache.load(&path, true).unwrap();
+        let linear: Arc<TextureImage> = cache.load(&path, false).unwrap();
+        assert!(Arc::ptr_eq(&srgb, &cache.load(&path, true).unwrap()));
+        assert!(!Arc::ptr_eq(&srgb, &linear));
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!((srgb.get_width(), srgb.get_height()), (2, 1));
+        let bright: Color = srgb.get_pixel(0, 0);
+        assert!(bright.x == 1.0 && (bright.y - 0.5029).abs() < 1e-3 && bright.z == 0.0);
+        let dark: Color = srgb.get_pixel(1, 0);
+        assert!((dark.x - 10.0 / 255.0 / 12.92).abs() < 1e-6 && (dark.y - 0.2159).abs() < 1e-3);
+        assert!((linear.get_pixel(1, 0).y - 128.0 / 255.0).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,55 @@
+use crate::material::Color;
+use crate::texture::noise::{mix, Perlin};
+use crate::texture::Texture;
+use crate::vec::Vec3;
+
+/// Veins running across the x axis, bent by turbulence
+pub struct Marble {
+    perlin: Perlin,
+    colors: [Color; 2],
+    /// Veins per unit of distance
+    scale: f32,
+    octaves: u32,
+    /// How far the turbulence bends the veins
+    strength: f32
+}
+
+impl Marble {
+    pub fn new(seed: u64, colors: [Color; 2], scale: f32, octaves: u32, strength: f32) -> Marble {
+        Marble {
+            perlin: Perlin::new(seed),
+            colors,
+            scale,
+            octaves,
+            strength
+        }
+    }
+}
+
+impl Texture for Marble {
+    fn value(&self, _uv: (f32, f32), point: &Vec3) -> Color {
+        let point: Vec3 = *point * self.scale;
+        let phase: f32 = point.x + self.strength * self.perlin.turbulence(&point, self.octaves);
+        mix(&self.colors, 0.5 * (1.0 + phase.sin()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+    use super::*;
+    use crate::texture::tests::{assert_blends_between_colors, DARK, LIGHT};
+
+    #[test]
+    fn veins_run_across_x() {
+        assert_blends_between_colors(&Marble::new(2, [DARK, LIGHT], 1.5, 4, 3.0));
+        // without turbulence the veins are straight bands following a sine along x
+        let straight: Marble = Marble::new(2, [DARK, LIGHT], 2.0, 4, 0.0);
+        for (x, expected) in [(0.0, 0.5), (PI / 4.0, 1.0), (3.0 * PI / 4.0, 0.0)] {
+            for point in [Vec3::new(x, 0.0, 0.0), Vec3::new(x, 3.0, -5.0)] {
+                let color: Color = straight.value((0.0, 0.0), &point);
+                assert!((color.z - (DARK.z + (LIGHT.z - DARK.z) * expected)).abs() < 1e-4);
+            }
+        }
+    }
+}
@@ -0,0 +1,192 @@
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use crate::material::Color;
+use crate::texture::Texture;
+use crate::vec::Vec3;
+
+/// Gradient noise after Perlin's 2002 "Improving Noise", seeded so scenes render the same
+/// every time
+#[derive(Debug, Clone)]
+pub struct Perlin {
+    permutation: [u8; 512]
+}
+
+impl Perlin {
+    pub fn new(seed: u64) -> Perlin {
+        let mut values: Vec<u8> = (0..=255).collect();
+        values.shuffle(&mut StdRng::seed_from_u64(seed));
+        // doubled so lookups of a hash plus one never need wrapping
+        let permutation: [u8; 512] = std::array::from_fn(|i| values[i % 256]);
+        Perlin {
+            permutation
+        }
+    }
+
+    fn hash(&self, x: usize, y: usize, z: usize) -> u8 {
+        let p = |i: usize| -> usize { self.permutation[i] as usize };
+        self.permutation[p(p(x) + y) + z]
+    }
+
+    /// The dot product of one of twelve cube edge gradients with the offset to the corner
+    fn gradient(hash: u8, x: f32, y: f32, z: f32) -> f32 {
+        match hash & 15 {
+            0 | 12 => x + y,
+            1 | 14 => -x + y,
+            2 => x - y,
+            3 => -x - y,
+            4 => x + z,
+            5 => -x + z,
+            6 => x - z,
+            7 => -x - z,
+            8 => y + z,
+            9 | 13 => -y + z,
+            10 => y - z,
+            _ => -y - z
+        }
+    }
+
+    fn fade(t: f32) -> f32 {
+        t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+    }
+
+    fn lerp(t: f32, a: f32, b: f32) -> f32 {
+        a + t * (b - a)
+    }
+
+    /// Smooth noise at a point, roughly between -1 and 1
+    pub fn noise(&self, point: &Vec3) -> f32 {
+        let floor: Vec3 = Vec3::new(point.x.floor(), point.y.floor(), point.z.floor());
+        let cell = |value: f32| -> usize { (value as i64).rem_euclid(256) as usize };
+        let (xi, yi, zi) = (cell(floor.x), cell(floor.y), cell(floor.z));
+        let (x, y, z) = (point.x - floor.x, point.y - floor.y, point.z - floor.z);
+        let (u, v, w) = (Perlin::fade(x), Perlin::fade(y), Perlin::fade(z));
+        let corner = |dx: usize, dy: usize, dz: usize| -> f32 {
+            let hash: u8 = self.hash(xi + dx, yi + dy, zi + dz);
+            Perlin::gradient(hash, x - dx as f32, y - dy as f32, z - dz as f32)
+        };
+        Perlin::lerp(w,
+            Perlin::lerp(v,
+                Perlin::lerp(u, corner(0, 0, 0), corner(1, 0, 0)),
+                Perlin::lerp(u, corner(0, 1, 0), corner(1, 1, 0))
+            ),
+            Perlin::lerp(v,
+                Perlin::lerp(u, corner(0, 0, 1), corner(1, 0, 1)),
+                Perlin::lerp(u, corner(0, 1, 1), corner(1, 1, 1))
+            )
+        )
+    }
+
+    /// Fractional Brownian motion, octaves of noise each at double the frequency and half the
+    /// amplitude of the last
+    pub fn fbm(&self, point: &Vec3, octaves: u32) -> f32 {
+        let mut sum: f32 = 0.0;
+        let mut amplitude: f32 = 1.0;
+        let mut point: Vec3 = *point;
+        for _ in 0..octaves {
+            sum += amplitude * self.noise(&point);
+            amplitude *= 0.5;
+            point = point * 2.0;
+        }
+        sum
+    }
+
+    /// Like `fbm` but summing the absolute value of each octave, which gives billowy creases
+    pub fn turbulence(&self, point: &Vec3, octaves: u32) -> f32 {
+        let mut sum: f32 = 0.0;
+        let mut amplitude: f32 = 1.0;
+        let mut point: Vec3 = *point;
+        for _ in 0..octaves {
+            sum += amplitude * self.noise(&point).abs();
+            amplitude *= 0.5;
+            point = point * 2.0;
+        }
+        sum
+    }
+}
+
+/// Blends between two colors by fractal noise
+pub struct NoiseTexture {
+    perlin: Perlin,
+    colors: [Color; 2],
+    /// Features per unit of distance
+    scale: f32,
+    octaves: u32,
+    turbulence: bool
+}
+
+impl NoiseTexture {
+    pub fn new(seed: u64, colors: [Color; 2], scale: f32, octaves: u32, turbulence: bool) -> NoiseTexture {
+        NoiseTexture {
+            perlin: Perlin::new(seed),
+            colors,
+            scale,
+            octaves,
+            turbulence
+        }
+    }
+}
+
+impl Texture for NoiseTexture {
+    fn value(&self, _uv: (f32, f32), point: &Vec3) -> Color {
+        let point: Vec3 = *point * self.scale;
+        let t: f32 = if self.turbulence {
+            self.perlin.turbulence(&point, self.octaves)
+        } else {
+            0.5 * (1.0 + self.perlin.fbm(&point, self.octaves))
+        };
+        mix(&self.colors, t)
+    }
+}
+
+/// Linearly blends between two colors, clamping `t` between 0 and 1
+pub fn mix(colors: &[Color; 2], t: f32) -> Color {
+    let t: f32 = t.clamp(0.0, 1.0);
+    colors[0] * (1.0 - t) + colors[1] * t
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::random;
+    use super::*;
+    use crate::texture::tests::{assert_blends_between_colors, DARK, LIGHT};
+
+    #[test]
+    fn noise_is_smooth_and_vanishes_on_the_lattice() {
+        let perlin: Perlin = Perlin::new(7);
+        for _ in 0..2000 {
+            let point: Vec3 = Vec3::new(random::<f32>() - 0.5, random::<f32>() - 0.5, random::<f32>() - 0.5) * 600.0;
+            let value: f32 = perlin.noise(&point);
+            assert!(value.abs() <= 1.1, "noise of {} at {:?}", value, point);
+            // the gradients are at most two long so the noise can't change faster than that
+            let nudged: f32 = perlin.noise(&(point + Vec3::new(1e-3, -1e-3, 1e-3)));
+            assert!((nudged - value).abs() < 1e-2);
+            let lattice: Vec3 = Vec3::new(point.x.floor(), point.y.floor(), point.z.floor());
+            assert!(perlin.noise(&lattice).abs() < 1e-6);
+            assert!(perlin.turbulence(&point, 4) >= 0.0 && perlin.fbm(&point, 4).abs() <= 2.2);
+        }
+    }
+
+    #[test]
+    fn seeds_give_repeatable_and_different_noise() {
+        let point: Vec3 = Vec3::new(1.3, -2.7, 0.4);
+        assert_eq!(Perlin::new(3).noise(&point), Perlin::new(3).noise(&point));
+        let differing: usize = (0..16)
+            .filter(|i| {
+                let point: Vec3 = point + Vec3::new(*i as f32 * 0.37, 0.0, 0.0);
+                Perlin::new(3).noise(&point) != Perlin::new(4).noise(&point)
+            })
+            .count();
+        assert!(differing > 12);
+    }
+
+    #[test]
+    fn noise_textures_stay_between_their_colors() {
+        let smooth: Vec<f32> = assert_blends_between_colors(&NoiseTexture::new(1, [DARK, LIGHT], 0.7, 5, false));
+        // fractal noise sits around the middle of the blend and turbulence towards the dark end
+        let mean: f32 = smooth.iter().sum::<f32>() / smooth.len() as f32;
+        assert!((mean - 0.5).abs() < 0.05, "fractal noise averages {}", mean);
+        let turbulent: Vec<f32> = assert_blends_between_colors(&NoiseTexture::new(1, [DARK, LIGHT], 0.7, 5, true));
+        assert!(turbulent.iter().sum::<f32>() / (turbulent.len() as f32) < mean);
+    }
+}
@@ -0,0 +1,99 @@
+use crate::material::Color;
+use crate::texture::noise::mix;
+use crate::texture::Texture;
+use crate::vec::Vec3;
+
+/// Cells around randomly placed points, one point in each unit cube, each cell shaded with
+/// its own blend of two colors and optionally outlined
+pub struct Voronoi {
+    seed: u64,
+    colors: [Color; 2],
+    /// Cells per unit of distance
+    scale: f32,
+    /// Outlines are drawn where the nearest two points are closer together than this
+    edge_width: f32,
+    edge_color: Color
+}
+
+impl Voronoi {
+    pub fn new(seed: u64, colors: [Color; 2], scale: f32, edge_width: f32, edge_color: Color) -> Voronoi {
+        Voronoi {
+            seed,
+            colors,
+            scale,
+            edge_width,
+            edge_color
+        }
+    }
+
+    /// Four pseudo random numbers from 0 to 1 for a cell, the position of its point and its shade
+    fn cell_random(&self, x: i64, y: i64, z: i64) -> [f32; 4] {
+        let mut hash: u64 = self.seed
+            ^ (x as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15)
+            ^ (y as u64).wrapping_mul(0xc2b2_ae3d_27d4_eb4f)
+            ^ (z as u64).wrapping_mul(0x1656_67b1_9e37_79f9);
+        std::array::from_fn(|_| {
+            // splitmix64 steps
+            hash = hash.wrapping_add(0x9e37_79b9_7f4a_7c15);
+            let mut value: u64 = hash;
+            value = (value ^ (value >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+            value = (value ^ (value >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+            value ^= value >> 31;
+            (value >> 40) as f32 / (1u64 << 24) as f32
+        })
+    }
+}
+
+impl Texture for Voronoi {
+    fn value(&self, _uv: (f32, f32), point: &Vec3) -> Color {
+        let point: Vec3 = *point * self.scale;
+        let cell: [i64; 3] = [point.x.floor() as i64, point.y.floor() as i64, point.z.floor() as i64];
+        // the nearest and second nearest points, searching the neighbouring cells
+        let mut nearest: (f32, f32) = (f32::INFINITY, 0.0);
+        let mut second_distance: f32 = f32::INFINITY;
+        for dx in -1..=1 {
+            for dy in -1..=1 {
+                for dz in -1..=1 {
+                    let (x, y, z) = (cell[0] + dx, cell[1] + dy, cell[2] + dz);
+                    let random: [f32; 4] = self.cell_random(x, y, z);
+                    let feature: Vec3 = Vec3::new(x as f32 + random[0], y as f32 + random[1], z as f32 + random[2]);
+                    let distance: f32 = (feature - point).get_length();
+                    if distance < nearest.0 {
+                        second_distance = nearest.0;
+                        nearest = (distance, random[3]);
+                    } else if distance < second_distance {
+                        second_distance = distance;
+                    }
+                }
+            }
+        }
+        if second_distance - nearest.0 < self.edge_width {
+            return self.edge_color;
+        }
+        mix(&self.colors, nearest.1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::texture::tests::{assert_blends_between_colors, DARK, LIGHT};
+
+    #[test]
+    fn cells_are_flat_shaded_and_outlined() {
+        assert_blends_between_colors(&Voronoi::new(9, [DARK, LIGHT], 1.3, 0.0, DARK));
+        let outlined: Voronoi = Voronoi::new(9, [DARK, LIGHT], 1.0, 0.1, Color::new(5.0, 5.0, 5.0));
+        // right on a cell's point its own shade shows and stays the same close by
+        let random: [f32; 4] = outlined.cell_random(2, -1, 0);
+        let feature: Vec3 = Vec3::new(2.0 + random[0], -1.0 + random[1], random[2]);
+        let expected: Color = mix(&[DARK, LIGHT], random[3]);
+        for offset in [Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.01, -0.01, 0.01)] {
+            assert!((outlined.value((0.0, 0.0), &(feature + offset)) - expected).get_length() < 1e-6);
+        }
+        // walking away from the point crosses an outline before reaching another cell
+        let crossed: bool = (0..200)
+            .map(|i| feature + Vec3::new(i as f32 * 0.01, 0.0, 0.0))
+            .any(|point| outlined.value((0.0, 0.0), &point).x == 5.0);
+        assert!(crossed);
+    }
+}
@@ -0,0 +1,58 @@
+use crate::material::Color;
+use crate::texture::noise::{mix, Perlin};
+use crate::texture::Texture;
+use crate::vec::Vec3;
+
+/// Growth rings around the z axis, made uneven by noise
+pub struct Wood {
+    perlin: Perlin,
+    colors: [Color; 2],
+    /// Rings per unit of distance from the axis
+    rings: f32,
+    /// The size of the wobbles in the rings
+    scale: f32,
+    octaves: u32,
+    /// How far the noise pushes the rings
+    strength: f32
+}
+
+impl Wood {
+    pub fn new(seed: u64, colors: [Color; 2], rings: f32, scale: f32, octaves: u32, strength: f32) -> Wood {
+        Wood {
+            perlin: Perlin::new(seed),
+            colors,
+            rings,
+            scale,
+            octaves,
+            strength
+        }
+    }
+}
+
+impl Texture for Wood {
+    fn value(&self, _uv: (f32, f32), point: &Vec3) -> Color {
+        let radius: f32 = (point.x * point.x + point.y * point.y).sqrt();
+        let ring: f32 = radius * self.rings + self.strength * self.perlin.fbm(&(*point * self.scale), self.octaves);
+        // sharpen each ring so the dark late wood is thinner than the light early wood
+        let t: f32 = ring.rem_euclid(1.0).powi(3);
+        mix(&self.colors, t)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::texture::tests::{assert_blends_between_colors, DARK, LIGHT};
+
+    #[test]
+    fn rings_repeat_outwards_from_the_z_axis() {
+        assert_blends_between_colors(&Wood::new(5, [DARK, LIGHT], 4.0, 2.0, 3, 0.5));
+        // without noise every point the same distance from the axis is the same shade and the
+        // shade repeats every ring
+        let even: Wood = Wood::new(5, [DARK, LIGHT], 4.0, 2.0, 3, 0.0);
+        let shade = |point: Vec3| -> f32 { even.value((0.0, 0.0), &point).z };
+        assert!((shade(Vec3::new(0.3, 0.0, 0.0)) - shade(Vec3::new(0.0, -0.3, 7.0))).abs() < 1e-5);
+        assert!((shade(Vec3::new(0.3, 0.0, 0.0)) - shade(Vec3::new(0.55, 0.0, 0.0))).abs() < 1e-4);
+        assert!(shade(Vec3::new(0.2, 0.0, 0.0)) < shade(Vec3::new(0.245, 0.0, 0.0)));
+    }
+}
@@ -1,5 +1,4 @@
 use std::ops::{Add, AddAssign, Div, Mul, Neg, Sub};
-use rand::random;
 use serde::Deserialize;
 use crate::util::q_rsqrt;
 
@@ -19,11 +18,6 @@ impl Vec3 {
         Vec3 { x: 0.0, y: 0.0, z: 0.0 }
     }
 
-    pub fn random_in_unit_sphere() -> Vec3 {
-        let vector: Vec3 = Vec3::new(random::<f32>(), random::<f32>(), random::<f32>());
-        vector.get_normalized()
-    }
-
     pub fn near_zero(&self) -> bool {
         let s: f32 = 1e-8;
         self.x.abs() < s && self.y.abs() < s && self.z.abs() < s
@@ -82,6 +76,44 @@ impl Vec3 {
         self * rhs
     }
 
+    pub fn min(&self, rhs: &Vec3) -> Vec3 {
+        Vec3 { x: self.x.min(rhs.x), y: self.y.min(rhs.y), z: self.z.min(rhs.z) }
+    }
+
+    pub fn max(&self, rhs: &Vec3) -> Vec3 {
+        Vec3 { x: self.x.max(rhs.x), y: self.y.max(rhs.y), z: self.z.max(rhs.z) }
+    }
+
+    /// Builds two unit vectors that together with this unit vector form an orthonormal basis
+    pub fn orthonormal_basis(&self) -> (Vec3, Vec3) {
+        // Duff et al. 2017, "Building an Orthonormal Basis, Revisited"
+        let sign: f32 = 1.0_f32.copysign(self.z);
+        let a: f32 = -1.0 / (sign + self.z);
+        let b: f32 = self.x * self.y * a;
+        (
+            Vec3::new(1.0 + sign * self.x * self.x * a, sign * b, -sign * self.x),
+            Vec3::new(b, sign + self.y * self.y * a, -self.y)
+        )
+    }
+
+    /// The relative luminance when the vector holds a linear RGB color
+    pub fn luminance(&self) -> f32 {
+        0.2126 * self.x + 0.7152 * self.y + 0.0722 * self.z
+    }
+
+    pub fn max_component(&self) -> f32 {
+        self.x.max(self.y).max(self.z)
+    }
+
+    /// Returns the component along the given axis, 0 for x, 1 for y and 2 for z
+    pub fn get_axis(&self, axis: usize) -> f32 {
+        match axis {
+            0 => self.x,
+            1 => self.y,
+            _ => self.z
+        }
+    }
+
     pub fn rotate(&mut self, rotation: Vec3) {
         let x: f32 = self.x;
         let y: f32 = self.y;